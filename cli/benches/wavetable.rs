@@ -0,0 +1,32 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use procsynth_cli::wavetable::{lookup, sine_table};
+
+const SAMPLES: usize = 44100;
+
+fn phases() -> Vec<f32> {
+    (0..SAMPLES)
+        .map(|i| 2.0 * std::f32::consts::PI * 330.0 * i as f32 / 44100.0)
+        .collect()
+}
+
+fn bench_sine(c: &mut Criterion) {
+    let phases = phases();
+    let table = sine_table();
+
+    let mut group = c.benchmark_group("sine_1s_at_44100");
+    group.bench_function("f32::sin", |b| {
+        b.iter(|| phases.iter().map(|&p| black_box(p).sin()).sum::<f32>())
+    });
+    group.bench_function("wavetable", |b| {
+        b.iter(|| {
+            phases
+                .iter()
+                .map(|&p| lookup(table, black_box(p)))
+                .sum::<f32>()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_sine);
+criterion_main!(benches);
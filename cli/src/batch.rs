@@ -0,0 +1,157 @@
+//! `procsynth batch`: renders every JSON config in a directory.
+//!
+//! Each `<name>.json` is rendered to `<out-dir>/<name>.<format>`; the
+//! config's own `output` is ignored so a directory of variations can't
+//! overwrite each other. Configs render in parallel, and one that fails to
+//! load or render is reported without stopping the rest.
+
+use crate::{Error, Generator, JsonConfig};
+use rayon::prelude::*;
+use std::error::Error as _;
+use std::path::{Path, PathBuf};
+
+/// Outcome of a batch render
+#[derive(Debug, Default)]
+pub struct BatchReport {
+    /// Files written, in config name order
+    pub rendered: Vec<PathBuf>,
+    /// Configs that failed, with the reason
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+/// `.json` files directly inside `dir`, sorted by name
+fn config_files(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut configs = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_json = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+        if is_json && path.is_file() {
+            configs.push(path);
+        }
+    }
+    configs.sort();
+    Ok(configs)
+}
+
+fn render_config(config: &Path, out_dir: &Path) -> Result<PathBuf, Error> {
+    let mut params = JsonConfig::from_file(config)?.to_params()?;
+    let stem = config.file_stem().unwrap_or_default();
+    let output = out_dir.join(stem).with_extension(params.format.as_str());
+    params.filename = output.to_string_lossy().into_owned();
+    Generator::new(params)?.run()?;
+    Ok(output)
+}
+
+/// Renders every config in `config_dir` into `out_dir`, creating it if needed
+pub fn render_dir(
+    config_dir: impl AsRef<Path>,
+    out_dir: impl AsRef<Path>,
+) -> std::io::Result<BatchReport> {
+    let configs = config_files(config_dir.as_ref())?;
+    let out_dir = out_dir.as_ref();
+    std::fs::create_dir_all(out_dir)?;
+
+    let results: Vec<_> = configs
+        .par_iter()
+        .map(|config| render_config(config, out_dir))
+        .collect();
+    let mut report = BatchReport::default();
+    for (config, result) in configs.into_iter().zip(results) {
+        match result {
+            Ok(output) => report.rendered.push(output),
+            Err(err) => {
+                let reason = match err.source() {
+                    Some(source) => format!("{err}: {source}"),
+                    None => err.to_string(),
+                };
+                report.failed.push((config, reason));
+            }
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::normalize::{match_loudness, NormalizeMode};
+
+    #[test]
+    fn test_renders_each_config_and_counts_failures() {
+        let root = std::env::temp_dir().join(format!("{}_batch", uuid::Uuid::new_v4()));
+        let (configs, out) = (root.join("configs"), root.join("out"));
+        std::fs::create_dir_all(&configs).unwrap();
+        for (name, voices) in [("first", 2), ("second", 3)] {
+            let config = JsonConfig {
+                duration: 0.2,
+                voices,
+                attack: 0.05,
+                release: 0.05,
+                seed: Some(1),
+                ..JsonConfig::default()
+            };
+            config
+                .to_file(configs.join(format!("{name}.json")))
+                .unwrap();
+        }
+        std::fs::write(configs.join("broken.json"), "{ not json").unwrap();
+        let typo = JsonConfig {
+            chord: Some("Cmaj9x".to_string()),
+            ..JsonConfig::default()
+        };
+        typo.to_file(configs.join("typo.json")).unwrap();
+        std::fs::write(configs.join("notes.txt"), "skipped").unwrap();
+
+        let report = render_dir(&configs, &out).unwrap();
+        assert_eq!(
+            report.rendered,
+            [out.join("first.wav"), out.join("second.wav")]
+        );
+        assert_eq!(report.failed.len(), 2);
+        assert_eq!(report.failed[0].0, configs.join("broken.json"));
+        assert!(report.failed[0].1.contains("JSON"));
+        assert_eq!(report.failed[1].0, configs.join("typo.json"));
+        assert!(report.failed[1].1.contains("Invalid chord"));
+        for file in &report.rendered {
+            assert!(hound::WavReader::open(file).unwrap().len() > 0);
+        }
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_loudness_match_evens_out_levels() {
+        let root = std::env::temp_dir().join(format!("{}_match", uuid::Uuid::new_v4()));
+        let (configs, out) = (root.join("configs"), root.join("out"));
+        std::fs::create_dir_all(&configs).unwrap();
+        for (name, gain_db) in [("loud", 0.0), ("quiet", -14.0)] {
+            let config = JsonConfig {
+                duration: 0.5,
+                attack: 0.05,
+                release: 0.05,
+                gain_db,
+                seed: Some(3),
+                ..JsonConfig::default()
+            };
+            config
+                .to_file(configs.join(format!("{name}.json")))
+                .unwrap();
+        }
+        let report = render_dir(&configs, &out).unwrap();
+        let rms = |path: &Path| {
+            let (_, samples) = crate::info::read_samples(path).unwrap();
+            20.0 * NormalizeMode::Rms.level(&samples).log10()
+        };
+        let before: Vec<f32> = report.rendered.iter().map(|p| rms(p)).collect();
+        assert!(before[0] - before[1] > 10.0);
+
+        let target = match_loudness(&report.rendered, None).unwrap();
+        let after: Vec<f32> = report.rendered.iter().map(|p| rms(p)).collect();
+        assert!((after[0] - after[1]).abs() < 0.1, "{after:?}");
+        assert!((after[0] - target).abs() < 0.1);
+        // the common level sits between the two, below the loud one's
+        assert!(target < before[0] && target > before[1]);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
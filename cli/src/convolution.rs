@@ -0,0 +1,160 @@
+//! Convolution reverb for `--reverb-ir`.
+//!
+//! The send is convolved with a recorded impulse response by uniformly
+//! partitioned FFT convolution: the response is cut into blocks, each block
+//! of input is transformed once, and every output block is the sum of the
+//! recent input spectra times the matching response partitions. That keeps
+//! the cost per sample proportional to the log of the block size rather than
+//! the length of the response, so multi-second halls stay cheap.
+
+use crate::info::read_samples;
+use rustfft::{num_complex::Complex, FftPlanner};
+use std::collections::VecDeque;
+use std::path::Path;
+
+/// Samples per partition; the FFTs are twice this long
+const BLOCK: usize = 1024;
+
+/// Loads an impulse response as left and right channels at `sample_rate`.
+///
+/// Mono responses feed both sides and channels past the second are
+/// ignored. Other sample rates are resampled linearly. The response is
+/// scaled so its louder channel has unit energy, which keeps the wet signal
+/// near the level of the dry one whatever the recording's gain.
+pub fn load_impulse_response(
+    path: impl AsRef<Path>,
+    sample_rate: u32,
+) -> Result<(Vec<f32>, Vec<f32>), hound::Error> {
+    let (spec, samples) = read_samples(path)?;
+    let channels = spec.channels.max(1) as usize;
+    let channel = |c: usize| -> Vec<f32> {
+        let samples: Vec<f32> = samples
+            .chunks_exact(channels)
+            .map(|frame| frame[c.min(channels - 1)])
+            .collect();
+        resample(&samples, spec.sample_rate, sample_rate)
+    };
+    let (mut left, mut right) = (channel(0), channel(1));
+    let energy = |samples: &[f32]| samples.iter().map(|s| s * s).sum::<f32>();
+    let loudest = energy(&left).max(energy(&right));
+    if loudest > 0.0 {
+        let scale = loudest.sqrt().recip();
+        for sample in left.iter_mut().chain(&mut right) {
+            *sample *= scale;
+        }
+    }
+    Ok((left, right))
+}
+
+/// `samples` at `from` Hz linearly interpolated to `to` Hz
+fn resample(samples: &[f32], from: u32, to: u32) -> Vec<f32> {
+    if from == to || samples.is_empty() {
+        return samples.to_vec();
+    }
+    let step = from as f64 / to as f64;
+    let len = ((samples.len() as f64) / step).ceil() as usize;
+    (0..len)
+        .map(|i| {
+            let position = i as f64 * step;
+            let index = position as usize;
+            let frac = (position - index as f64) as f32;
+            let a = samples[index.min(samples.len() - 1)];
+            let b = samples[(index + 1).min(samples.len() - 1)];
+            a + (b - a) * frac
+        })
+        .collect()
+}
+
+/// `input` convolved with `response`, cut to the length of `input`
+pub fn convolve(input: &[f32], response: &[f32]) -> Vec<f32> {
+    if response.is_empty() {
+        return vec![0.0; input.len()];
+    }
+    let size = 2 * BLOCK;
+    let mut planner = FftPlanner::new();
+    let fft = planner.plan_fft_forward(size);
+    let ifft = planner.plan_fft_inverse(size);
+    let spectrum = |block: &[f32]| {
+        let mut buffer = vec![Complex::default(); size];
+        for (bin, &sample) in buffer.iter_mut().zip(block) {
+            bin.re = sample;
+        }
+        fft.process(&mut buffer);
+        buffer
+    };
+    let partitions: Vec<Vec<Complex<f32>>> = response.chunks(BLOCK).map(spectrum).collect();
+
+    // the latest input spectra, newest first, one per partition
+    let mut history: VecDeque<Vec<Complex<f32>>> = VecDeque::with_capacity(partitions.len());
+    let mut output = vec![0.0; input.len() + size];
+    let mut sum = vec![Complex::default(); size];
+    for (j, block) in input.chunks(BLOCK).enumerate() {
+        history.push_front(spectrum(block));
+        history.truncate(partitions.len());
+        sum.fill(Complex::default());
+        for (x, h) in history.iter().zip(&partitions) {
+            for ((bin, x), h) in sum.iter_mut().zip(x).zip(h) {
+                *bin += x * h;
+            }
+        }
+        ifft.process(&mut sum);
+        // each block's result overlaps the next one by a block
+        for (out, bin) in output[j * BLOCK..].iter_mut().zip(&sum) {
+            *out += bin.re / size as f32;
+        }
+    }
+    output.truncate(input.len());
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unit_impulse_passes_through() {
+        let input: Vec<f32> = (0..5000).map(|i| (i as f32 * 0.01).sin()).collect();
+        let output = convolve(&input, &[1.0]);
+        assert_eq!(output.len(), input.len());
+        for (a, b) in input.iter().zip(&output) {
+            assert!((a - b).abs() < 1e-5);
+        }
+
+        // a delayed impulse delays, across partition boundaries too
+        let mut delayed = vec![0.0; 1500];
+        delayed[1500 - 1] = 0.5;
+        let output = convolve(&input, &delayed);
+        for i in 1499..input.len() {
+            assert!((output[i] - 0.5 * input[i - 1499]).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_decaying_response_leaves_a_decaying_tail() {
+        let response: Vec<f32> = (0..20_000)
+            .map(|i| (-(i as f32) / 2000.0).exp() * if i % 2 == 0 { 1.0 } else { -1.0 })
+            .collect();
+        // a short burst followed by silence
+        let mut input = vec![0.0; 30_000];
+        input[..100].fill(0.5);
+        let output = convolve(&input, &response);
+        let rms = |range: std::ops::Range<usize>| {
+            (output[range.clone()].iter().map(|s| s * s).sum::<f32>() / range.len() as f32).sqrt()
+        };
+        let levels: Vec<f32> = (0..4)
+            .map(|i| rms(1000 + i * 4000..5000 + i * 4000))
+            .collect();
+        assert!(levels[3] > 0.0);
+        for pair in levels.windows(2) {
+            assert!(pair[1] < pair[0] * 0.5, "{levels:?}");
+        }
+    }
+
+    #[test]
+    fn test_resample_keeps_duration() {
+        let samples: Vec<f32> = (0..480).map(|i| i as f32).collect();
+        let resampled = resample(&samples, 48000, 44100);
+        assert_eq!(resampled.len(), 441);
+        assert!((resampled[100] - 100.0 * 48000.0 / 44100.0).abs() < 1e-3);
+    }
+}
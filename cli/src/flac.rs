@@ -0,0 +1,335 @@
+//! Minimal FLAC encoder for `--format flac`.
+//!
+//! Writes a STREAMINFO block followed by fixed-size frames. Every channel is
+//! coded independently with whichever fixed polynomial predictor (order 0-4)
+//! leaves the smallest residual, and residuals are Rice coded with a single
+//! partition per subframe. That misses the last few percent a full encoder
+//! squeezes out with LPC and stereo decorrelation, but slow, smooth ambient
+//! material is exactly what the fixed predictors handle well.
+
+use std::io::{self, Write};
+
+/// Samples per channel in each frame
+pub const BLOCK_SIZE: usize = 4096;
+
+/// Largest Rice parameter allowed with the 4-bit parameter field
+const MAX_RICE_PARAM: u32 = 14;
+
+/// MSB-first bit packer
+struct BitWriter {
+    bytes: Vec<u8>,
+    acc: u64,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn new() -> BitWriter {
+        BitWriter {
+            bytes: Vec::new(),
+            acc: 0,
+            bits: 0,
+        }
+    }
+
+    /// Appends the low `count` bits of `value` (at most 32)
+    fn write(&mut self, value: u64, count: u32) {
+        if count == 0 {
+            return;
+        }
+        let mask = (1u64 << count) - 1;
+        self.acc = (self.acc << count) | (value & mask);
+        self.bits += count;
+        while self.bits >= 8 {
+            self.bits -= 8;
+            self.bytes.push((self.acc >> self.bits) as u8);
+        }
+        self.acc &= (1u64 << self.bits) - 1;
+    }
+
+    fn write_signed(&mut self, value: i64, count: u32) {
+        self.write(value as u64, count);
+    }
+
+    /// Writes `count` zero bits followed by a one
+    fn write_unary(&mut self, mut count: u64) {
+        while count >= 32 {
+            self.write(0, 32);
+            count -= 32;
+        }
+        self.write(1, count as u32 + 1);
+    }
+
+    /// Pads the last partial byte with zeros
+    fn align(&mut self) {
+        if self.bits > 0 {
+            self.write(0, 8 - self.bits);
+        }
+    }
+
+    fn into_bytes(mut self) -> Vec<u8> {
+        self.align();
+        self.bytes
+    }
+}
+
+fn crc8(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |mut crc, &byte| {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    })
+}
+
+fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0u16, |mut crc, &byte| {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x8005
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    })
+}
+
+/// Frame numbers use the same variable-length coding as UTF-8
+fn write_utf8_number(out: &mut BitWriter, value: u32) {
+    if value < 0x80 {
+        out.write(value as u64, 8);
+        return;
+    }
+    let mut continuation = Vec::new();
+    let mut rest = value;
+    let mut prefix_room = 6;
+    loop {
+        continuation.push(0x80 | (rest & 0x3F));
+        rest >>= 6;
+        prefix_room -= 1;
+        if rest < (1 << prefix_room) {
+            break;
+        }
+    }
+    let count = continuation.len() as u32 + 1;
+    let lead = (0xFFu32 << (8 - count)) & 0xFF;
+    out.write((lead | rest) as u64, 8);
+    for byte in continuation.iter().rev() {
+        out.write(*byte as u64, 8);
+    }
+}
+
+/// Residual of the fixed polynomial predictor of `order` at `i`
+fn fixed_residual(samples: &[i64], order: usize, i: usize) -> i64 {
+    let s = |back: usize| samples[i - back];
+    match order {
+        0 => s(0),
+        1 => s(0) - s(1),
+        2 => s(0) - 2 * s(1) + s(2),
+        3 => s(0) - 3 * s(1) + 3 * s(2) - s(3),
+        _ => s(0) - 4 * s(1) + 6 * s(2) - 4 * s(3) + s(4),
+    }
+}
+
+fn zigzag(residual: i64) -> u64 {
+    ((residual << 1) ^ (residual >> 63)) as u64
+}
+
+/// Rice parameter with the fewest total bits, and that bit count
+fn best_rice_param(folded: &[u64]) -> (u32, u64) {
+    (0..=MAX_RICE_PARAM)
+        .map(|k| {
+            let bits: u64 = folded.iter().map(|u| (u >> k) + 1 + k as u64).sum();
+            (k, bits)
+        })
+        .min_by_key(|&(_, bits)| bits)
+        .unwrap_or((0, 0))
+}
+
+fn write_subframe(out: &mut BitWriter, samples: &[i64], bits_per_sample: u32) {
+    let max_order = 4.min(samples.len().saturating_sub(1));
+    let (order, param, folded) = (0..=max_order)
+        .map(|order| {
+            let folded: Vec<u64> = (order..samples.len())
+                .map(|i| zigzag(fixed_residual(samples, order, i)))
+                .collect();
+            let (param, bits) = best_rice_param(&folded);
+            (
+                order,
+                param,
+                folded,
+                bits + order as u64 * bits_per_sample as u64,
+            )
+        })
+        .min_by_key(|candidate| candidate.3)
+        .map(|(order, param, folded, _)| (order, param, folded))
+        .expect("at least one predictor order");
+
+    // zero pad bit, FIXED subframe type 0b001xxx, no wasted bits
+    out.write(0, 1);
+    out.write(0b001000 | order as u64, 6);
+    out.write(0, 1);
+    for &warm_up in &samples[..order] {
+        out.write_signed(warm_up, bits_per_sample);
+    }
+    // Rice coding with 4-bit parameters, partition order 0
+    out.write(0, 2);
+    out.write(0, 4);
+    out.write(param as u64, 4);
+    for u in folded {
+        out.write_unary(u >> param);
+        out.write(u, param);
+    }
+}
+
+fn sample_size_code(bits_per_sample: u32) -> u64 {
+    match bits_per_sample {
+        8 => 0b001,
+        12 => 0b010,
+        16 => 0b100,
+        20 => 0b101,
+        24 => 0b110,
+        _ => 0b000,
+    }
+}
+
+/// Encodes interleaved `samples` (already quantized to `bits_per_sample`)
+/// as a FLAC stream.
+pub fn write_flac<W: Write>(
+    mut writer: W,
+    samples: &[i32],
+    channels: u8,
+    sample_rate: u32,
+    bits_per_sample: u32,
+) -> io::Result<()> {
+    let channel_count = channels as usize;
+    let frames = samples.len() / channel_count.max(1);
+
+    let mut info = BitWriter::new();
+    info.write(BLOCK_SIZE as u64, 16);
+    info.write(BLOCK_SIZE as u64, 16);
+    // unknown minimum and maximum frame sizes
+    info.write(0, 24);
+    info.write(0, 24);
+    info.write(sample_rate as u64, 20);
+    info.write(channels as u64 - 1, 3);
+    info.write(bits_per_sample as u64 - 1, 5);
+    info.write((frames as u64) >> 32, 4);
+    info.write(frames as u64, 32);
+    // no MD5 signature
+    info.write(0, 32);
+    info.write(0, 32);
+    info.write(0, 32);
+    info.write(0, 32);
+    let info = info.into_bytes();
+
+    writer.write_all(b"fLaC")?;
+    // last-metadata-block flag, STREAMINFO type, length
+    writer.write_all(&[0x80, 0, 0, info.len() as u8])?;
+    writer.write_all(&info)?;
+
+    for (number, block) in samples.chunks(BLOCK_SIZE * channel_count).enumerate() {
+        let block_len = block.len() / channel_count;
+        let mut frame = BitWriter::new();
+        frame.write(0b11_1111_1111_1110, 14);
+        // reserved, fixed blocking strategy
+        frame.write(0, 2);
+        // block size given as a 16-bit value after the header
+        frame.write(0b0111, 4);
+        // sample rate taken from STREAMINFO
+        frame.write(0b0000, 4);
+        // independently coded channels
+        frame.write(channels as u64 - 1, 4);
+        frame.write(sample_size_code(bits_per_sample), 3);
+        frame.write(0, 1);
+        write_utf8_number(&mut frame, number as u32);
+        frame.write(block_len as u64 - 1, 16);
+        let header = frame.into_bytes();
+
+        let mut frame = BitWriter::new();
+        for &byte in &header {
+            frame.write(byte as u64, 8);
+        }
+        frame.write(crc8(&header) as u64, 8);
+        for channel in 0..channel_count {
+            let channel_samples: Vec<i64> = block
+                .iter()
+                .skip(channel)
+                .step_by(channel_count)
+                .map(|&s| s as i64)
+                .collect();
+            write_subframe(&mut frame, &channel_samples, bits_per_sample);
+        }
+        let mut bytes = frame.into_bytes();
+        let crc = crc16(&bytes);
+        bytes.extend_from_slice(&crc.to_be_bytes());
+        writer.write_all(&bytes)?;
+    }
+
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(bytes: &[u8]) -> (claxon::metadata::StreamInfo, Vec<i32>) {
+        let mut reader = claxon::FlacReader::new(bytes).unwrap();
+        let info = reader.streaminfo();
+        let samples = reader.samples().map(|s| s.unwrap()).collect();
+        (info, samples)
+    }
+
+    #[test]
+    fn test_round_trip_is_lossless() {
+        // a few blocks of a stereo sine with an uneven tail and a noisy channel
+        let frames = BLOCK_SIZE * 3 + 123;
+        let mut state = 12345u32;
+        let samples: Vec<i32> = (0..frames)
+            .flat_map(|i| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                let sine = ((i as f32 * 0.01).sin() * 30_000.0) as i32;
+                let noise = (state >> 16) as i32 - 32_768;
+                [sine, noise]
+            })
+            .collect();
+
+        let mut bytes = Vec::new();
+        write_flac(&mut bytes, &samples, 2, 44100, 16).unwrap();
+        let (info, decoded) = decode(&bytes);
+        assert_eq!(info.sample_rate, 44100);
+        assert_eq!(info.channels, 2);
+        assert_eq!(info.bits_per_sample, 16);
+        assert_eq!(info.samples, Some(frames as u64));
+        assert_eq!(decoded, samples);
+    }
+
+    #[test]
+    fn test_24_bit_mono_and_compression() {
+        let samples: Vec<i32> = (0..BLOCK_SIZE * 2)
+            .map(|i| ((i as f32 * 0.003).sin() * 8_000_000.0) as i32)
+            .collect();
+        let mut bytes = Vec::new();
+        write_flac(&mut bytes, &samples, 1, 48000, 24).unwrap();
+        let (info, decoded) = decode(&bytes);
+        assert_eq!(info.bits_per_sample, 24);
+        assert_eq!(decoded, samples);
+        // a smooth signal compresses well below 3 bytes per sample
+        assert!(bytes.len() < samples.len() * 3 / 2);
+    }
+
+    #[test]
+    fn test_frame_numbers_use_utf8_coding() {
+        let mut out = BitWriter::new();
+        write_utf8_number(&mut out, 0x7F);
+        write_utf8_number(&mut out, 0x80);
+        write_utf8_number(&mut out, 0x800);
+        assert_eq!(out.into_bytes(), [0x7F, 0xC2, 0x80, 0xE0, 0xA0, 0x80]);
+    }
+}
@@ -0,0 +1,161 @@
+//! Slow portamento between scale notes for `--glide-rate`.
+//!
+//! A voice glides from its starting frequency to a randomly chosen scale
+//! note, then on to another as soon as it arrives, at a constant rate in
+//! semitones per second. Like the grain cloud, the whole path is drawn up
+//! front, and both the frequency and the integrated carrier phase have a
+//! closed form at any time, so a voice can still seek straight to the start
+//! of a chunk.
+
+use rand::Rng;
+use std::f64::consts::{LN_2, TAU};
+
+/// One glide from `from` to `to`; the last one holds its frequency forever
+#[derive(Debug, Clone, PartialEq)]
+struct Segment {
+    /// Start time in seconds
+    start: f64,
+    /// Frequency in Hz at `start`
+    from: f64,
+    /// Signed pitch change in octaves per second, 0.0 while holding
+    rate: f64,
+    /// Unwrapped carrier phase in radians at `start`
+    phase: f64,
+}
+
+impl Segment {
+    fn freq_at(&self, elapsed: f64) -> f64 {
+        self.from * (self.rate * elapsed).exp2()
+    }
+
+    /// Phase accumulated `elapsed` seconds into the segment
+    fn phase_at(&self, elapsed: f64) -> f64 {
+        if self.rate == 0.0 {
+            return self.phase + TAU * self.from * elapsed;
+        }
+        // integral of from * 2^(rate * t)
+        let integral = self.from * ((self.rate * elapsed).exp2() - 1.0) / (self.rate * LN_2);
+        self.phase + TAU * integral
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlidePath {
+    /// Segments sorted by start time, the first starting at 0.0
+    segments: Vec<Segment>,
+}
+
+impl GlidePath {
+    /// Draws a path starting at `start_freq` that moves between `targets`
+    /// (frequencies in Hz) at `rate` semitones per second, covering at least
+    /// `length` seconds.
+    pub fn new(
+        start_freq: f32,
+        targets: &[f32],
+        rate: f32,
+        length: f32,
+        rng: &mut impl Rng,
+    ) -> GlidePath {
+        let mut segments = Vec::new();
+        let (mut time, mut phase, mut freq) = (0.0, 0.0, start_freq as f64);
+        while time < length as f64 && rate > 0.0 {
+            let choices: Vec<f64> = targets
+                .iter()
+                .map(|&t| t as f64)
+                .filter(|t| (t - freq).abs() > 1e-3)
+                .collect();
+            if choices.is_empty() {
+                break;
+            }
+            let target = choices[rng.random_range(0..choices.len())];
+            let octaves = (target / freq).log2();
+            let duration = (octaves.abs() * 12.0 / rate as f64).max(f64::EPSILON);
+            let segment = Segment {
+                start: time,
+                from: freq,
+                rate: octaves / duration,
+                phase,
+            };
+            phase = segment.phase_at(duration);
+            segments.push(segment);
+            (time, freq) = (time + duration, target);
+        }
+        segments.push(Segment {
+            start: time,
+            from: freq,
+            rate: 0.0,
+            phase,
+        });
+        GlidePath { segments }
+    }
+
+    fn segment(&self, time: f64) -> &Segment {
+        let index = self.segments.partition_point(|s| s.start <= time);
+        &self.segments[index.saturating_sub(1)]
+    }
+
+    /// Frequency in Hz at `time` seconds
+    pub fn freq_at(&self, time: f64) -> f64 {
+        let segment = self.segment(time);
+        segment.freq_at(time - segment.start)
+    }
+
+    /// Unwrapped carrier phase in radians at `time` seconds
+    pub fn phase_at(&self, time: f64) -> f64 {
+        let segment = self.segment(time);
+        segment.phase_at(time - segment.start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn path() -> GlidePath {
+        // D4 gliding within a D minor pentatonic
+        let targets = [220.0, 261.63, 293.66, 349.23, 392.0];
+        GlidePath::new(293.66, &targets, 0.5, 120.0, &mut StdRng::seed_from_u64(3))
+    }
+
+    #[test]
+    fn test_glides_monotonically_at_the_rate() {
+        let path = path();
+        let first = &path.segments[0];
+        let second = &path.segments[1];
+        let target = second.from;
+        let toward = (target - first.from).signum();
+        let (mut time, mut prev) = (0.0, path.freq_at(0.0));
+        while time + 0.1 < second.start {
+            time += 0.1;
+            let freq = path.freq_at(time);
+            assert!((freq - prev) * toward > 0.0);
+            assert!((freq - target) * toward <= 1e-9);
+            // 0.1 s at 0.5 semitones per second
+            assert!((12.0 * (freq / prev).log2()).abs() - 0.05 < 1e-6);
+            prev = freq;
+        }
+        assert!((path.freq_at(second.start) - target).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_phase_integrates_frequency() {
+        let path = path();
+        assert!(path.segments.len() > 2);
+        let dt = 1e-4;
+        for time in [0.0, 5.0, 17.3, 60.0, 119.0, 500.0] {
+            let slope = (path.phase_at(time + dt) - path.phase_at(time)) / dt;
+            let mid = path.freq_at(time + dt / 2.0);
+            assert!((slope / TAU - mid).abs() < 1e-3, "at {time}s");
+        }
+        // the path holds still past its length
+        assert_eq!(path.freq_at(1000.0), path.freq_at(2000.0));
+    }
+
+    #[test]
+    fn test_zero_rate_holds() {
+        let path = GlidePath::new(440.0, &[220.0], 0.0, 10.0, &mut StdRng::seed_from_u64(0));
+        assert_eq!(path.freq_at(5.0), 440.0);
+        assert!((path.phase_at(1.0) - TAU * 440.0).abs() < 1e-9);
+    }
+}
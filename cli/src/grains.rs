@@ -0,0 +1,216 @@
+//! Overlap-add grain cloud.
+//!
+//! Grains are short Hann-windowed sine bursts scheduled as a Poisson process
+//! at `density` grains per second. The whole schedule is drawn up front from
+//! the generator's seeded RNG, so any range of samples can be rendered on its
+//! own and chunks rendered on different threads agree with a serial render.
+
+use rand::Rng;
+use std::f32::consts::TAU;
+
+/// Peak amplitude of a single grain
+const GRAIN_AMP: f32 = 0.05;
+
+/// One scheduled grain
+#[derive(Debug, Clone, PartialEq)]
+pub struct Grain {
+    /// First sample covered by the grain's window
+    pub start: usize,
+    /// Window length in samples
+    pub len: usize,
+    /// Sine frequency in Hz
+    pub freq: f32,
+    /// Stereo position in -1.0..=1.0
+    pub pan: f32,
+}
+
+impl Grain {
+    /// Hann window value at `offset` samples into the grain
+    fn window(&self, offset: usize) -> f32 {
+        let x = offset as f32 / self.len as f32;
+        0.5 - 0.5 * (TAU * x).cos()
+    }
+
+    fn end(&self) -> usize {
+        self.start + self.len
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GrainCloud {
+    /// Grains sorted by start sample
+    grains: Vec<Grain>,
+    /// Longest grain, bounds how far back a render has to look
+    max_len: usize,
+    sample_rate: f32,
+}
+
+impl GrainCloud {
+    /// Schedules grains over `num_samples`.
+    ///
+    /// `duration` is the grain length in seconds and `pitch_jitter` the maximum
+    /// detune in semitones either side of `base_freq`. A density of zero
+    /// produces an empty cloud.
+    pub fn new(
+        density: f32,
+        duration: f32,
+        pitch_jitter: f32,
+        base_freq: f32,
+        sample_rate: u32,
+        num_samples: usize,
+        rng: &mut impl Rng,
+    ) -> GrainCloud {
+        let sr = sample_rate as f32;
+        let len = ((duration * sr) as usize).max(1);
+        let mut grains = Vec::new();
+
+        if density > 0.0 {
+            let mut time = 0.0f32;
+            loop {
+                // exponential inter-onset times give a Poisson process
+                let u: f32 = rng.random_range(f32::EPSILON..1.0);
+                time += -u.ln() / density;
+                let start = (time * sr) as usize;
+                if start >= num_samples {
+                    break;
+                }
+                let semitones = if pitch_jitter > 0.0 {
+                    rng.random_range(-pitch_jitter..=pitch_jitter)
+                } else {
+                    0.0
+                };
+                grains.push(Grain {
+                    start,
+                    len,
+                    freq: base_freq * 2f32.powf(semitones / 12.0),
+                    pan: rng.random_range(-1.0..=1.0),
+                });
+            }
+        }
+
+        GrainCloud {
+            grains,
+            max_len: len,
+            sample_rate: sr,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn grains(&self) -> &[Grain] {
+        &self.grains
+    }
+
+    /// Grains whose window overlaps `start..end`
+    fn overlapping(&self, start: usize, end: usize) -> impl Iterator<Item = &Grain> {
+        let first = self
+            .grains
+            .partition_point(|g| g.start + self.max_len <= start);
+        self.grains[first..]
+            .iter()
+            .take_while(move |g| g.start < end)
+            .filter(move |g| g.end() > start)
+    }
+
+    /// Number of grains sounding at `sample`
+    #[cfg(test)]
+    pub fn active_at(&self, sample: usize) -> usize {
+        self.overlapping(sample, sample + 1).count()
+    }
+
+    /// Adds the grains overlapping `out` (which begins at sample `start`) into it
+    pub fn render(&self, start: usize, out: &mut [(f32, f32)]) {
+        let end = start + out.len();
+        for grain in self.overlapping(start, end) {
+            let from = grain.start.max(start);
+            let to = grain.end().min(end);
+            let step = TAU * grain.freq / self.sample_rate;
+            let l_gain = (1.0 - grain.pan) * 0.5;
+            let r_gain = (1.0 + grain.pan) * 0.5;
+            for i in from..to {
+                let offset = i - grain.start;
+                let value = (step * offset as f32).sin() * grain.window(offset) * GRAIN_AMP;
+                let sample = &mut out[i - start];
+                sample.0 += value * l_gain;
+                sample.1 += value * r_gain;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn cloud(density: f32) -> GrainCloud {
+        GrainCloud::new(
+            density,
+            0.05,
+            3.0,
+            440.0,
+            44100,
+            44100 * 10,
+            &mut StdRng::seed_from_u64(1),
+        )
+    }
+
+    fn mean_active(cloud: &GrainCloud) -> f32 {
+        let total: usize = (0..44100 * 10)
+            .step_by(100)
+            .map(|i| cloud.active_at(i))
+            .sum();
+        total as f32 / (44100 * 10 / 100) as f32
+    }
+
+    #[test]
+    fn test_density_increases_overlap() {
+        assert!(cloud(0.0).grains().is_empty());
+
+        let sparse = mean_active(&cloud(10.0));
+        let dense = mean_active(&cloud(80.0));
+        // expected overlap is density * duration
+        assert!((sparse - 0.5).abs() < 0.15);
+        assert!((dense - 4.0).abs() < 0.8);
+        assert!(dense > sparse * 4.0);
+    }
+
+    #[test]
+    fn test_grains_stay_inside_their_windows() {
+        let grain = Grain {
+            start: 100,
+            len: 441,
+            freq: 1000.0,
+            pan: 0.0,
+        };
+        let single = GrainCloud {
+            grains: vec![grain.clone()],
+            max_len: grain.len,
+            sample_rate: 44100.0,
+        };
+        let mut out = vec![(0.0, 0.0); 1000];
+        single.render(0, &mut out);
+
+        assert!(out[..100].iter().all(|&s| s == (0.0, 0.0)));
+        assert!(out[541..].iter().all(|&s| s == (0.0, 0.0)));
+        for (offset, (l, r)) in out[100..541].iter().enumerate() {
+            let bound = grain.window(offset) * GRAIN_AMP * 0.5 + 1e-6;
+            assert!(l.abs() <= bound && r.abs() <= bound);
+        }
+        assert!(out[100..541]
+            .iter()
+            .any(|(l, _)| l.abs() > GRAIN_AMP * 0.25));
+    }
+
+    #[test]
+    fn test_chunked_render_matches_whole() {
+        let cloud = cloud(40.0);
+        let mut whole = vec![(0.0, 0.0); 20_000];
+        cloud.render(0, &mut whole);
+
+        let mut chunked = vec![(0.0, 0.0); 20_000];
+        for (i, chunk) in chunked.chunks_mut(1234).enumerate() {
+            cloud.render(i * 1234, chunk);
+        }
+        assert_eq!(whole, chunked);
+    }
+}
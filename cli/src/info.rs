@@ -0,0 +1,168 @@
+//! `procsynth info`: a quick look at an existing WAV file.
+//!
+//! Reports the format along with the peak and RMS levels over every channel,
+//! which is enough to check a render's length and headroom without opening
+//! an editor. Multichannel files also get the phase correlation of their
+//! first two channels, a quick check that they survive being summed to mono.
+
+use hound::{SampleFormat, WavReader, WavSpec};
+use std::fmt;
+use std::path::Path;
+
+/// Reads every sample of a WAV file as f32 in -1.0..=1.0, interleaved
+pub(crate) fn read_samples(path: impl AsRef<Path>) -> Result<(WavSpec, Vec<f32>), hound::Error> {
+    let mut reader = WavReader::open(path)?;
+    let spec = reader.spec();
+    let samples = match spec.sample_format {
+        SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>()?,
+        SampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|s| s.map(|s| s as f32 / scale))
+                .collect::<Result<_, _>>()?
+        }
+    };
+    Ok((spec, samples))
+}
+
+/// Converts a linear amplitude to dB relative to full scale
+pub(crate) fn dbfs(amplitude: f32) -> f32 {
+    20.0 * amplitude.log10()
+}
+
+/// Normalized cross-correlation of the first two channels of interleaved
+/// `samples`: 1.0 for identical channels, -1.0 for one the inverse of the
+/// other, 0.0 for unrelated ones (or silence)
+pub fn phase_correlation(samples: &[f32], channels: u16) -> f32 {
+    let (mut lr, mut ll, mut rr) = (0.0f64, 0.0f64, 0.0f64);
+    for frame in samples.chunks_exact(channels.max(2) as usize) {
+        let (l, r) = (frame[0] as f64, frame[1] as f64);
+        lr += l * r;
+        ll += l * l;
+        rr += r * r;
+    }
+    let norm = (ll * rr).sqrt();
+    if norm == 0.0 {
+        return 0.0;
+    }
+    (lr / norm) as f32
+}
+
+/// Format and levels of a WAV file
+#[derive(Debug, Clone, PartialEq)]
+pub struct WavInfo {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub bits_per_sample: u16,
+    /// Length in seconds
+    pub duration: f32,
+    /// Largest absolute sample value, 1.0 being full scale
+    pub peak: f32,
+    /// Root mean square over all channels
+    pub rms: f32,
+    /// Phase correlation of the first two channels, `None` for mono files
+    pub correlation: Option<f32>,
+}
+
+impl WavInfo {
+    pub fn read(path: impl AsRef<Path>) -> Result<WavInfo, hound::Error> {
+        let (spec, samples) = read_samples(path)?;
+        let frames = samples.len() / spec.channels.max(1) as usize;
+        let peak = samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+        let power = samples.iter().map(|s| (s * s) as f64).sum::<f64>();
+        let rms = (power / samples.len().max(1) as f64).sqrt() as f32;
+        Ok(WavInfo {
+            sample_rate: spec.sample_rate,
+            channels: spec.channels,
+            bits_per_sample: spec.bits_per_sample,
+            duration: frames as f32 / spec.sample_rate as f32,
+            peak,
+            rms,
+            correlation: (spec.channels >= 2).then(|| phase_correlation(&samples, spec.channels)),
+        })
+    }
+}
+
+impl fmt::Display for WavInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "sample rate: {} Hz", self.sample_rate)?;
+        writeln!(f, "channels:    {}", self.channels)?;
+        writeln!(f, "bit depth:   {}", self.bits_per_sample)?;
+        writeln!(f, "duration:    {:.3} s", self.duration)?;
+        writeln!(f, "peak:        {:.1} dBFS", dbfs(self.peak))?;
+        write!(f, "rms:         {:.1} dBFS", dbfs(self.rms))?;
+        if let Some(correlation) = self.correlation {
+            write!(f, "\ncorrelation: {correlation:+.2}")?;
+            if correlation < 0.0 {
+                write!(f, " (partly cancels when summed to mono)")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_stereo(frames: impl Iterator<Item = (f32, f32)>) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("{}_info.wav", uuid::Uuid::new_v4()));
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate: 8000,
+            bits_per_sample: 32,
+            sample_format: SampleFormat::Float,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for (left, right) in frames {
+            writer.write_sample(left).unwrap();
+            writer.write_sample(right).unwrap();
+        }
+        writer.finalize().unwrap();
+        path
+    }
+
+    #[test]
+    fn test_phase_correlation() {
+        let sine = |i: usize| (i as f32 * 0.07).sin() * 0.5;
+        let mono = write_stereo((0..8000).map(|i| (sine(i), sine(i))));
+        let anti = write_stereo((0..8000).map(|i| (sine(i), -sine(i))));
+
+        let mono_info = WavInfo::read(&mono).unwrap();
+        assert!((mono_info.correlation.unwrap() - 1.0).abs() < 1e-6);
+        assert!(mono_info.to_string().ends_with("correlation: +1.00"));
+        let anti_info = WavInfo::read(&anti).unwrap();
+        assert!((anti_info.correlation.unwrap() + 1.0).abs() < 1e-6);
+        assert!(anti_info.to_string().contains("cancels"));
+        std::fs::remove_file(&mono).unwrap();
+        std::fs::remove_file(&anti).unwrap();
+    }
+
+    #[test]
+    fn test_levels_of_a_known_signal() {
+        let path = std::env::temp_dir().join(format!("{}_info.wav", uuid::Uuid::new_v4()));
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: 8000,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        // a half-scale square wave: peak and RMS are both 0.5
+        for i in 0..8000 {
+            writer
+                .write_sample(if i % 2 == 0 { 16384i16 } else { -16384 })
+                .unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let info = WavInfo::read(&path).unwrap();
+        assert_eq!(info.duration, 1.0);
+        assert_eq!(info.peak, 0.5);
+        assert!((info.rms - 0.5).abs() < 1e-6);
+        assert!(info.to_string().contains("peak:        -6.0 dBFS"));
+        assert_eq!(info.correlation, None);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
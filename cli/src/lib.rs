@@ -24,26 +24,78 @@
 //! 2. **Modulation**: LFO-based amplitude and frequency modulation for organic movement
 //! 3. **Noise Generation**: Multiple layers of filtered noise for texture
 //! 4. **Envelope Shaping**: Attack and release phases for smooth transitions
-//! 5. **Reverb**: Simple delay-based reverb for spatial depth
-//! 6. **Stereo Processing**: Panning and stereo effects for width
+//! 5. **Chorus**: Optional swept short delay that thickens the voices
+//! 6. **Reverb**: Simple delay-based reverb for spatial depth, or convolution with a recorded space
+//! 7. **Stereo Processing**: Panning and stereo effects for width
 //!
 //! ### Noise Layers
 //! The synthesizer incorporates several types of noise:
 //! - **Base Noise**: Continuous low-level noise for subtle texture
-//! - **Granular Noise**: Burst-shaped noise modulated by a 10Hz LFO
+//! - **Grain Cloud**: Overlapping Hann-windowed sine grains at random onsets and pitches
 //! - **Filtered Noise**: Low-pass filtered noise for warmth
+//! - **Resonators**: The filtered noise rung through comb filters tuned to chosen notes
 //!
 //! ## CLI Parameters
 //!
 //! ### Basic Parameters
-//! - `--output (-o)`: Output WAV filename (auto-generated if not specified)
+//! - `--output (-o)`: Output filename (auto-generated if not specified)
+//! - `--format`: Output format: wav, flac or ogg (default: from the output extension, else wav)
+//!   - FLAC is lossless and noticeably smaller than WAV for smooth material
+//!   - OGG Vorbis needs the `ogg` cargo feature, which builds libvorbis from source
+//! - `--bit-depth`: Bits per sample for WAV and FLAC, 16 or 24 (default: 16)
+//! - `--dither`: Add +/-1 LSB triangular dither before quantizing 16-bit output
+//!   - Turns quantization distortion on quiet fades into a steady, benign hiss
+//! - `--noise-shaping`: Feed back the 16-bit quantization error so it lands in the treble
+//!   - A second-order `(1 - z^-1)^2` shape: less noise where the ear is most sensitive,
+//!     more near Nyquist; works with or without `--dither`
+//! - `--ogg-quality`: Vorbis quality from -0.2 to 1.0 (default: 0.5)
 //! - `--duration (-d)`: Length of generated audio in seconds (default: 60.0)
 //! - `--sample-rate (-r)`: Audio sample rate in Hz (default: 44100)
+//! - `--max-duration`: Refuse to render more than this many seconds (default: 3600.0)
+//!   - Guards against typos such as `--duration 60000`; raise it for intentionally long pieces
 //!
 //! ### Voice Configuration
 //! - `--voices (-v)`: Number of synthetic voices (default: 4)
+//!   - 0 renders a noise-only texture bed: the noise layers and grain cloud, still shaped
+//!     by the envelope and the effects
 //! - `--base-freq`: Fundamental frequency in Hz for voice generation (default: 330.0)
-//!   - Other voices are derived by random ratios (0.8-1.2x) from this base
+//!   - Voices are derived by random ratios (see `--detune-range`) from this base
+//! - `--detune-range`: Range of voice frequency ratios to the base, as "min:max" (default: "0.8:1.2")
+//!   - "1.0:1.0" gives a pure unison; a narrow range like "0.99:1.01" a chorused drone
+//! - `--detune-cents`: Spread voices within this many cents either side of the base instead
+//!   - 100 cents is a semitone, so 7 gives a lush unison and 1200 the range "0.5:2.0"
+//!   - Takes the place of `--detune-range` when given
+//! - `--detune-dist`: How ratios are drawn from the range: uniform or gaussian (default: uniform)
+//!   - `gaussian` clusters voices around the middle, with 95% of them inside the range
+//! - `--chord`: Sustain a chord as a drone instead, e.g. "Cmaj7", "F#m", "Bb7"
+//!   - One voice per chord tone (overrides `--voices`), detuned by under a cent
+//!   - The chord is voiced in the octave whose root lies closest to `--base-freq`
+//!   - Qualities: maj (or none), m, dim, aug, maj7, m7, 7
+//! - `--progression`: Cycle through chords written as Roman numerals, e.g. "I,vi,IV,V"
+//!   (needs `--scale`, which also goes by `--key`, e.g. "C:major")
+//!   - Uppercase numerals are major, lowercase minor; add "°", "+", "7" or "maj7" for other
+//!     qualities, and a leading "b" or "#" for chords off the scale, as in "bVII"
+//!   - Each chord sounds for `--chord-duration` seconds (default: 4.0), crossfading into the
+//!     next over a second, and the progression repeats to the end of the piece
+//!   - Each chord takes up to `--voices` of its tones from the root up, so a seventh chord with
+//!     three voices drops its seventh
+//! - `--max-voices`: Most voices sounding at once (default: 0 = no limit)
+//!   - Each note past the cap steals a voice sounding with it; in a progression that means the
+//!     voices of its own chord and the ones it crossfades with
+//!   - `--voice-steal`: `oldest` gives up the voice requested first, `quietest` the one with the
+//!     lowest average level (default: oldest)
+//!   - Bounds the CPU cost and headroom of large chords and progressions
+//! - `--scale`: Key for gliding voices, e.g. "D dorian", "F# minor" (a bare root is major)
+//! - `--glide-rate`: Let voices drift between `--scale` notes at this many semitones per second
+//!   (default: 0.0 = off; needs `--scale`)
+//!   - Each voice glides to a random scale note within a fifth of where it started, then
+//!     straight on to the next; 0.05-0.2 takes tens of seconds per move
+//!
+//! - `--partials`: Additive timbre as comma-separated "ratio:amplitude" pairs (default: pure sine)
+//!   - "1:1.0,2:0.5,3:0.25" stacks the first three harmonics, organ style
+//!   - Non-integer ratios such as "1:1,2.76:0.4" give bell-like inharmonic tones
+//!   - Amplitudes are relative; they're scaled to sum to 1 so the level stays the same
+//!   - Binaural voices stay pure sines
 //!
 //! ### Modulation Parameters
 //! - `--lfo-rate-range`: LFO frequency range as "min:max" in Hz (default: "0.05:0.2")
@@ -52,20 +104,217 @@
 //! - `--mod-depth-range`: Modulation depth range as "min:max" (0.0-1.0) (default: "0.5:1.0")
 //!   - Controls how intense the LFO modulation effect is
 //!   - Higher values create more dramatic volume changes
+//! - `--lfo-sync`: Pick LFO rates from whole, half, quarter and eighth notes instead
+//!   - Converted to Hz as `tempo / 60 / beats`, so a quarter note at 120 BPM is 2 Hz
+//! - `--tempo`: Tempo in BPM for `--lfo-sync` and `--click`, from 20 to 400 (default: 120)
+//! - `--click`: Overlay a metronome click on every beat, after every effect
+//!   - `--time-sig`: Time signature whose downbeats are accented, e.g. `3/4` or `6/8`
+//!     (default: 4/4); the click follows its note value, so `6/8` clicks in eighth notes
+//!   - A debugging aid for the tempo-synced options, or a pulse under rhythmic pieces
+//!
+//! ### Levels
+//! - `--gain`: Master gain in dB (default: 0.0)
+//!   - -6 dB roughly halves the output amplitude
+//! - `--voice-gains`: Comma-separated linear weights per voice, e.g. "1.0,0.5,0.25"
+//!   - Voices past the end of the list keep a weight of 1.0
 //!
 //! ### Texture and Dynamics
 //! - `--noise-level`: Base noise level (0.0-1.0) (default: 0.005)
 //!   - Adds subtle texture and prevents pure digital silence
+//! - `--noise-type`: `white` random noise, or `value` for smooth noise computed from the seed
+//!   and the time alone (default: white)
+//!   - Value noise sums a few octaves of smoothly interpolated random points from 200 Hz up,
+//!     a softer, rounder texture than white noise
+//!   - Only the `--noise-level` layer; the filtered and wind layers stay random
+//! - `--noise-cutoff`: Low-pass cutoff of the filtered noise layer in Hz (default: 739.5)
+//!   - Sounds the same at any sample rate; lower values give a darker rumble
+//! - `--wind-level`: Level of a band-passed noise layer that sounds like wind or breath
+//!   (default: 0.0 = off)
+//!   - `--wind-center`: Centre of the band in Hz (default: 800.0)
+//!   - `--wind-sweep`: How far the centre drifts either way over a 20 second cycle, in octaves
+//!     (default: 1.0)
 //! - `--attack`: Fade-in time in seconds (default: 5.0)
 //!   - How long it takes for the sound to reach full volume
 //! - `--release`: Fade-out time in seconds (default: 10.0)
 //!   - How long the ending fade-out lasts
+//! - `--voice-stagger`: Spread the voices' entries evenly over this many seconds (default: 0.0)
+//!   - Each voice fades in over `--attack` from its entry, then out over `--release` so the
+//!     last to enter is the first to leave, inside the overall envelope
+//!   - Gives a slowly assembling and dispersing ensemble; ignored with `--loop`
+//! - `--fade-curve`: Shape of both fades: linear, exponential or logarithmic (default: linear)
+//!   - `exponential` squares the ramp, easing in slowly; it sounds most natural on long fades
+//!   - `logarithmic` uses the square root, rising quickly and settling gently
+//! - `--fade-in-ms` / `--fade-out-ms`: Short linear fades over the very start and end of the
+//!   file, after every effect (default: 0.0 = off)
+//!   - Separate from attack/release, so a sharp musical attack can still start without a click
+//!   - A few milliseconds is enough; ignored with `--loop`
+//! - `--resonators`: Comma-separated notes, e.g. `C4,E4,G4`, that the filtered noise rings at
+//!   - Each is a feedback delay line one period long, so the noise takes on that pitch and
+//!     its harmonics; the layer's level stays about the same
+//! - `--resonator-decay`: Seconds for a resonance to fade by 60 dB (default: 2.0)
+//!   - Longer decays give purer, more sustained tones
+//! - `--noise-duck`: Duck the noise layers as the voices swell (0.0-1.0) (default: 0.0 = off)
+//!   - Noise is scaled by `1 - amount * level`, with `level` the voices' average LFO envelope,
+//!     so texture fills the quiet stretches without muddying the loud ones
+//! - `--saturation`: Soft tanh saturation drive on the mix (default: 0.0 = bypass)
+//!   - Shapes the mix with `tanh(drive * x) / tanh(drive)` before reverb, so the tail
+//!     inherits the warmth; 1-3 is gentle, higher values squash peaks harder
+//! - `--chorus-depth`: Chorus sweep depth (0.0-1.0) (default: 0.0 = off)
+//!   - Mixes in a copy delayed by 15 ms, swept by up to 5 ms either side, to thicken voices
+//! - `--chorus-rate`: Chorus sweep rate in Hz (default: 0.3)
 //! - `--reverb-mix`: Dry/wet reverb balance (0.0-1.0) (default: 0.3)
 //!   - 0.0 = completely dry, 1.0 = completely wet
+//! - `--reverb-hpf`: High-pass cutoff in Hz on the signal fed to the reverb (default: 0.0 = off)
+//!   - Only mids and highs get reverberated, so the bass stays dry and tight
+//! - `--reverb-fade`: Seconds over which the mix ramps from dry up to `--reverb-mix`
+//!   (default: 0.0 = wet from the first sample)
+//!   - The space opens up gradually instead of appearing with the first note
+//! - `--reverb-ir`: Convolve the reverb send with an impulse response WAV instead of using the
+//!   feedback delay, for the sound of a real space
+//!   - Mono or stereo, resampled to `--sample-rate` if need be, and scaled to unit energy so
+//!     `--reverb-mix` balances it like the built-in reverb
+//!   - Partitioned FFT convolution keeps long halls fast
+//! - `--reverb-tail`: Seconds of silence added to the end for the reverb to decay into
+//!   (default: 0.0)
+//!   - The file runs `--duration` plus the tail; without it the reverb is cut off mid-decay
+//!   - Ignored with `--loop`
+//!
+//! ### Automation
+//! - `--automate`: Move a parameter along breakpoints over the render, e.g.
+//!   "reverb_mix:0.1@0,0.6@120" (value@seconds; repeat the flag for more parameters)
+//!   - Values are interpolated linearly and held before the first and after the last breakpoint
+//!   - `reverb_mix` and `noise_level` can be automated
+//!
+//! ### Grain Cloud
+//! - `--grain-density`: Average grains per second (default: 0.0 = off)
+//! - `--grain-duration`: Length of each grain in seconds (default: 0.08)
+//! - `--grain-pitch-jitter`: Random detune in semitones around `--base-freq` (default: 7.0)
+//!   - Grains overlap and sum; the average number sounding at once is density x duration
+//!
+//! ### Excerpts
+//! - `--render-range <start:end>`: Render only that window of the piece, in seconds
+//!   - Envelope, LFOs, glides and grains run on the full piece's clock, so the file sounds
+//!     like a cut from the full render; the reverb and filtered noise start fresh
+//!   - Can't be combined with `--loop`
+//! - `--preview <seconds>`: Render just the first N seconds for a quick listen
+//!   - Unlike a shorter `--duration`, the release still falls at the configured end, so
+//!     the preview has the real attack and sustain; same as `--render-range 0:N`
+//!
+//! ### Looping
+//! - `--loop`: Render a seamlessly loopable file
+//!   - Disables the attack/release envelope
+//!   - Renders `--crossfade-seconds` past the end and blends that tail into the start,
+//!     so the last sample leads straight back into the first
+//! - `--crossfade-seconds`: Length of the loop-point crossfade (default: 2.0)
+//!
+//! ### FM Synthesis
+//! - `--fm-index`: Frequency-modulation index (default: 0.0 = off)
+//!   - Peak frequency deviation is `index * modulator_freq`; around 1-3 gives bell-like tones
+//! - `--fm-pairing`: Which voices get a modulator (default: pairs)
+//!   - A modulator is a plain sine at the previous voice's frequency, not that voice's output
+//!   - `pairs`: each odd voice is modulated at the frequency of the even voice before it
+//!   - `adjacent`: every voice after the first is modulated at the previous one's frequency
+//!     (also accepted as `chain`)
+//!
+//! ### Stereo Image
+//! - `--pan-spread`: Panning depth (0.0-1.0) (default: 1.0)
+//!   - 0.0 collapses every voice to the center, 1.0 uses the full stereo width
+//! - `--static-pan`: Place voices at fixed, evenly spaced positions instead of oscillating
+//! - `--autopan-depth`: Depth of a slow sweep of the whole mix across the field (default: 0.0 = off)
+//!   - Scales the channels by `1 - pan` and `1 + pan` with `pan = depth * sin(lfo_phase)`
+//! - `--autopan-rate`: Auto-pan sweep rate in Hz (default: 0.05)
+//! - `--width`: Mid/side width of the final mix, applied after reverb (default: 1.0)
+//!   - 0.0 folds to mono, 1.0 leaves the mix untouched, above 1.0 widens it
+//! - `--haas-ms`: Delay the right channel by a few ms for width (default: 0.0 = off)
+//!   - 5-30 ms reads as width rather than an echo; applied after `--width`
+//!   - Ignored (with a warning) for `--mono` output, where the delayed copy would comb filter
+//! - `--mono`: Average left and right into a single-channel WAV
+//! - `--channels`: 2 for stereo or 4 for quad (default: 2)
+//!   - Quad writes front left, front right, rear left, rear right
+//!   - Each voice also drifts between front and rear (or alternates with `--static-pan`)
+//!   - White noise and effects run per speaker pair; filtered noise and grains stay in front
+//! - `--binaural`: Binaural beat frequency in Hz (default: 0.0 = off)
+//!   - Each voice plays `freq` in the left ear and `freq + beat` in the right
+//!   - Voices stay centered instead of panning; 1-30 Hz beats are typical
+//!   - Ignored (with a warning) for `--mono` output, where the ears can't be separated
+//!
+//! ### Analysis
+//! - `--spectrogram <file.png>`: Also write a spectrogram of the final mix, summed to mono
+//!   - One column per frame, low frequencies at the bottom, covering 96 dB below the peak
+//! - `--spectrogram-window`: FFT window size in samples (default: 2048)
+//! - `--spectrogram-hop`: Samples between frames (default: 512)
+//!
+//! ### Stems
+//! - `--stems <dir>`: Also write each layer of the mix to its own WAV for mixing in a DAW:
+//!   `voices.wav`, `noise.wav`, `grains.wav`, `filtered.wav` and `reverb.wav`, plus
+//!   `wind.wav` with `--wind-level`
+//!   - Every stem goes through the same envelope and effects as the mix, with `reverb.wav`
+//!     holding the reverb's wet signal and the others their dry share, so the stems sum back
+//!     to the mix
+//!   - Stems are taken before `--saturation`, so with it they only add up to an undriven mix
+//!
+//! ### Tools
+//! - `info <file.wav>`: Print a WAV file's sample rate, channels, bit depth, duration,
+//!   and peak/RMS levels in dBFS
+//!   - Stereo and quad files also show the phase correlation of the first two channels
+//!     (+1 mono-safe, 0 unrelated, below 0 partly cancels when summed to mono)
+//! - `normalize <in.wav> <out.wav>`: Rescale a WAV so its level lands on `--target` dBFS,
+//!   keeping its format
+//!   - `--normalize-mode peak` (default) targets the peak, -1 dBFS unless given
+//!   - `--normalize-mode rms` targets the RMS level, -18 dBFS unless given; this matches
+//!     perceived loudness far better, but loud targets can clip peaks
+//!   - Gain is capped at +40 dB so near-silent files aren't turned into loud noise
+//! - `process <in.wav> <out.wav>`: Fix up a WAV's channels, losslessly in its own format
+//!   - `--swap-channels` exchanges left and right; `--invert-right` flips the right
+//!     channel's polarity, after any swap
+//!   - Quad files get the same done to their rear pair; mono files are copied unchanged
+//! - `batch <config-dir> <out-dir>`: Render every `.json` config in a directory, in parallel
+//!   - `name.json` is written to `<out-dir>/name.wav` (or the config's format); its `output`
+//!     is ignored
+//!   - Failed configs are listed and skipped; the exit status is non-zero if any failed
+//!   - `--loudness-match`: Then rescale every WAV rendered to one RMS level, so an album of
+//!     them plays back evenly; that's `--loudness-target` dBFS if given, else their average,
+//!     lowered if need be to keep every peak at or below -1 dBFS
+//!
+//! ### Feedback
+//! - `--progress`: Print a progress bar to stderr while rendering
+//! - `--verbose`: Print every voice's randomly chosen parameters before rendering
+//!   - Frequency with its nearest note and cent offset, LFO rate, modulation depth, pan rate
+//!   - Together with `--seed`, handy for noting down what made a render work
+//! - `--peak-report`: After rendering, print how many samples went past full scale and were
+//!   clamped, and the peak level; a hint to lower `--gain` or the voice count
+//! - `--play`: Play the result on the default audio device once it's written
+//!   - Needs the `playback` cargo feature (cpal; ALSA development headers on Linux)
+//!   - Fails with the supported rates if the device can't run at `--sample-rate`
+//!
+//! ### Reproducibility and Performance
+//! - `--fast-sine`: Use a shared 4096-entry interpolated sine table instead of `sin()`
+//!   - Error stays below 1e-5 of full scale; compare with `cargo bench --bench wavetable`
+//! - `--oversample`: Render voices at 2 or 4 times the sample rate, then low-pass and
+//!   decimate back down (default: 1 = off)
+//!   - Keeps high `--partials` and strong FM from aliasing into inharmonic tones, for 2-4
+//!     times the voice rendering cost
+//! - `--seed`: Seed for all random choices; the same seed renders the same file
+//!   - A random seed is picked (and printed) when omitted
+//! - `--threads`: Worker threads used for rendering (default: 0 = one per CPU core)
+//!   - `--threads 1` renders serially and produces the same output as any other count
+//!
+//! ### Presets
+//! - `--preset`: Start from a built-in configuration: airport, drone, glacial, textured
+//!   - Any flag passed explicitly overrides the preset's value
+//! - `--list-presets`: Print the presets with their key parameters and exit
+//!
+//! ### Environment
+//! - `PROCSYNTH_DURATION`, `PROCSYNTH_SAMPLE_RATE`, `PROCSYNTH_VOICES`: Used in place of the
+//!   built-in default (or the preset's value) when the matching flag isn't passed
+//!   - An explicit flag always wins; `--config` files ignore them
 //!
 //! ## Usage Examples
 //!
 //! ```bash
+//! # Start from the Music for Airports preset but make it shorter
+//! cargo run -- --preset airport --duration 60
+//!
 //! # Generate a 2-minute ambient piece with default settings
 //! cargo run -- --duration 120
 //!
@@ -89,14 +338,29 @@
 //!    - Add multiple noise layers for texture
 //!    - Apply envelope shaping (attack/sustain/release)
 //!    - Accumulate into stereo output
-//! 4. **Post-Processing**: Apply reverb using a simple delay line with feedback
-//! 5. **File Output**: Write 16-bit stereo WAV file
+//! 4. **Post-Processing**: Apply chorus, then reverb using a simple delay line with feedback
+//! 5. **File Output**: Write 16-bit stereo (or mono-summed, or quad) WAV file
+//!
+//! ### Parallel Rendering
+//! Voices, white noise, and the grain cloud don't depend on previous samples, so the
+//! buffer is cut into fixed 4096-sample chunks that are rendered on a rayon thread
+//! pool. Each chunk draws its noise from its own RNG seeded from the master seed and
+//! the chunk index, so the result doesn't depend on thread count or scheduling. The
+//! stateful layers (filtered noise, envelope, reverb) then run in a single serial pass.
+//!
+//! How much faster that is depends on the core count and how much of the render the
+//! serial pass takes; compare `--threads 1` against the default to measure it on a given
+//! machine.
 //!
 //! ### Mathematical Foundations
-//! - **Sine Wave Generation**: `sin(2π * frequency * time)`
-//! - **LFO Modulation**: `sin(2π * lfo_rate * time) * 0.5 + 0.5` (unipolar)
-//! - **Panning**: `pan = sin(2π * pan_rate * time)`, `left = (1-pan)*0.5`, `right = (1+pan)*0.5`
-//! - **Envelope**: Linear attack/release with rectangular sustain
+//! - **Sine Wave Generation**: `sin(phase)` with `phase += 2π * frequency / sample_rate`
+//!   - Every oscillator (carrier, LFO, pan, FM modulator) keeps its own phase accumulator
+//!     wrapped to `[0, 2π)`, so precision doesn't degrade as `time` grows
+//! - **FM**: `phase += 2π * (freq + index * mod_freq * sin(mod_phase)) / sample_rate`
+//! - **LFO Modulation**: `sin(lfo_phase) * 0.5 + 0.5` (unipolar)
+//! - **Panning**: `pan = sin(pan_phase) * spread`, `left = (1-pan)*0.5`, `right = (1+pan)*0.5`
+//! - **Envelope**: Linear, exponential or logarithmic attack/release with rectangular sustain
+//! - **Chorus**: `output = (dry + dry[t - (15ms + 5ms * depth * sin(lfo_phase))]) / 2`
 //! - **Reverb**: `output = dry * (1-mix) + delayed_feedback * mix`
 //!
 //! ## References and Inspiration
@@ -124,34 +388,86 @@
 //! - **Digital Reverb**: Delay lines with feedback for spatial effects
 //! - **Procedural Generation**: Algorithmic parameter selection within aesthetic constraints
 
-use clap::{Parser, ValueEnum};
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use glide::GlidePath;
+use grains::GrainCloud;
 use hound::{WavSpec, WavWriter};
-use rand::{rngs::ThreadRng, Rng};
+pub use info::WavInfo;
+use oversample::Decimator;
+use pool::VoicePool;
+use procsynth_core::{Chord, Duration, Note, Octave, Scale, Tempo, TimeSignature};
+use rand::{
+    rngs::{SmallRng, StdRng},
+    Rng, SeedableRng,
+};
+use rayon::prelude::*;
+use resonator::ResonatorBank;
 use serde::{Deserialize, Serialize};
-use std::{f32::consts::PI, fs::File, io::BufWriter};
+use std::{
+    f32::consts::FRAC_PI_2, f64::consts::TAU, fmt, fs::File, io::BufWriter, path::Path, sync::Arc,
+};
+use value_noise::value_noise;
+use wind::Wind;
+
+mod batch;
+mod convolution;
+mod flac;
+mod glide;
+mod grains;
+mod info;
+mod normalize;
+mod oversample;
+#[cfg(feature = "playback")]
+mod playback;
+mod pool;
+mod process;
+mod resonator;
+mod spectrogram;
+mod value_noise;
+pub mod wavetable;
+mod wind;
 
 /// Ambient WAV generator inspired by Brian Eno
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Parser, Debug)]
-#[command(author, version, about)]
+#[command(author, version, about, args_conflicts_with_subcommands = true)]
 struct CLI {
-    /// Load configuration from JSON file (overrides other parameters)
+    /// Run a tool on an existing file instead of generating
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Load configuration from JSON file (overrides other parameters); omitted fields use
+    /// their defaults
     #[arg(short, long)]
     config: Option<String>,
 
+    /// Start from a built-in preset; explicitly passed flags override it
+    #[arg(long, value_parser = clap::builder::PossibleValuesParser::new(PRESETS))]
+    preset: Option<String>,
+
+    /// Print the built-in presets and exit
+    #[arg(long)]
+    list_presets: bool,
+
     /// Output WAV file
     #[arg(short, long)]
     output: Option<String>,
 
     /// Duration in seconds
-    #[arg(short, long, default_value_t = 60.0)]
+    #[arg(short, long, env = "PROCSYNTH_DURATION", default_value_t = 60.0)]
     duration: f32,
 
     /// Sample rate (Hz)
-    #[arg(short = 'r', long, default_value_t = 44100)]
+    #[arg(
+        short = 'r',
+        long,
+        env = "PROCSYNTH_SAMPLE_RATE",
+        default_value_t = 44100
+    )]
     sample_rate: u32,
 
-    /// Number of voices
-    #[arg(short, long, default_value_t = 4)]
+    /// Number of voices (0 renders only the noise and grain layers)
+    #[arg(short, long, env = "PROCSYNTH_VOICES", default_value_t = 4)]
     voices: usize,
 
     /// Base frequency (Hz) for voices; others derived by random ratio
@@ -180,13 +496,398 @@ struct CLI {
 
     #[arg(long, default_value_t = 0.3)]
     reverb_mix: f32,
+
+    /// Stereo pan spread (0.0 = mono center, 1.0 = full width)
+    #[arg(long, default_value_t = 1.0)]
+    pan_spread: f32,
+
+    /// Distribute voices evenly across the stereo field instead of oscillating
+    #[arg(long)]
+    static_pan: bool,
+
+    /// Write a single-channel WAV (left and right averaged)
+    #[arg(long)]
+    mono: bool,
+
+    /// Print a progress bar to stderr while rendering
+    #[arg(long)]
+    progress: bool,
+
+    /// Play the result on the default audio device after writing it
+    #[arg(long)]
+    play: bool,
+
+    /// Print each voice's frequency, nearest note, LFO rate, depth and pan rate before rendering
+    #[arg(long)]
+    verbose: bool,
+
+    /// After rendering, print how many samples clipped and the peak level
+    #[arg(long)]
+    peak_report: bool,
+
+    /// Seed for reproducible output (random if omitted)
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Worker threads for rendering (0 = one per CPU core)
+    #[arg(long, default_value_t = 0)]
+    threads: usize,
+
+    /// Use a precomputed sine wavetable instead of sin() for oscillators
+    #[arg(long)]
+    fast_sine: bool,
+
+    /// FM modulation index (0.0 disables FM)
+    #[arg(long, default_value_t = 0.0)]
+    fm_index: f32,
+
+    /// Which voices get a sine modulator, at a neighbour's frequency, when FM is enabled
+    #[arg(long, value_enum, default_value_t = FmPairing::Pairs)]
+    fm_pairing: FmPairing,
+
+    /// Master gain in dB applied to the final mix
+    #[arg(long = "gain", default_value_t = 0.0, allow_negative_numbers = true)]
+    gain_db: f32,
+
+    /// Per-voice linear gain weights; voices past the end of the list use 1.0
+    #[arg(long, value_delimiter = ',')]
+    voice_gains: Vec<f32>,
+
+    /// Skip the attack/release envelope and crossfade the ends for gapless looping
+    #[arg(long = "loop")]
+    loop_mode: bool,
+
+    /// Length (seconds) of the loop-point crossfade in loop mode
+    #[arg(long, default_value_t = 2.0)]
+    crossfade_seconds: f32,
+
+    /// Grains per second in the grain cloud (0.0 disables it)
+    #[arg(long, default_value_t = 0.0)]
+    grain_density: f32,
+
+    /// Length (seconds) of each grain
+    #[arg(long, default_value_t = 0.08)]
+    grain_duration: f32,
+
+    /// Maximum grain detune (semitones) either side of the base frequency
+    #[arg(long, default_value_t = 7.0)]
+    grain_pitch_jitter: f32,
+
+    /// Sustain a chord such as "Cmaj7", one voice per chord tone
+    #[arg(long)]
+    chord: Option<Chord>,
+
+    /// Binaural beat (Hz): the right ear plays each voice this much higher (0.0 disables)
+    #[arg(long, default_value_t = 0.0)]
+    binaural: f32,
+
+    /// Tempo (BPM) used by --lfo-sync and --click, from 20 to 400
+    #[arg(long, default_value_t = 120, value_parser = parse_tempo)]
+    tempo: u16,
+
+    /// Pick LFO rates from note divisions of --tempo instead of --lfo-rate-range
+    #[arg(long)]
+    lfo_sync: bool,
+
+    /// Output format (guessed from the output extension when omitted, else WAV)
+    #[arg(long, value_enum)]
+    format: Option<OutputType>,
+
+    /// OGG Vorbis quality from -0.2 to 1.0
+    #[arg(long, default_value_t = 0.5)]
+    ogg_quality: f32,
+
+    /// Bits per sample for WAV and FLAC output (16 or 24)
+    #[arg(long, default_value_t = 16, value_parser = parse_bit_depth)]
+    bit_depth: u16,
+
+    /// Harmonic series for every voice as ratio:amplitude pairs (a pure sine when empty)
+    #[arg(long, value_delimiter = ',', value_parser = parse_partial)]
+    partials: Vec<(f32, f32)>,
+
+    /// Mid/side stereo width of the final mix (0.0 = mono, 1.0 = unchanged, >1.0 = wider)
+    #[arg(long, default_value_t = 1.0)]
+    width: f32,
+
+    /// Tape-style tanh saturation drive applied before reverb (0.0 bypasses it)
+    #[arg(long, default_value_t = 0.0)]
+    saturation: f32,
+
+    /// Cutoff (Hz) of the low-pass filter on the filtered noise layer
+    #[arg(long = "noise-cutoff", default_value_t = 739.5)]
+    noise_cutoff_hz: f32,
+
+    /// Shape of the attack and release ramps
+    #[arg(long, value_enum, default_value_t = FadeCurve::Linear)]
+    fade_curve: FadeCurve,
+
+    /// High-pass cutoff (Hz) on the reverb send so the bass stays dry (0.0 disables it)
+    #[arg(long, default_value_t = 0.0)]
+    reverb_hpf: f32,
+
+    /// Chorus delay sweep as a fraction of its 5 ms maximum (0.0 disables the chorus)
+    #[arg(long, default_value_t = 0.0)]
+    chorus_depth: f32,
+
+    /// Rate (Hz) of the LFO sweeping the chorus delay
+    #[arg(long, default_value_t = 0.3)]
+    chorus_rate: f32,
+
+    /// Depth of the global auto-pan sweep over the whole mix (0.0 disables it)
+    #[arg(long, default_value_t = 0.0)]
+    autopan_depth: f32,
+
+    /// Rate (Hz) of the global auto-pan sweep
+    #[arg(long, default_value_t = 0.05)]
+    autopan_rate: f32,
+
+    /// Output channels: 2 for stereo, 4 for quad (front L/R, rear L/R)
+    #[arg(long = "channels", default_value_t = 2, value_parser = parse_channels, conflicts_with = "mono")]
+    output_channels: u16,
+
+    /// Breakpoint automation such as "reverb_mix:0.1@0,0.6@120" (value@seconds); repeatable
+    #[arg(long = "automate", value_parser = parse_automation)]
+    automation: Vec<Automation>,
+
+    /// Longest duration in seconds a render may have, to catch accidental multi-hour renders
+    #[arg(long, default_value_t = 3600.0)]
+    max_duration: f32,
+
+    /// Range of voice frequency ratios to the base frequency, as min:max
+    #[arg(long, default_value = "0.8:1.2")]
+    detune_range: String,
+
+    /// How voice frequency ratios are spread over --detune-range
+    #[arg(long, value_enum, default_value_t = DetuneDistribution::Uniform)]
+    detune_dist: DetuneDistribution,
+
+    /// Anti-click fade (ms) over the very start of the file, independent of --attack
+    #[arg(long, default_value_t = 0.0)]
+    fade_in_ms: f32,
+
+    /// Anti-click fade (ms) over the very end of the file, independent of --release
+    #[arg(long, default_value_t = 0.0)]
+    fade_out_ms: f32,
+
+    /// Seconds of silence appended before the effects so the reverb can ring out
+    #[arg(long, default_value_t = 0.0)]
+    reverb_tail: f32,
+
+    /// Key such as "D dorian" whose notes --glide-rate moves voices between, and
+    /// which --progression's numerals count from
+    #[arg(long, visible_alias = "key")]
+    scale: Option<Scale>,
+
+    /// Portamento speed (semitones per second) of voices gliding between --scale notes (0.0 disables it)
+    #[arg(long, default_value_t = 0.0, requires = "scale")]
+    glide_rate: f32,
+
+    /// How far the noise layers dip while the voices swell (0.0 disables it, 1.0 ducks fully)
+    #[arg(long, default_value_t = 0.0)]
+    noise_duck: f32,
+
+    /// Haas widening: delay the right channel by this many ms (5-30 is typical, 0.0 is off)
+    #[arg(long, default_value_t = 0.0)]
+    haas_ms: f32,
+
+    /// Also write a spectrogram of the mono-summed mix to this PNG
+    #[arg(long, value_name = "FILE.png")]
+    spectrogram: Option<String>,
+
+    /// FFT window size in samples for --spectrogram
+    #[arg(long, default_value_t = 2048, requires = "spectrogram")]
+    spectrogram_window: usize,
+
+    /// Samples between --spectrogram frames (one image column each)
+    #[arg(long, default_value_t = 512, requires = "spectrogram")]
+    spectrogram_hop: usize,
+
+    /// Add triangular (TPDF) dither before quantizing 16-bit output
+    #[arg(long)]
+    dither: bool,
+
+    /// Shape 16-bit quantization error toward high frequencies with error feedback
+    #[arg(long)]
+    noise_shaping: bool,
+
+    /// Seconds over which the reverb mix ramps up from dry at the start (0.0 = instant)
+    #[arg(long, default_value_t = 0.0)]
+    reverb_fade: f32,
+
+    /// Notes such as "C4,E4,G4" whose tuned comb filters the filtered noise rings through
+    #[arg(long, value_delimiter = ',')]
+    resonators: Vec<Note>,
+
+    /// Seconds for a --resonators ring to die away by 60 dB
+    #[arg(long, default_value_t = 2.0, requires = "resonators")]
+    resonator_decay: f32,
+
+    /// Render only this window of the piece, in seconds, as an excerpt of the full render
+    #[arg(long, value_name = "START:END", value_parser = parse_render_range, conflicts_with = "loop_mode")]
+    render_range: Option<(f32, f32)>,
+
+    /// Quick audition: render only the first N seconds, shaped as in the full piece
+    #[arg(long, value_name = "SECONDS", value_parser = parse_preview, conflicts_with_all = ["render_range", "loop_mode"])]
+    preview: Option<f32>,
+
+    /// Chord progression in Roman numerals such as "I,vi,IV,V", played in the --scale key
+    #[arg(long, requires = "scale", conflicts_with = "chord")]
+    progression: Option<String>,
+
+    /// Seconds each chord of --progression lasts
+    #[arg(long, default_value_t = 4.0, requires = "progression")]
+    chord_duration: f32,
+
+    /// Also write each layer of the mix (voices, noise, grains, filtered noise, reverb) to its own WAV in this directory
+    #[arg(long, value_name = "DIR")]
+    stems: Option<String>,
+
+    /// Render voices at 2 or 4 times the sample rate and filter back down, against aliasing (1 disables it)
+    #[arg(long, default_value_t = 1, value_parser = parse_oversample)]
+    oversample: usize,
+
+    /// Seconds over which the voices enter one after another, and leave in reverse order
+    #[arg(long, default_value_t = 0.0)]
+    voice_stagger: f32,
+
+    /// Level of the swept band-pass noise "wind" layer (0.0 disables it)
+    #[arg(long, default_value_t = 0.0)]
+    wind_level: f32,
+
+    /// Centre frequency (Hz) of the wind layer's band
+    #[arg(long, default_value_t = 800.0)]
+    wind_center: f32,
+
+    /// How far the wind layer's centre sweeps either way, in octaves
+    #[arg(long, default_value_t = 1.0)]
+    wind_sweep: f32,
+
+    /// Overlay a metronome click on every beat of --tempo, accenting each bar's downbeat
+    #[arg(long)]
+    click: bool,
+
+    /// Time signature the --click accents bars by, e.g. 3/4 or 6/8
+    #[arg(long, default_value = "4/4", value_parser = parse_time_signature)]
+    time_sig: TimeSignature,
+
+    /// Kind of noise in the --noise-level layer
+    #[arg(long, value_enum, default_value_t = NoiseType::White)]
+    noise_type: NoiseType,
+
+    /// Impulse response WAV to convolve the reverb send with, instead of the feedback delay
+    #[arg(long)]
+    reverb_ir: Option<String>,
+
+    /// Most voices sounding at once, stealing one for each note past the cap (0 = no limit)
+    #[arg(long, default_value_t = 0)]
+    max_voices: usize,
+
+    /// Which voice --max-voices steals for a new note
+    #[arg(long, value_enum, default_value_t = StealPolicy::Oldest)]
+    voice_steal: StealPolicy,
+
+    /// Spread voices within this many cents either side of the base frequency, instead of --detune-range
+    #[arg(long)]
+    detune_cents: Option<f32>,
+}
+
+/// Tools for working with files that have already been rendered
+#[derive(Subcommand, Debug, Clone, PartialEq)]
+enum Command {
+    /// Print the format, duration and peak/RMS levels of a WAV file
+    Info {
+        /// WAV file to inspect
+        file: String,
+    },
+    /// Rescale a WAV file so its peak sits at a target level
+    Normalize {
+        /// WAV file to read
+        input: String,
+        /// Where to write the normalized copy
+        output: String,
+        /// Target level in dBFS (default: -1 for peak, -18 for rms)
+        #[arg(long, allow_negative_numbers = true)]
+        target: Option<f32>,
+        /// Whether the target is the peak or the RMS level
+        #[arg(long, value_enum, default_value_t = normalize::NormalizeMode::Peak)]
+        normalize_mode: normalize::NormalizeMode,
+    },
+    /// Render every .json config in a directory
+    Batch {
+        /// Directory of JSON configs
+        config_dir: String,
+        /// Where to write the renders, named after their configs
+        out_dir: String,
+        /// Afterwards, bring every WAV rendered to one RMS level
+        #[arg(long)]
+        loudness_match: bool,
+        /// RMS level in dBFS for --loudness-match (default: the renders' average, kept
+        /// clear of clipping)
+        #[arg(long, allow_negative_numbers = true, requires = "loudness_match")]
+        loudness_target: Option<f32>,
+    },
+    /// Swap the channels of a WAV file or invert the polarity of its right channel
+    Process {
+        /// WAV file to read
+        input: String,
+        /// Where to write the processed copy
+        output: String,
+        /// Exchange the left and right channels
+        #[arg(long)]
+        swap_channels: bool,
+        /// Invert the right channel (after any swap)
+        #[arg(long)]
+        invert_right: bool,
+    },
 }
 
 impl CLI {
+    /// Parses `args`, using the selected preset's values in place of the
+    /// built-in defaults so only flags given on the command line override it
+    fn parse_with_preset<I, T>(args: I) -> CLI
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<std::ffi::OsString> + Clone,
+    {
+        let args: Vec<std::ffi::OsString> = args.into_iter().map(Into::into).collect();
+        let cli = CLI::parse_from(&args);
+        let Some(preset) = cli.preset.as_deref().and_then(JsonConfig::preset) else {
+            return cli;
+        };
+
+        // JSON widens f32 fields to f64; print them back at f32 precision
+        let scalar = |value: &serde_json::Value| match value.as_f64() {
+            Some(float) if value.is_f64() => (float as f32).to_string(),
+            _ => value.to_string(),
+        };
+
+        let mut command = CLI::command();
+        let values = serde_json::to_value(preset).expect("presets serialize to JSON");
+        for (key, value) in values.as_object().into_iter().flatten() {
+            let default = match value {
+                serde_json::Value::Null => continue,
+                serde_json::Value::String(s) => s.clone(),
+                serde_json::Value::Array(items) => {
+                    let items: Vec<String> = items.iter().map(scalar).collect();
+                    // ranges are "min:max" on the command line, lists are comma separated
+                    items.join(if key.ends_with("_range") { ":" } else { "," })
+                }
+                other => scalar(other),
+            };
+            if default.is_empty() || !command.get_arguments().any(|a| a.get_id() == key) {
+                continue;
+            }
+            command = command.mut_arg(key, |arg| arg.default_value(default));
+        }
+
+        CLI::from_arg_matches(&command.get_matches_from(args)).unwrap_or_else(|err| err.exit())
+    }
+
+    #[allow(clippy::wrong_self_convention)]
     fn to_params(self) -> Result<GeneratorParams, ConfigError> {
         // If config file is specified, load from JSON
         if let Some(config_path) = &self.config {
-            return Ok(JsonConfig::from_file(config_path)?.into());
+            return JsonConfig::from_file(config_path)?.to_params();
         }
 
         // Otherwise use CLI parameters
@@ -194,10 +895,14 @@ impl CLI {
     }
 }
 
-#[derive(Debug, Clone, Copy, ValueEnum)]
-enum OutputType {
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputType {
     MIDI,
     WAV,
+    OGG,
+    FLAC,
 }
 
 impl OutputType {
@@ -205,20 +910,176 @@ impl OutputType {
         match self {
             Self::MIDI => "midi",
             Self::WAV => "wav",
+            Self::OGG => "ogg",
+            Self::FLAC => "flac",
+        }
+    }
+
+    /// Uses `format` if given, otherwise guesses from the extension of
+    /// `filename`, falling back to WAV
+    fn resolve(format: Option<OutputType>, filename: Option<&str>) -> OutputType {
+        if let Some(format) = format {
+            return format;
+        }
+        let extension = filename
+            .and_then(|f| std::path::Path::new(f).extension())
+            .and_then(|e| e.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("ogg") => Self::OGG,
+            Some("flac") => Self::FLAC,
+            Some("mid" | "midi") => Self::MIDI,
+            _ => Self::WAV,
+        }
+    }
+}
+
+/// Parameters that can follow an `--automate` curve instead of a fixed value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AutomationTarget {
+    ReverbMix,
+    NoiseLevel,
+}
+
+/// A parameter curve through `(seconds, value)` breakpoints, sorted by time
+#[derive(Debug, Clone, PartialEq)]
+pub struct Automation {
+    pub target: AutomationTarget,
+    pub breakpoints: Vec<(f32, f32)>,
+}
+
+impl Automation {
+    /// Value at `time`, linearly interpolated between the surrounding
+    /// breakpoints and held flat before the first and after the last
+    pub fn value_at(&self, time: f32) -> f32 {
+        let next = self.breakpoints.partition_point(|&(t, _)| t <= time);
+        match (next.checked_sub(1), self.breakpoints.get(next)) {
+            (Some(i), Some(&(t1, v1))) => {
+                let (t0, v0) = self.breakpoints[i];
+                v0 + (v1 - v0) * (time - t0) / (t1 - t0)
+            }
+            (Some(i), None) => self.breakpoints[i].1,
+            (None, Some(&(_, v))) => v,
+            (None, None) => 0.0,
+        }
+    }
+}
+
+/// How voice frequency ratios are drawn from `--detune-range`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DetuneDistribution {
+    /// Every ratio in the range is equally likely
+    #[default]
+    Uniform,
+    /// Normal distribution centred in the range, with a quarter of the
+    /// range as its standard deviation so about 95% of voices land inside it
+    Gaussian,
+}
+
+/// Kind of noise in the `--noise-level` layer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NoiseType {
+    /// Random white noise
+    #[default]
+    White,
+    /// Smooth value noise, a deterministic function of time and the seed
+    Value,
+}
+
+/// Which voice `--max-voices` gives up when a note needs one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StealPolicy {
+    /// The voice requested longest ago
+    #[default]
+    Oldest,
+    /// The voice with the lowest average level
+    Quietest,
+}
+
+/// Shape of the attack and release ramps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FadeCurve {
+    /// Straight-line ramp
+    #[default]
+    Linear,
+    /// Slow start, fast finish; fades in the way the ear expects
+    Exponential,
+    /// Fast start, slow finish
+    Logarithmic,
+}
+
+impl FadeCurve {
+    /// Exponent of the exponential curve; the logarithmic curve uses its inverse
+    const EXPONENT: f32 = 2.0;
+
+    /// Maps linear ramp progress in 0.0..=1.0 onto the curve
+    fn shape(self, progress: f32) -> f32 {
+        match self {
+            FadeCurve::Linear => progress,
+            FadeCurve::Exponential => progress.powf(Self::EXPONENT),
+            FadeCurve::Logarithmic => progress.powf(Self::EXPONENT.recip()),
         }
     }
 }
 
+/// How FM modulators are assigned to carrier voices
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FmPairing {
+    /// Each odd voice is modulated at the frequency of the even voice
+    /// before it
+    #[default]
+    Pairs,
+    /// Every voice after the first is modulated at the previous voice's
+    /// frequency
+    #[value(alias = "chain")]
+    #[serde(alias = "chain")]
+    Adjacent,
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("Hound WAV Error")]
     HoundError(#[from] hound::Error),
     #[error("Configuration Error")]
     ConfigError(#[from] ConfigError),
+    #[error("Thread Pool Error")]
+    ThreadPoolError(#[from] rayon::ThreadPoolBuildError),
+    #[error("IO Error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[cfg(feature = "ogg")]
+    #[error("Vorbis Encoder Error")]
+    VorbisError(#[from] vorbis_rs::VorbisError),
+    #[error("Unsupported Output Format: {0}")]
+    UnsupportedFormat(&'static str),
+    #[error("Playback Error: {0}")]
+    PlaybackError(String),
+    #[error("Duration Error: {duration}s is longer than --max-duration {max}s")]
+    DurationTooLong { duration: f32, max: f32 },
+    #[error("Duration Error: {0} samples per channel is more than a render can hold")]
+    TooManySamples(u64),
+    #[error("Spectrogram Error")]
+    SpectrogramError(#[from] png::EncodingError),
+    #[error("Render Range Error: {start}s is past the end of the {duration}s piece")]
+    RenderRangeOutside { start: f32, duration: f32 },
+    #[error("Batch Error: {0} config(s) failed to render")]
+    BatchFailed(usize),
+    #[error("Progression Error: {0}")]
+    ProgressionError(String),
+    #[error("Tempo Error: {0} BPM is outside {min} to {max}", min = Tempo::MIN, max = Tempo::MAX)]
+    TempoOutOfRange(u16),
 }
 
 /// JSON configuration for ambient synthesis parameters
+///
+/// Every field is optional in the file; anything left out takes its value
+/// from [`JsonConfig::default`], so a config only needs what it overrides.
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
 pub struct JsonConfig {
     /// Output WAV file
     pub output: Option<String>,
@@ -226,7 +1087,7 @@ pub struct JsonConfig {
     pub duration: f32,
     /// Sample rate (Hz)
     pub sample_rate: u32,
-    /// Number of voices
+    /// Number of voices (0 renders only the noise and grain layers)
     pub voices: usize,
     /// Base frequency (Hz) for voices
     pub base_freq: f32,
@@ -242,6 +1103,143 @@ pub struct JsonConfig {
     pub release: f32,
     /// Reverb mix level (0.0 to 1.0)
     pub reverb_mix: f32,
+    /// Stereo pan spread (0.0 = mono center, 1.0 = full width)
+    pub pan_spread: f32,
+    /// Distribute voices evenly across the stereo field instead of oscillating
+    pub static_pan: bool,
+    /// Write a single-channel WAV (left and right averaged)
+    pub mono: bool,
+    /// Seed for reproducible output (random if omitted)
+    pub seed: Option<u64>,
+    /// Worker threads for rendering (0 = one per CPU core)
+    pub threads: usize,
+    /// Use a precomputed sine wavetable instead of sin() for oscillators
+    pub fast_sine: bool,
+    /// FM modulation index (0.0 disables FM)
+    pub fm_index: f32,
+    /// Which voices get a sine modulator, at a neighbour's frequency, when FM is enabled
+    pub fm_pairing: FmPairing,
+    /// Master gain in dB applied to the final mix
+    pub gain_db: f32,
+    /// Per-voice linear gain weights; voices past the end of the list use 1.0
+    pub voice_gains: Vec<f32>,
+    /// Skip the attack/release envelope and crossfade the ends for gapless looping
+    pub loop_mode: bool,
+    /// Length (seconds) of the loop-point crossfade in loop mode
+    pub crossfade_seconds: f32,
+    /// Grains per second in the grain cloud (0.0 disables it)
+    pub grain_density: f32,
+    /// Length (seconds) of each grain
+    pub grain_duration: f32,
+    /// Maximum grain detune (semitones) either side of the base frequency
+    pub grain_pitch_jitter: f32,
+    /// Sustain a chord such as "Cmaj7", one voice per chord tone
+    pub chord: Option<String>,
+    /// Binaural beat (Hz): the right ear plays each voice this much higher (0.0 disables)
+    pub binaural: f32,
+    /// Tempo (BPM) used by --lfo-sync and --click, from 20 to 400
+    pub tempo: u16,
+    /// Pick LFO rates from note divisions of --tempo instead of --lfo-rate-range
+    pub lfo_sync: bool,
+    /// Output format (guessed from the output extension when omitted, else WAV)
+    pub format: Option<OutputType>,
+    /// OGG Vorbis quality from -0.2 to 1.0
+    pub ogg_quality: f32,
+    /// Bits per sample for WAV and FLAC output (16 or 24)
+    pub bit_depth: u16,
+    /// Harmonic series for every voice as ratio:amplitude pairs (a pure sine when empty)
+    pub partials: Vec<(f32, f32)>,
+    /// Mid/side stereo width of the final mix (0.0 = mono, 1.0 = unchanged, >1.0 = wider)
+    pub width: f32,
+    /// Tape-style tanh saturation drive applied before reverb (0.0 bypasses it)
+    pub saturation: f32,
+    /// Cutoff (Hz) of the low-pass filter on the filtered noise layer
+    pub noise_cutoff_hz: f32,
+    /// Shape of the attack and release ramps
+    pub fade_curve: FadeCurve,
+    /// High-pass cutoff (Hz) on the reverb send so the bass stays dry (0.0 disables it)
+    pub reverb_hpf: f32,
+    /// Chorus delay sweep as a fraction of its 5 ms maximum (0.0 disables the chorus)
+    pub chorus_depth: f32,
+    /// Rate (Hz) of the LFO sweeping the chorus delay
+    pub chorus_rate: f32,
+    /// Depth of the global auto-pan sweep over the whole mix (0.0 disables it)
+    pub autopan_depth: f32,
+    /// Rate (Hz) of the global auto-pan sweep
+    pub autopan_rate: f32,
+    /// Output channels: 2 for stereo, 4 for quad (front L/R, rear L/R)
+    #[serde(alias = "channels")]
+    pub output_channels: u16,
+    /// Breakpoint automation such as "reverb_mix:0.1@0,0.6@120" (value@seconds); repeatable
+    pub automation: Vec<String>,
+    /// Longest duration in seconds a render may have, to catch accidental multi-hour renders
+    pub max_duration: f32,
+    /// Range of voice frequency ratios to the base frequency, as min:max
+    pub detune_range: [f32; 2],
+    /// How voice frequency ratios are spread over --detune-range
+    pub detune_dist: DetuneDistribution,
+    /// Anti-click fade (ms) over the very start of the file, independent of --attack
+    pub fade_in_ms: f32,
+    /// Anti-click fade (ms) over the very end of the file, independent of --release
+    pub fade_out_ms: f32,
+    /// Seconds of silence appended before the effects so the reverb can ring out
+    pub reverb_tail: f32,
+    /// Key such as "D dorian" whose notes --glide-rate moves voices between, and
+    /// which --progression's numerals count from
+    #[serde(alias = "key")]
+    pub scale: Option<String>,
+    /// Portamento speed (semitones per second) of voices gliding between --scale notes (0.0 disables it)
+    pub glide_rate: f32,
+    /// How far the noise layers dip while the voices swell (0.0 disables it, 1.0 ducks fully)
+    pub noise_duck: f32,
+    /// Haas widening: delay the right channel by this many ms (5-30 is typical, 0.0 is off)
+    pub haas_ms: f32,
+    /// Also write a spectrogram of the mono-summed mix to this PNG
+    pub spectrogram: Option<String>,
+    /// FFT window size in samples for --spectrogram
+    pub spectrogram_window: usize,
+    /// Samples between --spectrogram frames (one image column each)
+    pub spectrogram_hop: usize,
+    /// Add triangular (TPDF) dither before quantizing 16-bit output
+    pub dither: bool,
+    /// Shape 16-bit quantization error toward high frequencies with error feedback
+    pub noise_shaping: bool,
+    /// Seconds over which the reverb mix ramps up from dry at the start (0.0 = instant)
+    pub reverb_fade: f32,
+    /// Notes such as "C4,E4,G4" whose tuned comb filters the filtered noise rings through
+    pub resonators: Vec<String>,
+    /// Seconds for a --resonators ring to die away by 60 dB
+    pub resonator_decay: f32,
+    /// Chord progression in Roman numerals such as "I,vi,IV,V", played in the --scale key
+    pub progression: Option<String>,
+    /// Seconds each chord of --progression lasts
+    pub chord_duration: f32,
+    /// Also write each layer of the mix (voices, noise, grains, filtered noise, reverb) to its own WAV in this directory
+    pub stems: Option<String>,
+    /// Render voices at 2 or 4 times the sample rate and filter back down, against aliasing (1 disables it)
+    pub oversample: usize,
+    /// Seconds over which the voices enter one after another, and leave in reverse order
+    pub voice_stagger: f32,
+    /// Level of the swept band-pass noise "wind" layer (0.0 disables it)
+    pub wind_level: f32,
+    /// Centre frequency (Hz) of the wind layer's band
+    pub wind_center: f32,
+    /// How far the wind layer's centre sweeps either way, in octaves
+    pub wind_sweep: f32,
+    /// Overlay a metronome click on every beat of --tempo, accenting each bar's downbeat
+    pub click: bool,
+    /// Time signature the --click accents bars by, e.g. 3/4 or 6/8
+    pub time_sig: String,
+    /// Kind of noise in the --noise-level layer
+    pub noise_type: NoiseType,
+    /// Impulse response WAV to convolve the reverb send with, instead of the feedback delay
+    pub reverb_ir: Option<String>,
+    /// Most voices sounding at once, stealing one for each note past the cap (0 = no limit)
+    pub max_voices: usize,
+    /// Which voice --max-voices steals for a new note
+    pub voice_steal: StealPolicy,
+    /// Spread voices within this many cents either side of the base frequency, instead of --detune-range
+    pub detune_cents: Option<f32>,
 }
 
 impl Default for JsonConfig {
@@ -258,11 +1256,143 @@ impl Default for JsonConfig {
             attack: 5.0,
             release: 10.0,
             reverb_mix: 0.3,
+            pan_spread: 1.0,
+            static_pan: false,
+            mono: false,
+            seed: None,
+            threads: 0,
+            fast_sine: false,
+            fm_index: 0.0,
+            fm_pairing: FmPairing::Pairs,
+            gain_db: 0.0,
+            voice_gains: Vec::new(),
+            loop_mode: false,
+            crossfade_seconds: 2.0,
+            grain_density: 0.0,
+            grain_duration: 0.08,
+            grain_pitch_jitter: 7.0,
+            chord: None,
+            binaural: 0.0,
+            tempo: 120,
+            lfo_sync: false,
+            format: None,
+            ogg_quality: 0.5,
+            bit_depth: 16,
+            partials: Vec::new(),
+            width: 1.0,
+            saturation: 0.0,
+            noise_cutoff_hz: 739.5,
+            fade_curve: FadeCurve::Linear,
+            reverb_hpf: 0.0,
+            chorus_depth: 0.0,
+            chorus_rate: 0.3,
+            autopan_depth: 0.0,
+            autopan_rate: 0.05,
+            output_channels: 2,
+            automation: Vec::new(),
+            max_duration: 3600.0,
+            detune_range: [0.8, 1.2],
+            detune_dist: DetuneDistribution::Uniform,
+            fade_in_ms: 0.0,
+            fade_out_ms: 0.0,
+            reverb_tail: 0.0,
+            scale: None,
+            glide_rate: 0.0,
+            noise_duck: 0.0,
+            haas_ms: 0.0,
+            spectrogram: None,
+            spectrogram_window: 2048,
+            spectrogram_hop: 512,
+            dither: false,
+            noise_shaping: false,
+            reverb_fade: 0.0,
+            resonators: Vec::new(),
+            resonator_decay: 2.0,
+            progression: None,
+            chord_duration: 4.0,
+            stems: None,
+            oversample: 1,
+            voice_stagger: 0.0,
+            wind_level: 0.0,
+            wind_center: 800.0,
+            wind_sweep: 1.0,
+            click: false,
+            time_sig: "4/4".to_string(),
+            noise_type: NoiseType::White,
+            reverb_ir: None,
+            max_voices: 0,
+            voice_steal: StealPolicy::Oldest,
+            detune_cents: None,
         }
     }
 }
 
+/// Names of the built-in presets accepted by `--preset`
+pub const PRESETS: [&str; 4] = ["airport", "drone", "glacial", "textured"];
+
 impl JsonConfig {
+    /// Returns the built-in preset called `name`, if there is one
+    pub fn preset(name: &str) -> Option<JsonConfig> {
+        let base = JsonConfig::default();
+        let config = match name {
+            // Music for Airports: a few slow, sparse, overlapping tones
+            "airport" => JsonConfig {
+                duration: 180.0,
+                voices: 5,
+                base_freq: 262.0,
+                lfo_rate_range: [0.02, 0.08],
+                mod_depth_range: [0.4, 0.9],
+                attack: 10.0,
+                release: 20.0,
+                reverb_mix: 0.5,
+                static_pan: true,
+                grain_density: 2.0,
+                ..base
+            },
+            // a sustained minor chord with barely moving modulation
+            "drone" => JsonConfig {
+                duration: 300.0,
+                base_freq: 110.0,
+                lfo_rate_range: [0.01, 0.03],
+                mod_depth_range: [0.1, 0.3],
+                attack: 15.0,
+                release: 20.0,
+                reverb_mix: 0.4,
+                grain_density: 0.0,
+                chord: Some("Am".to_string()),
+                ..base
+            },
+            // many low voices drifting over very long fades
+            "glacial" => JsonConfig {
+                duration: 600.0,
+                voices: 8,
+                base_freq: 165.0,
+                lfo_rate_range: [0.005, 0.02],
+                mod_depth_range: [0.6, 1.0],
+                attack: 30.0,
+                release: 45.0,
+                reverb_mix: 0.6,
+                pan_spread: 0.6,
+                grain_density: 1.0,
+                ..base
+            },
+            // noisy and grainy, closer to the Microsound reference
+            "textured" => JsonConfig {
+                duration: 120.0,
+                voices: 6,
+                base_freq: 440.0,
+                noise_level: 0.02,
+                reverb_mix: 0.45,
+                grain_density: 40.0,
+                grain_duration: 0.04,
+                grain_pitch_jitter: 12.0,
+                ..base
+            },
+            _ => return None,
+        };
+        Some(config)
+    }
+
     /// Load configuration from a JSON file
     pub fn from_file<P: AsRef<std::path::Path>>(path: P) -> Result<Self, ConfigError> {
         let content = std::fs::read_to_string(path)?;
@@ -282,29 +1412,141 @@ impl JsonConfig {
         Self::default().to_file(path)
     }
 
-    pub fn to_params(self) -> GeneratorParams {
-        self.into()
+    pub fn to_params(self) -> Result<GeneratorParams, ConfigError> {
+        self.try_into()
     }
 }
 
-impl Into<GeneratorParams> for JsonConfig {
-    fn into(self) -> GeneratorParams {
-        GeneratorParams {
-            filename: match &self.output {
-                Some(output) => format!("{}_{}", v4_uuid(), output.to_string()),
-                None => generate_filename("ambient", OutputType::WAV),
-            },
-            sample_rate: self.sample_rate,
-            duration: self.duration,
-            lfo_range: format!("{}:{}", self.lfo_rate_range[0], self.lfo_rate_range[1]),
-            mod_depth_range: format!("{}:{}", self.mod_depth_range[0], self.mod_depth_range[1]),
-            voices: self.voices,
-            base_freq: self.base_freq,
-            noise_level: self.noise_level,
-            attack: self.attack,
-            release: self.release,
-            reverb_mix: self.reverb_mix,
+impl TryFrom<JsonConfig> for GeneratorParams {
+    type Error = ConfigError;
+
+    fn try_from(config: JsonConfig) -> Result<Self, ConfigError> {
+        let output_channels = parse_channels(&config.output_channels.to_string())
+            .map_err(ConfigError::invalid("output_channels"))?;
+        // the CLI's --channels conflicts with --mono
+        if config.mono && output_channels != 2 {
+            return Err(ConfigError::InvalidValue(
+                "output_channels",
+                "can't be combined with mono".to_string(),
+            ));
         }
+        // the CLI's --glide-rate requires --scale
+        if config.glide_rate != 0.0 && config.scale.is_none() {
+            return Err(ConfigError::InvalidValue(
+                "glide_rate",
+                "needs a scale to glide between".to_string(),
+            ));
+        }
+        Ok(GeneratorParams {
+            filename: match &config.output {
+                Some(output) => format!("{}_{}", v4_uuid(), output),
+                None => generate_filename("ambient", OutputType::resolve(config.format, None)),
+            },
+            sample_rate: config.sample_rate,
+            duration: config.duration,
+            lfo_range: format!("{}:{}", config.lfo_rate_range[0], config.lfo_rate_range[1]),
+            mod_depth_range: format!(
+                "{}:{}",
+                config.mod_depth_range[0], config.mod_depth_range[1]
+            ),
+            voices: config.voices,
+            base_freq: config.base_freq,
+            noise_level: config.noise_level,
+            attack: config.attack,
+            release: config.release,
+            reverb_mix: config.reverb_mix,
+            pan_spread: config.pan_spread,
+            static_pan: config.static_pan,
+            mono: config.mono,
+            seed: config.seed,
+            threads: config.threads,
+            fast_sine: config.fast_sine,
+            fm_index: config.fm_index,
+            fm_pairing: config.fm_pairing,
+            gain_db: config.gain_db,
+            voice_gains: config.voice_gains,
+            loop_mode: config.loop_mode,
+            crossfade_seconds: config.crossfade_seconds,
+            grain_density: config.grain_density,
+            grain_duration: config.grain_duration,
+            grain_pitch_jitter: config.grain_pitch_jitter,
+            chord: config
+                .chord
+                .as_deref()
+                .map(str::parse)
+                .transpose()
+                .map_err(ConfigError::invalid("chord"))?,
+            binaural: config.binaural,
+            tempo: parse_tempo(&config.tempo.to_string()).map_err(ConfigError::invalid("tempo"))?,
+            lfo_sync: config.lfo_sync,
+            format: OutputType::resolve(config.format, config.output.as_deref()),
+            ogg_quality: config.ogg_quality,
+            bit_depth: parse_bit_depth(&config.bit_depth.to_string())
+                .map_err(ConfigError::invalid("bit_depth"))?,
+            partials: config.partials,
+            width: config.width,
+            saturation: config.saturation,
+            noise_cutoff_hz: config.noise_cutoff_hz,
+            fade_curve: config.fade_curve,
+            reverb_hpf: config.reverb_hpf,
+            chorus_depth: config.chorus_depth,
+            chorus_rate: config.chorus_rate,
+            autopan_depth: config.autopan_depth,
+            autopan_rate: config.autopan_rate,
+            output_channels,
+            automation: config
+                .automation
+                .iter()
+                .map(|a| parse_automation(a))
+                .collect::<Result<_, _>>()
+                .map_err(ConfigError::invalid("automation"))?,
+            max_duration: config.max_duration,
+            detune_range: format!("{}:{}", config.detune_range[0], config.detune_range[1]),
+            detune_dist: config.detune_dist,
+            fade_in_ms: config.fade_in_ms,
+            fade_out_ms: config.fade_out_ms,
+            reverb_tail: config.reverb_tail,
+            scale: config
+                .scale
+                .as_deref()
+                .map(str::parse)
+                .transpose()
+                .map_err(ConfigError::invalid("scale"))?,
+            glide_rate: config.glide_rate,
+            noise_duck: config.noise_duck,
+            haas_ms: config.haas_ms,
+            spectrogram: config.spectrogram,
+            spectrogram_window: config.spectrogram_window,
+            spectrogram_hop: config.spectrogram_hop,
+            dither: config.dither,
+            noise_shaping: config.noise_shaping,
+            reverb_fade: config.reverb_fade,
+            resonators: config
+                .resonators
+                .iter()
+                .map(|note| note.parse())
+                .collect::<Result<_, _>>()
+                .map_err(ConfigError::invalid("resonators"))?,
+            resonator_decay: config.resonator_decay,
+            render_range: None,
+            progression: config.progression,
+            chord_duration: config.chord_duration,
+            stems: config.stems,
+            oversample: parse_oversample(&config.oversample.to_string())
+                .map_err(ConfigError::invalid("oversample"))?,
+            voice_stagger: config.voice_stagger,
+            wind_level: config.wind_level,
+            wind_center: config.wind_center,
+            wind_sweep: config.wind_sweep,
+            click: config.click,
+            time_sig: parse_time_signature(&config.time_sig)
+                .map_err(ConfigError::invalid("time_sig"))?,
+            noise_type: config.noise_type,
+            reverb_ir: config.reverb_ir,
+            max_voices: config.max_voices,
+            voice_steal: config.voice_steal,
+            detune_cents: config.detune_cents,
+        })
     }
 }
 
@@ -315,6 +1557,15 @@ pub enum ConfigError {
     IoError(#[from] std::io::Error),
     #[error("JSON Parse Error: {0}")]
     JsonError(#[from] serde_json::Error),
+    #[error("Invalid {0}: {1}")]
+    InvalidValue(&'static str, String),
+}
+
+impl ConfigError {
+    /// Turns a parse error in the config's `field` into [`ConfigError::InvalidValue`]
+    fn invalid<E: fmt::Display>(field: &'static str) -> impl FnOnce(E) -> ConfigError {
+        move |err| ConfigError::InvalidValue(field, err.to_string())
+    }
 }
 
 pub struct GeneratorParams {
@@ -329,51 +1580,360 @@ pub struct GeneratorParams {
     attack: f32,
     release: f32,
     reverb_mix: f32,
+    pan_spread: f32,
+    static_pan: bool,
+    mono: bool,
+    seed: Option<u64>,
+    threads: usize,
+    fast_sine: bool,
+    fm_index: f32,
+    fm_pairing: FmPairing,
+    gain_db: f32,
+    voice_gains: Vec<f32>,
+    loop_mode: bool,
+    crossfade_seconds: f32,
+    grain_density: f32,
+    grain_duration: f32,
+    grain_pitch_jitter: f32,
+    chord: Option<Chord>,
+    binaural: f32,
+    tempo: u16,
+    lfo_sync: bool,
+    format: OutputType,
+    #[cfg_attr(not(feature = "ogg"), allow(dead_code))]
+    ogg_quality: f32,
+    bit_depth: u16,
+    partials: Vec<(f32, f32)>,
+    width: f32,
+    saturation: f32,
+    noise_cutoff_hz: f32,
+    fade_curve: FadeCurve,
+    reverb_hpf: f32,
+    chorus_depth: f32,
+    chorus_rate: f32,
+    autopan_depth: f32,
+    autopan_rate: f32,
+    output_channels: u16,
+    automation: Vec<Automation>,
+    max_duration: f32,
+    detune_range: String,
+    detune_dist: DetuneDistribution,
+    fade_in_ms: f32,
+    fade_out_ms: f32,
+    reverb_tail: f32,
+    scale: Option<Scale>,
+    glide_rate: f32,
+    noise_duck: f32,
+    haas_ms: f32,
+    spectrogram: Option<String>,
+    spectrogram_window: usize,
+    spectrogram_hop: usize,
+    dither: bool,
+    noise_shaping: bool,
+    reverb_fade: f32,
+    resonators: Vec<Note>,
+    resonator_decay: f32,
+    render_range: Option<(f32, f32)>,
+    progression: Option<String>,
+    chord_duration: f32,
+    stems: Option<String>,
+    oversample: usize,
+    voice_stagger: f32,
+    wind_level: f32,
+    wind_center: f32,
+    wind_sweep: f32,
+    click: bool,
+    time_sig: TimeSignature,
+    noise_type: NoiseType,
+    reverb_ir: Option<String>,
+    max_voices: usize,
+    voice_steal: StealPolicy,
+    detune_cents: Option<f32>,
 }
 
 impl GeneratorParams {
-    fn num_samples(&self) -> u32 {
-        (self.duration * self.sample_rate as f32) as u32
+    /// Samples per channel, rounded to the nearest sample. Computed in 64
+    /// bits so long renders at high sample rates don't wrap around.
+    fn num_samples(&self) -> u64 {
+        (self.duration as f64 * self.sample_rate as f64)
+            .round()
+            .max(0.0) as u64
+    }
+
+    /// `--tempo`, which [`Generator::new`] has checked is in range
+    fn tempo(&self) -> Tempo {
+        Tempo::new(self.tempo).expect("tempo is checked in Generator::new")
+    }
+
+    /// Extra samples rendered past the end to crossfade into the start in loop mode
+    fn crossfade_samples(&self) -> usize {
+        if !self.loop_mode {
+            return 0;
+        }
+        ((self.crossfade_seconds * self.sample_rate as f32) as usize)
+            .min(self.num_samples() as usize)
+    }
+
+    /// Value of `target` at `time`: its `--automate` curve if it has one,
+    /// else the fixed parameter
+    fn automated(&self, target: AutomationTarget, time: f32) -> f32 {
+        match self.automation.iter().find(|a| a.target == target) {
+            Some(automation) => automation.value_at(time),
+            None => match target {
+                AutomationTarget::ReverbMix => self.reverb_mix,
+                AutomationTarget::NoiseLevel => self.noise_level,
+            },
+        }
+    }
+
+    /// Chords of `--progression` in the `--scale` key, none without one
+    fn progression_chords(&self) -> Result<Vec<Chord>, String> {
+        let Some(numerals) = &self.progression else {
+            return Ok(Vec::new());
+        };
+        let scale = self
+            .scale
+            .as_ref()
+            .ok_or("--progression needs a --scale (or --key) to count from")?;
+        if self.chord_duration <= 0.0 || self.chord_duration.is_nan() {
+            return Err(format!(
+                "--chord-duration {}s must be positive",
+                self.chord_duration
+            ));
+        }
+        scale.progression(numerals).map_err(|err| err.to_string())
+    }
+
+    fn channels(&self) -> u16 {
+        if self.mono {
+            1
+        } else {
+            self.output_channels
+        }
+    }
+
+    /// Whether voices are spread over front and rear speaker pairs
+    fn quad(&self) -> bool {
+        self.channels() == 4
     }
 }
 
-impl Into<GeneratorParams> for CLI {
-    fn into(self) -> GeneratorParams {
+impl From<CLI> for GeneratorParams {
+    fn from(cli: CLI) -> Self {
         GeneratorParams {
-            filename: match &self.output {
+            filename: match &cli.output {
                 Some(output) => output.to_string(),
-                None => generate_filename("ambient", OutputType::WAV),
+                None => generate_filename("ambient", OutputType::resolve(cli.format, None)),
             },
-            sample_rate: self.sample_rate,
-            duration: self.duration,
-            lfo_range: self.lfo_rate_range,
-            mod_depth_range: self.mod_depth_range,
-            voices: self.voices,
-            base_freq: self.base_freq,
-            noise_level: self.noise_level,
-            attack: self.attack,
-            release: self.release,
-            reverb_mix: self.reverb_mix,
+            sample_rate: cli.sample_rate,
+            duration: cli.duration,
+            lfo_range: cli.lfo_rate_range,
+            mod_depth_range: cli.mod_depth_range,
+            voices: cli.voices,
+            base_freq: cli.base_freq,
+            noise_level: cli.noise_level,
+            attack: cli.attack,
+            release: cli.release,
+            reverb_mix: cli.reverb_mix,
+            pan_spread: cli.pan_spread,
+            static_pan: cli.static_pan,
+            mono: cli.mono,
+            seed: cli.seed,
+            threads: cli.threads,
+            fast_sine: cli.fast_sine,
+            fm_index: cli.fm_index,
+            fm_pairing: cli.fm_pairing,
+            gain_db: cli.gain_db,
+            voice_gains: cli.voice_gains,
+            loop_mode: cli.loop_mode,
+            crossfade_seconds: cli.crossfade_seconds,
+            grain_density: cli.grain_density,
+            grain_duration: cli.grain_duration,
+            grain_pitch_jitter: cli.grain_pitch_jitter,
+            chord: cli.chord,
+            binaural: cli.binaural,
+            tempo: cli.tempo,
+            lfo_sync: cli.lfo_sync,
+            format: OutputType::resolve(cli.format, cli.output.as_deref()),
+            ogg_quality: cli.ogg_quality,
+            bit_depth: cli.bit_depth,
+            partials: cli.partials,
+            width: cli.width,
+            saturation: cli.saturation,
+            noise_cutoff_hz: cli.noise_cutoff_hz,
+            fade_curve: cli.fade_curve,
+            reverb_hpf: cli.reverb_hpf,
+            chorus_depth: cli.chorus_depth,
+            chorus_rate: cli.chorus_rate,
+            autopan_depth: cli.autopan_depth,
+            autopan_rate: cli.autopan_rate,
+            output_channels: cli.output_channels,
+            automation: cli.automation,
+            max_duration: cli.max_duration,
+            detune_range: cli.detune_range,
+            detune_dist: cli.detune_dist,
+            fade_in_ms: cli.fade_in_ms,
+            fade_out_ms: cli.fade_out_ms,
+            reverb_tail: cli.reverb_tail,
+            scale: cli.scale,
+            glide_rate: cli.glide_rate,
+            noise_duck: cli.noise_duck,
+            haas_ms: cli.haas_ms,
+            spectrogram: cli.spectrogram,
+            spectrogram_window: cli.spectrogram_window,
+            spectrogram_hop: cli.spectrogram_hop,
+            dither: cli.dither,
+            noise_shaping: cli.noise_shaping,
+            reverb_fade: cli.reverb_fade,
+            resonators: cli.resonators,
+            resonator_decay: cli.resonator_decay,
+            render_range: cli
+                .render_range
+                .or(cli.preview.map(|seconds| (0.0, seconds))),
+            progression: cli.progression,
+            chord_duration: cli.chord_duration,
+            stems: cli.stems,
+            oversample: cli.oversample,
+            voice_stagger: cli.voice_stagger,
+            wind_level: cli.wind_level,
+            wind_center: cli.wind_center,
+            wind_sweep: cli.wind_sweep,
+            click: cli.click,
+            time_sig: cli.time_sig,
+            noise_type: cli.noise_type,
+            reverb_ir: cli.reverb_ir,
+            max_voices: cli.max_voices,
+            voice_steal: cli.voice_steal,
+            detune_cents: cli.detune_cents,
         }
     }
 }
 
-struct Generator {
+/// Note divisions tempo-synced LFO rates are drawn from
+const LFO_SYNC_DIVISIONS: [Duration; 4] = [
+    Duration::Whole,
+    Duration::Half,
+    Duration::Quarter,
+    Duration::Eighth,
+];
+
+/// Seconds neighbouring chords of a `--progression` crossfade over, at most
+/// the length of a chord
+const CHORD_CROSSFADE: f64 = 1.0;
+
+/// Maximum random detune (cents) of each chord-tone voice
+const CHORD_DETUNE_CENTS: f32 = 0.8;
+
+/// Samples per independently seeded render chunk
+const CHUNK_SIZE: usize = 4096;
+
+/// Number of samples rendered between progress callback invocations
+const PROGRESS_INTERVAL: usize = CHUNK_SIZE * 64;
+
+/// First RNG stream of the rear pair's chunks in quad output
+const REAR_STREAM: u64 = 1 << 40;
+
+/// Centre delay of the chorus in seconds
+const CHORUS_DELAY: f32 = 0.015;
+
+/// Largest chorus sweep either side of the centre delay, in seconds
+const CHORUS_SWEEP: f32 = 0.005;
+
+/// RNG stream of the `--dither` noise
+const DITHER_STREAM: u64 = 1 << 41;
+
+/// RNG stream used by the serial filtered-noise pass; chunk streams follow it
+const FILTER_STREAM: u64 = 0;
+
+/// RNG stream of the `--wind-level` noise
+const WIND_STREAM: u64 = 1 << 42;
+
+/// Length of a `--click` in seconds
+const CLICK_SECONDS: f64 = 0.015;
+
+/// Callback receiving the fraction of the render completed (0.0 to 1.0)
+type ProgressCallback = Box<dyn Fn(f32) + Send + Sync>;
+
+pub struct Generator {
     params: GeneratorParams,
     voices: Vec<Voice>,
+    grains: GrainCloud,
     num_samples: u32,
     samples: Vec<(f32, f32)>,
+    /// Rear left/right pair in quad output, empty otherwise
+    rear: Vec<(f32, f32)>,
+    /// Master seed every random choice is derived from
+    seed: u64,
+    /// One-pole coefficient for `--noise-cutoff` at the render's sample rate
+    filter_coeff: f32,
     /// Filter state for noise filtering
     /// TODO: change to 2-tuple
     filter_prev_l: f32,
     filter_prev_r: f32,
+    /// Band-pass filter state of the `--wind-level` layer
+    wind: Wind,
+    /// Left and right `--reverb-ir`, loaded at the render's sample rate
+    impulse_response: Option<(Vec<f32>, Vec<f32>)>,
+    progress: Option<ProgressCallback>,
+    /// Index in the full piece of the first sample rendered, past 0 with
+    /// `--render-range`
+    first_sample: usize,
+    /// Layers kept apart for `--stems`, empty without it
+    stems: Vec<Stem>,
+}
+
+/// A layer of the mix that `--stems` writes on its own
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Layer {
+    Voices,
+    /// White noise
+    Noise,
+    Grains,
+    /// Low-passed noise, through the resonators if there are any
+    Filtered,
+    /// Swept band-passed noise
+    Wind,
+    /// Wet signal of the reverb
+    Reverb,
+}
+
+impl Layer {
+    fn name(self) -> &'static str {
+        match self {
+            Layer::Voices => "voices",
+            Layer::Noise => "noise",
+            Layer::Grains => "grains",
+            Layer::Filtered => "filtered",
+            Layer::Wind => "wind",
+            Layer::Reverb => "reverb",
+        }
+    }
+}
+
+/// One layer's own front and rear buffers
+#[derive(Debug, Clone)]
+struct Stem {
+    layer: Layer,
+    samples: Vec<(f32, f32)>,
+    rear: Vec<(f32, f32)>,
+}
+
+/// What `reverb` leaves in the buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ReverbPart {
+    /// The dry and wet signals blended by the reverb mix
+    Mix,
+    /// The dry signal's share of the blend alone
+    Dry,
+    /// The wet signal's share of the blend alone
+    Wet,
 }
 
 impl Generator {
-    fn parse_range(s: &String, default_min: f32, default_max: f32) -> (f32, f32) {
+    fn parse_range(s: &str, default_min: f32, default_max: f32) -> (f32, f32) {
         let parts: Vec<&str> = s.split(':').collect();
         let min: f32 = parts
-            .get(0)
+            .first()
             .and_then(|v| v.parse().ok())
             .unwrap_or(default_min);
         let max: f32 = parts
@@ -383,97 +1943,566 @@ impl Generator {
         (min, max)
     }
 
-    fn spec(sample_rate: u32) -> WavSpec {
+    fn spec(sample_rate: u32, channels: u16, bits_per_sample: u16) -> WavSpec {
         WavSpec {
-            channels: 2,
-            sample_rate: sample_rate,
-            bits_per_sample: 16,
+            channels,
+            sample_rate,
+            bits_per_sample,
             sample_format: hound::SampleFormat::Int,
         }
     }
 
-    fn generate_voices(params: &GeneratorParams) -> Vec<Voice> {
-        let mut rng = rand::rng();
+    fn generate_voices(params: &GeneratorParams, rng: &mut impl Rng) -> Vec<Voice> {
         let (lfo_min, lfo_max) = Self::parse_range(&params.lfo_range, 0.05, 0.2);
         let (depth_min, depth_max) = Self::parse_range(&params.mod_depth_range, 0.5, 1.0);
-        (0..params.voices)
-            .map(|_| {
-                let freq = params.base_freq * rng.random_range(0.8..1.2);
-                let lfo_rate = rng.random_range(lfo_min..lfo_max);
+        let (detune_min, detune_max) = match params.detune_cents {
+            Some(cents) => Self::cents_range(cents),
+            None => Self::parse_range(&params.detune_range, 0.8, 1.2),
+        };
+        let pan_spread = params.pan_spread.clamp(0.0, 1.0);
+        let wavetable = params.fast_sine.then(wavetable::sine_table);
+        let binaural = if params.mono && params.binaural != 0.0 {
+            eprintln!("warning: --binaural has no effect on mono output, ignoring it");
+            0.0
+        } else {
+            params.binaural
+        };
+        let progression = params.progression_chords().unwrap_or_default();
+        // a progression takes up to --voices tones of each chord, in turn
+        let chord_tones: Option<Vec<(f32, Option<ChordSlot>)>> = if !progression.is_empty() {
+            let duration = params.chord_duration as f64;
+            let tones = progression.iter().enumerate().flat_map(|(index, chord)| {
+                let slot = ChordSlot {
+                    index,
+                    count: progression.len(),
+                    duration,
+                    crossfade: CHORD_CROSSFADE.min(duration),
+                };
+                Self::chord_frequencies(chord, params.base_freq)
+                    .into_iter()
+                    .take(params.voices)
+                    .map(move |freq| (freq, Some(slot)))
+            });
+            Some(tones.collect())
+        } else {
+            params.chord.as_ref().map(|chord| {
+                Self::chord_frequencies(chord, params.base_freq)
+                    .into_iter()
+                    .map(|freq| (freq, None))
+                    .collect()
+            })
+        };
+        let count = chord_tones.as_ref().map_or(params.voices, Vec::len);
+        let glide_length = (params.num_samples() as usize + params.crossfade_samples()) as f32
+            / params.sample_rate as f32;
+        let total: f32 = params.partials.iter().map(|(_, amp)| amp.abs()).sum();
+        let partials: Vec<(f32, f32)> = if total > 0.0 {
+            params
+                .partials
+                .iter()
+                .map(|&(ratio, amp)| (ratio, amp / total))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let stagger = if params.loop_mode || count < 2 {
+            0.0
+        } else {
+            params.voice_stagger.max(0.0) as f64
+        };
+        let mut pool = VoicePool::new(params.max_voices, params.voice_steal);
+        for i in 0..count {
+            let voice = {
+                let freq = match &chord_tones {
+                    Some(tones) => {
+                        let cents = rng.random_range(-CHORD_DETUNE_CENTS..CHORD_DETUNE_CENTS);
+                        tones[i].0 * 2f32.powf(cents / 1200.0)
+                    }
+                    None => {
+                        params.base_freq
+                            * Self::detune_ratio(params.detune_dist, detune_min, detune_max, rng)
+                    }
+                };
+                let lfo_rate = if params.lfo_sync {
+                    let division =
+                        &LFO_SYNC_DIVISIONS[rng.random_range(0..LFO_SYNC_DIVISIONS.len())];
+                    params.tempo().frequency(division)
+                } else {
+                    rng.random_range(lfo_min..lfo_max)
+                };
                 let mod_depth = rng.random_range(depth_min..depth_max);
                 let pan_rate = rng.random_range(0.01..0.05);
+                let static_pan = params
+                    .static_pan
+                    .then(|| Self::static_pan_position(i, count));
+                // stereo voices sit at the front; quad ones alternate front and
+                // rear when static, or drift between them
+                let (static_depth, depth_rate) = if !params.quad() {
+                    (Some(-1.0), 0.0)
+                } else if params.static_pan {
+                    let side = if i % 2 == 0 { -1.0 } else { 1.0 };
+                    (Some(side * pan_spread), 0.0)
+                } else {
+                    (None, rng.random_range(0.01..0.05))
+                };
+                let glide = match &params.scale {
+                    Some(scale) if params.glide_rate > 0.0 => Some(Arc::new(GlidePath::new(
+                        freq,
+                        &Self::glide_targets(scale, freq),
+                        params.glide_rate,
+                        glide_length,
+                        rng,
+                    ))),
+                    _ => None,
+                };
 
                 Voice {
                     freq,
                     lfo_rate,
                     mod_depth,
                     pan_rate,
+                    pan_spread,
+                    static_pan,
+                    static_depth,
+                    depth_rate,
+                    wavetable,
+                    gain: params.voice_gains.get(i).copied().unwrap_or(1.0),
+                    binaural,
+                    partial_phases: vec![0.0; partials.len()],
+                    partials: partials.clone(),
+                    modulator: None,
+                    glide,
+                    chord_slot: chord_tones.as_ref().and_then(|tones| tones[i].1),
+                    entry: (stagger > 0.0).then(|| {
+                        let delay = stagger * i as f64 / (count - 1) as f64;
+                        VoiceEntry {
+                            enter: delay,
+                            leave: params.duration as f64 - delay,
+                            attack: params.attack as f64,
+                            release: params.release as f64,
+                        }
+                    }),
+                    phases: VoicePhases::default(),
+                    position: 0,
+                    sample_rate: (params.sample_rate as usize * params.oversample.max(1)) as f32,
+                }
+            };
+            pool.push(voice);
+        }
+        if pool.stolen() > 0 {
+            eprintln!(
+                "warning: {} notes went over --max-voices {}, their voices were stolen",
+                pool.stolen(),
+                params.max_voices
+            );
+        }
+        let mut voices = pool.into_voices();
+
+        if params.fm_index != 0.0 {
+            for i in 1..voices.len() {
+                let modulated = match params.fm_pairing {
+                    FmPairing::Pairs => i % 2 == 1,
+                    FmPairing::Adjacent => true,
+                };
+                if modulated {
+                    voices[i].modulator = Some(Modulator {
+                        freq: voices[i - 1].freq,
+                        index: params.fm_index,
+                    });
                 }
+            }
+        }
+
+        voices
+    }
+
+    /// Frequency ratios `cents` below and above the base, for `--detune-cents`
+    fn cents_range(cents: f32) -> (f32, f32) {
+        let ratio = 2f32.powf(cents.abs() / 1200.0);
+        (ratio.recip(), ratio)
+    }
+
+    /// Random frequency ratio of a voice to the base frequency
+    fn detune_ratio(dist: DetuneDistribution, min: f32, max: f32, rng: &mut impl Rng) -> f32 {
+        if min >= max {
+            return min;
+        }
+        match dist {
+            DetuneDistribution::Uniform => rng.random_range(min..max),
+            DetuneDistribution::Gaussian => {
+                // Box-Muller transform of two uniform draws
+                let u1: f32 = rng.random_range(f32::EPSILON..1.0);
+                let u2: f32 = rng.random_range(0.0..1.0);
+                let z = (-2.0 * u1.ln()).sqrt() * (std::f32::consts::TAU * u2).cos();
+                let ratio = (min + max) * 0.5 + z * (max - min) * 0.25;
+                ratio.max(f32::EPSILON)
+            }
+        }
+    }
+
+    /// Frequencies of the notes of `scale` within a fifth of `freq`, which a
+    /// gliding voice wanders between
+    fn glide_targets(scale: &Scale, freq: f32) -> Vec<f32> {
+        let degrees: Vec<_> = scale
+            .clone()
+            .notes(Octave::new(4))
+            .into_iter()
+            .map(|note| note.pitch_cls)
+            .collect();
+        let (low, high) = (freq * 2f32.powf(-7.0 / 12.0), freq * 2f32.powf(7.0 / 12.0));
+        (0..=127u8)
+            .map(|midi| Note {
+                pitch_cls: (midi % 12).into(),
+                octave: Octave::new((midi / 12) as i8 - 1),
             })
+            .filter(|note| degrees.contains(&note.pitch_cls))
+            .map(Note::frequency)
+            .filter(|f| (low..=high).contains(f))
             .collect()
     }
 
-    fn writer(params: &GeneratorParams) -> Result<WavWriter<BufWriter<File>>, Error> {
-        WavWriter::create(&params.filename, Self::spec(params.sample_rate))
-            .map_err(|err| err.into())
+    /// Frequencies of the chord's tones, moved by whole octaves so the root
+    /// lies as close as possible to `base_freq`
+    fn chord_frequencies(chord: &Chord, base_freq: f32) -> Vec<f32> {
+        let shift = (base_freq / chord.root.frequency()).log2().round();
+        let octave = (chord.root.octave.num() as f32 + shift).clamp(-1.0, 8.0) as i8;
+        let voiced = Chord {
+            root: Note {
+                octave: Octave::new(octave),
+                ..chord.root
+            },
+            kind: chord.kind.clone(),
+        };
+        voiced.frequencies()
+    }
+
+    /// Evenly spaced position in -1.0..=1.0 for voice `index` of `count`.
+    /// A single voice sits in the center.
+    fn static_pan_position(index: usize, count: usize) -> f32 {
+        if count <= 1 {
+            return 0.0;
+        }
+        -1.0 + 2.0 * index as f32 / (count - 1) as f32
     }
 
-    fn new(params: GeneratorParams) -> Result<Generator, Error> {
-        let num_samples = params.num_samples();
-        let voices = Self::generate_voices(&params);
+    pub fn new(params: GeneratorParams) -> Result<Generator, Error> {
+        if params.duration > params.max_duration {
+            return Err(Error::DurationTooLong {
+                duration: params.duration,
+                max: params.max_duration,
+            });
+        }
+        // WAV sample counts are 32-bit, and the buffer has to fit in memory
+        let total_samples = u32::try_from(params.num_samples())
+            .map_err(|_| Error::TooManySamples(params.num_samples()))?;
+        let (first_sample, num_samples) = match params.render_range {
+            Some((start, end)) => {
+                let sr = params.sample_rate as f64;
+                let first = (start as f64 * sr).round() as u32;
+                let last = ((end as f64 * sr).round() as u32).min(total_samples);
+                if first >= last {
+                    return Err(Error::RenderRangeOutside {
+                        start,
+                        duration: params.duration,
+                    });
+                }
+                (first as usize, last - first)
+            }
+            None => (0, total_samples),
+        };
+        params
+            .progression_chords()
+            .map_err(Error::ProgressionError)?;
+        if Tempo::new(params.tempo).is_none() {
+            return Err(Error::TempoOutOfRange(params.tempo));
+        }
+        let seed = params.seed.unwrap_or_else(rand::random);
+        let mut rng = StdRng::seed_from_u64(seed);
+        let voices = Self::generate_voices(&params, &mut rng);
+        let grains = GrainCloud::new(
+            params.grain_density,
+            params.grain_duration,
+            params.grain_pitch_jitter,
+            params.base_freq,
+            params.sample_rate,
+            total_samples as usize + params.crossfade_samples(),
+            &mut rng,
+        );
+        let impulse_response = match &params.reverb_ir {
+            Some(path) => Some(convolution::load_impulse_response(
+                path,
+                params.sample_rate,
+            )?),
+            None => None,
+        };
         Ok(Generator {
+            impulse_response,
+            filter_coeff: one_pole_coefficient(params.noise_cutoff_hz, params.sample_rate),
+            wind: Wind::new(params.wind_center, params.wind_sweep, params.sample_rate),
             params,
             voices,
+            grains,
             num_samples,
             samples: Vec::new(),
+            rear: Vec::new(),
+            seed,
             filter_prev_l: 0.0,
             filter_prev_r: 0.0,
+            progress: None,
+            first_sample,
+            stems: Vec::new(),
         })
     }
 
+    /// The voices this generator renders, in mixing order
+    pub fn voices(&self) -> &[Voice] {
+        &self.voices
+    }
+
+    /// The randomly chosen parameters of every voice, in mixing order
+    pub fn voice_info(&self) -> Vec<VoiceInfo> {
+        self.voices
+            .iter()
+            .enumerate()
+            .map(|(index, voice)| {
+                let (note, cents) = Note::nearest_from_frequency(voice.freq);
+                VoiceInfo {
+                    index,
+                    freq: voice.freq,
+                    note,
+                    cents,
+                    lfo_rate: voice.lfo_rate,
+                    mod_depth: voice.mod_depth,
+                    pan_rate: voice.pan_rate,
+                }
+            })
+            .collect()
+    }
+
+    /// Registers a callback that is invoked periodically during [`Generator::run`]
+    /// with the fraction of samples rendered so far, and once more with `1.0`
+    /// when rendering completes.
+    pub fn set_progress_callback(&mut self, callback: impl Fn(f32) + Send + Sync + 'static) {
+        self.progress = Some(Box::new(callback));
+    }
+
+    fn report_progress(&self, rendered: usize) {
+        if let Some(callback) = &self.progress {
+            callback(rendered as f32 / self.num_samples.max(1) as f32);
+        }
+    }
+
     fn envelope(&self, time: f32) -> f32 {
-        if time < self.params.attack {
+        let ramp = if time < self.params.attack {
             time / self.params.attack
         } else if time > self.params.duration - self.params.release {
             (self.params.duration - time) / self.params.release
         } else {
             1.0
         }
-        .clamp(0.0, 1.0)
+        .clamp(0.0, 1.0);
+        self.params.fade_curve.shape(ramp)
     }
 
-    fn write_wav(&self) -> Result<(), Error> {
-        let mut writer = Self::writer(&self.params)?;
-        for (l, r) in &self.samples {
-            let amp = i16::MAX as f32;
-            let ls = (l * amp).clamp(i16::MIN as f32, i16::MAX as f32) as i16;
-            let rs = (r * amp).clamp(i16::MIN as f32, i16::MAX as f32) as i16;
-            writer.write_sample(ls)?;
-            writer.write_sample(rs)?;
+    /// Interleaved output samples, mixed down for mono output
+    fn interleaved_samples(&self) -> Vec<f32> {
+        if self.params.mono {
+            self.samples.iter().map(|(l, r)| (l + r) * 0.5).collect()
+        } else if self.params.quad() {
+            self.samples
+                .iter()
+                .zip(&self.rear)
+                .flat_map(|(&(fl, fr), &(rl, rr))| [fl, fr, rl, rr])
+                .collect()
+        } else {
+            self.samples.iter().flat_map(|&(l, r)| [l, r]).collect()
         }
-        writer.finalize()?;
-        Ok(())
     }
 
-    fn noise(&self, rng: &mut ThreadRng) -> f32 {
-        rng.random_range(-1.0..1.0) * self.params.noise_level
+    /// Counts the output samples `quantize` will clamp
+    pub fn clip_report(&self) -> ClipReport {
+        let samples = self.interleaved_samples();
+        let peak = samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+        ClipReport {
+            clipped: samples.iter().filter(|s| s.abs() > 1.0).count(),
+            samples: samples.len(),
+            peak,
+        }
     }
 
-    fn burst_env(t: f32) -> f32 {
-        (2.0 * PI * 10.0 * t).sin() * 0.5 + 0.5
+    /// Interleaved integer samples at `--bit-depth`, dithered and noise
+    /// shaped at 16 bits with `--dither` and `--noise-shaping`
+    fn quantized_samples(&self) -> Vec<i32> {
+        let bits = self.params.bit_depth;
+        let dither = self.params.dither && bits == 16;
+        let shaping = self.params.noise_shaping && bits == 16;
+        let lsb = (((1i32 << (bits - 1)) - 1) as f32).recip();
+        let channels = self.params.channels() as usize;
+        let mut rng = self.stream_rng(DITHER_STREAM);
+        // the last two quantization errors of each channel, newest first
+        let mut errors = vec![[0.0f32; 2]; channels];
+        self.interleaved_samples()
+            .into_iter()
+            .enumerate()
+            .map(|(i, mut sample)| {
+                let error = &mut errors[i % channels];
+                if shaping {
+                    // subtracting 2e[n-1] - e[n-2] leaves (1 - z^-1)^2 e[n] in the output
+                    sample -= 2.0 * error[0] - error[1];
+                }
+                let mut noisy = sample;
+                if dither {
+                    // the sum of two uniform draws has a triangular PDF over +/-1 LSB
+                    noisy += (rng.random_range(-0.5..0.5) + rng.random_range(-0.5..0.5)) * lsb;
+                }
+                let quantized = Self::quantize(noisy, bits);
+                if shaping {
+                    // clamped so clipping can't wind the feedback up
+                    let e = (quantized as f32 * lsb - sample).clamp(-2.0 * lsb, 2.0 * lsb);
+                    *error = [e, error[0]];
+                }
+                quantized
+            })
+            .collect()
+    }
+
+    fn write_wav(&self) -> Result<(), Error> {
+        self.write_wav_to(&self.params.filename)
+    }
+
+    fn write_wav_to(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let spec = Self::spec(
+            self.params.sample_rate,
+            self.params.channels(),
+            self.params.bit_depth,
+        );
+        let mut writer = WavWriter::create(path, spec)?;
+        for sample in self.quantized_samples() {
+            writer.write_sample(sample)?;
+        }
+        writer.finalize()?;
+        Ok(())
+    }
+
+    fn write_flac(&self) -> Result<(), Error> {
+        let file = BufWriter::new(File::create(&self.params.filename)?);
+        flac::write_flac(
+            file,
+            &self.quantized_samples(),
+            self.params.channels() as u8,
+            self.params.sample_rate,
+            self.params.bit_depth as u32,
+        )?;
+        Ok(())
+    }
+
+    /// Encodes the buffer as OGG Vorbis at `--ogg-quality`
+    #[cfg(feature = "ogg")]
+    fn write_ogg(&self) -> Result<(), Error> {
+        use std::num::{NonZeroU32, NonZeroU8};
+        use vorbis_rs::{VorbisBitrateManagementStrategy, VorbisEncoderBuilder};
+
+        const BLOCK: usize = 1024;
+        let sample_rate = NonZeroU32::new(self.params.sample_rate)
+            .ok_or(Error::UnsupportedFormat("ogg at a sample rate of 0"))?;
+        let channels = NonZeroU8::new(self.params.channels() as u8).unwrap_or(NonZeroU8::MIN);
+        let file = BufWriter::new(File::create(&self.params.filename)?);
+        // a serial derived from the seed keeps the output reproducible
+        let mut encoder =
+            VorbisEncoderBuilder::new_with_serial(sample_rate, channels, file, self.seed as i32)
+                .bitrate_management_strategy(VorbisBitrateManagementStrategy::QualityVbr {
+                    target_quality: self.params.ogg_quality.clamp(-0.2, 1.0),
+                })
+                .build()?;
+
+        for (i, block) in self.samples.chunks(BLOCK).enumerate() {
+            if self.params.mono {
+                let mixed: Vec<f32> = block.iter().map(|(l, r)| (l + r) * 0.5).collect();
+                encoder.encode_audio_block([mixed])?;
+            } else if self.params.quad() {
+                // Vorbis orders four channels front left, front right, rear left, rear right
+                let rear = &self.rear[i * BLOCK..i * BLOCK + block.len()];
+                let (front_l, front_r): (Vec<f32>, Vec<f32>) = block.iter().copied().unzip();
+                let (rear_l, rear_r): (Vec<f32>, Vec<f32>) = rear.iter().copied().unzip();
+                encoder.encode_audio_block([front_l, front_r, rear_l, rear_r])?;
+            } else {
+                let (left, right): (Vec<f32>, Vec<f32>) = block.iter().copied().unzip();
+                encoder.encode_audio_block([left, right])?;
+            }
+        }
+        encoder.finish()?;
+        Ok(())
+    }
+
+    #[cfg(not(feature = "ogg"))]
+    fn write_ogg(&self) -> Result<(), Error> {
+        Err(Error::UnsupportedFormat(
+            "ogg output needs a build with `--features ogg`",
+        ))
+    }
+
+    /// Writes the buffer in the configured output format
+    fn write_output(&self) -> Result<(), Error> {
+        match self.params.format {
+            OutputType::WAV => self.write_wav(),
+            OutputType::OGG => self.write_ogg(),
+            OutputType::FLAC => self.write_flac(),
+            OutputType::MIDI => Err(Error::UnsupportedFormat(
+                "midi is not a rendered audio format",
+            )),
+        }
+    }
+
+    /// Scales a sample in -1.0..=1.0 to a signed integer of `bits` bits, up
+    /// to 32
+    fn quantize(sample: f32, bits: u16) -> i32 {
+        // in i64 so a 32-bit full scale doesn't overflow
+        let amp = ((1i64 << (bits - 1)) - 1) as f32;
+        (sample * amp).clamp(-amp - 1.0, amp) as i32
+    }
+
+    /// Gain on the noise layers at `time` for `--noise-duck`, following the
+    /// voices' average modulation envelope like a sidechain compressor. The
+    /// envelope is used rather than the waveform so the noise isn't
+    /// modulated at audio rate.
+    fn noise_duck(&self, time: f32) -> f32 {
+        let amount = self.params.noise_duck.clamp(0.0, 1.0);
+        if amount == 0.0 || self.voices.is_empty() {
+            return 1.0;
+        }
+        let level = self
+            .voices
+            .iter()
+            .map(|voice| voice.envelope_at(time as f64))
+            .sum::<f32>()
+            / self.voices.len() as f32;
+        (1.0 - amount * level).max(0.0)
     }
 
-    /// granular texture: occasional bursts of noise shaped by a 10Hz LFO
-    fn granular_noise(&self, time: f32, rng: &mut impl Rng) -> f32 {
-        rng.random_range(-1.0..1.0) * self.params.noise_level * 0.5 * Self::burst_env(time)
+    /// The `--noise-level` layer at `sample`
+    fn noise(&self, sample: usize, rng: &mut impl Rng) -> f32 {
+        let time = sample as f32 / self.params.sample_rate as f32;
+        let level = self.params.automated(AutomationTarget::NoiseLevel, time);
+        let noise = match self.params.noise_type {
+            NoiseType::White => rng.random_range(-1.0..1.0),
+            // timed in f64, so the curve stays smooth hours in
+            NoiseType::Value => {
+                value_noise(self.seed, sample as f64 / self.params.sample_rate as f64)
+            }
+        };
+        noise * level * self.noise_duck(time)
     }
 
-    fn filtered_noise(&mut self, _time: f32, rng: &mut impl Rng) -> (f32, f32) {
-        const A: f32 = 0.1;
-        let w = rng.random_range(-1.0..1.0) * self.params.noise_level * 0.3;
+    fn filtered_noise(&mut self, time: f32, rng: &mut impl Rng) -> (f32, f32) {
+        let a = self.filter_coeff;
+        let level =
+            self.params.automated(AutomationTarget::NoiseLevel, time) * self.noise_duck(time) * 0.3;
+        // independent draws per channel keep the layer decorrelated in stereo
+        let wl = rng.random_range(-1.0..1.0) * level;
+        let wr = rng.random_range(-1.0..1.0) * level;
 
-        let fl = A * w + (1.0 - A) * self.filter_prev_l;
-        let fr = A * w + (1.0 - A) * self.filter_prev_r;
+        let fl = a * wl + (1.0 - a) * self.filter_prev_l;
+        let fr = a * wr + (1.0 - a) * self.filter_prev_r;
 
         self.filter_prev_l = fl;
         self.filter_prev_r = fr;
@@ -481,24 +2510,315 @@ impl Generator {
         (fl, fr)
     }
 
+    /// The `--wind-level` layer at `time`: stereo white noise through the
+    /// swept band-pass, silent without drawing any noise when it's off
+    fn wind_noise(&mut self, time: f32, rng: &mut impl Rng) -> (f32, f32) {
+        if self.params.wind_level <= 0.0 {
+            return (0.0, 0.0);
+        }
+        let level = self.params.wind_level * self.noise_duck(time);
+        let white = (
+            rng.random_range(-1.0..1.0) * level,
+            rng.random_range(-1.0..1.0) * level,
+        );
+        self.wind.process(time, white)
+    }
+
+    /// Soft-clips `x` with a tanh curve normalized so full scale maps to full scale
+    fn saturate(x: f32, drive: f32) -> f32 {
+        if drive <= 0.0 {
+            return x;
+        }
+        (drive * x).tanh() / drive.tanh()
+    }
+
+    /// Sweeps the whole mix between the speakers with a slow sine LFO
+    fn apply_autopan(&mut self) {
+        let depth = self.params.autopan_depth;
+        if depth == 0.0 {
+            return;
+        }
+        let step = TAU * self.params.autopan_rate as f64 / self.params.sample_rate as f64;
+        for pair in [&mut self.samples, &mut self.rear] {
+            for (i, (left, right)) in pair.iter_mut().enumerate() {
+                let pan = depth * ((step * i as f64).rem_euclid(TAU) as f32).sin();
+                *left *= 1.0 - pan;
+                *right *= 1.0 + pan;
+            }
+        }
+    }
+
+    /// Scales the side (L-R) signal of the mix by `--width`
+    fn apply_width(&mut self) {
+        let width = self.params.width;
+        if width == 1.0 {
+            return;
+        }
+        for (left, right) in self.samples.iter_mut().chain(&mut self.rear) {
+            let mid = (*left + *right) * 0.5;
+            let side = (*left - *right) * 0.5 * width;
+            (*left, *right) = (mid + side, mid - side);
+        }
+    }
+
+    /// Puts each `--stems` layer through the same effects as the mix, in
+    /// place of the mix's own buffers, and writes it to `<layer>.wav`
+    fn write_stems(&mut self) -> Result<(), Error> {
+        let Some(dir) = self.params.stems.clone() else {
+            return Ok(());
+        };
+        std::fs::create_dir_all(&dir)?;
+        let mix = (
+            std::mem::take(&mut self.samples),
+            std::mem::take(&mut self.rear),
+        );
+        for stem in std::mem::take(&mut self.stems) {
+            self.samples = stem.samples;
+            self.rear = stem.rear;
+            if stem.layer == Layer::Reverb {
+                // taken from the mix after its tail and chorus
+                self.reverb(ReverbPart::Wet);
+            } else {
+                self.extend_tail();
+                self.apply_chorus();
+                self.reverb(ReverbPart::Dry);
+            }
+            self.apply_autopan();
+            self.apply_width();
+            self.apply_haas();
+            self.apply_loop_crossfade();
+            self.apply_fades();
+            let path = Path::new(&dir)
+                .join(stem.layer.name())
+                .with_extension("wav");
+            self.write_wav_to(path)?;
+        }
+        (self.samples, self.rear) = mix;
+        Ok(())
+    }
+
+    /// Writes `--spectrogram`, if asked for, from the front pair summed to mono
+    fn write_spectrogram(&self) -> Result<(), Error> {
+        let Some(path) = &self.params.spectrogram else {
+            return Ok(());
+        };
+        let mono: Vec<f32> = self.samples.iter().map(|(l, r)| (l + r) * 0.5).collect();
+        spectrogram::write(
+            path,
+            &mono,
+            self.params.spectrogram_window,
+            self.params.spectrogram_hop,
+        )?;
+        Ok(())
+    }
+
+    /// Haas widening: shifts the right channel later by `--haas-ms`, padding
+    /// its start with silence
+    fn apply_haas(&mut self) {
+        let delay = (self.params.haas_ms.max(0.0) / 1000.0 * self.params.sample_rate as f32).round()
+            as usize;
+        if delay == 0 {
+            return;
+        }
+        if self.params.mono {
+            eprintln!("warning: --haas-ms has no effect on mono output, ignoring it");
+            return;
+        }
+        for pair in [&mut self.samples, &mut self.rear] {
+            for i in (0..pair.len()).rev() {
+                pair[i].1 = if i >= delay { pair[i - delay].1 } else { 0.0 };
+            }
+        }
+    }
+
+    /// Mixes in a copy of the signal delayed by a slowly swept 10-20 ms.
+    /// The right channel's sweep runs a quarter cycle ahead for width.
+    fn apply_chorus(&mut self) {
+        let depth = self.params.chorus_depth;
+        if depth == 0.0 {
+            return;
+        }
+        let sr = self.params.sample_rate as f32;
+        let len = ((CHORUS_DELAY + CHORUS_SWEEP) * sr) as usize + 2;
+        let step = TAU * self.params.chorus_rate as f64 / sr as f64;
+        let read = |buf: &[f32], write: usize, delay: f32| {
+            // linear interpolation between the two samples around `delay`
+            let back = delay.floor() as usize;
+            let frac = delay - back as f32;
+            let a = buf[(write + len - back) % len];
+            let b = buf[(write + len - back - 1) % len];
+            a + (b - a) * frac
+        };
+        for pair in [&mut self.samples, &mut self.rear] {
+            let mut buf_l = vec![0.0; len];
+            let mut buf_r = vec![0.0; len];
+            for (i, (left, right)) in pair.iter_mut().enumerate() {
+                buf_l[i % len] = *left;
+                buf_r[i % len] = *right;
+                let phase = (step * i as f64).rem_euclid(TAU) as f32;
+                let delay_l = (CHORUS_DELAY + CHORUS_SWEEP * depth * phase.sin()) * sr;
+                let delay_r = (CHORUS_DELAY + CHORUS_SWEEP * depth * phase.cos()) * sr;
+                *left = (*left + read(&buf_l, i % len, delay_l)) * 0.5;
+                *right = (*right + read(&buf_r, i % len, delay_r)) * 0.5;
+            }
+        }
+    }
+
+    /// Pads the buffers with `--reverb-tail` seconds of silence for the
+    /// chorus and reverb to decay into. Loops skip it; their tail is the
+    /// crossfade.
+    fn extend_tail(&mut self) {
+        if self.params.loop_mode {
+            return;
+        }
+        let tail = (self.params.reverb_tail.max(0.0) * self.params.sample_rate as f32) as usize;
+        for pair in [&mut self.samples, &mut self.rear] {
+            if !pair.is_empty() {
+                pair.resize(pair.len() + tail, (0.0, 0.0));
+            }
+        }
+    }
+
     fn apply_reverb(&mut self) {
+        self.reverb(ReverbPart::Mix);
+    }
+
+    /// Feedback-delay reverb, or convolution with `--reverb-ir`, blending
+    /// in `part` of its output
+    fn reverb(&mut self, part: ReverbPart) {
         let sr = self.params.sample_rate as usize;
         let delay = (0.05 * sr as f32) as usize;
+        // the send is high-passed by subtracting a one-pole low-pass from it
+        let hpf_coeff = if self.params.reverb_hpf > 0.0 {
+            one_pole_coefficient(self.params.reverb_hpf, self.params.sample_rate)
+        } else {
+            0.0
+        };
+        for pair in [&mut self.samples, &mut self.rear] {
+            let (mut low_l, mut low_r) = (0.0, 0.0);
+            let send: Vec<(f32, f32)> = pair
+                .iter()
+                .map(|&(dry_l, dry_r)| {
+                    low_l += hpf_coeff * (dry_l - low_l);
+                    low_r += hpf_coeff * (dry_r - low_r);
+                    (dry_l - low_l, dry_r - low_r)
+                })
+                .collect();
+            let wet: Vec<(f32, f32)> = match &self.impulse_response {
+                Some((left, right)) => {
+                    let channel = |side: fn(&(f32, f32)) -> f32, response| {
+                        convolution::convolve(&send.iter().map(side).collect::<Vec<_>>(), response)
+                    };
+                    let left = channel(|s| s.0, left);
+                    left.into_iter().zip(channel(|s| s.1, right)).collect()
+                }
+                None => Self::feedback_delay(&send, delay),
+            };
+            for (i, (sample, (wet_l, wet_r))) in pair.iter_mut().zip(wet).enumerate() {
+                // on the full piece's clock, so excerpts follow the automation
+                let time = (self.first_sample + i) as f32 / sr as f32;
+                let mut mix = self.params.automated(AutomationTarget::ReverbMix, time);
+                if self.params.reverb_fade > 0.0 {
+                    mix *= (time / self.params.reverb_fade).min(1.0);
+                }
+                let (dry, wet) = match part {
+                    ReverbPart::Mix => (1.0 - mix, mix),
+                    ReverbPart::Dry => (1.0 - mix, 0.0),
+                    ReverbPart::Wet => (0.0, mix),
+                };
+                *sample = (sample.0 * dry + wet_l * wet, sample.1 * dry + wet_r * wet);
+            }
+        }
+    }
+
+    /// Wet signal of the feedback-delay reverb: `send` echoed every `delay`
+    /// samples, each echo 0.7 times the last
+    fn feedback_delay(send: &[(f32, f32)], delay: usize) -> Vec<(f32, f32)> {
         let fb = 0.7;
-        let mut buf_l = vec![0.0; delay];
-        let mut buf_r = vec![0.0; delay];
-        let mut idx = 0;
-        for sample in &mut self.samples {
-            let dry_l = sample.0;
-            let dry_r = sample.1;
-            let wet_l = buf_l[idx];
-            let wet_r = buf_r[idx];
-            let out_l = dry_l * (1.0 - self.params.reverb_mix) + wet_l * self.params.reverb_mix;
-            let out_r = dry_r * (1.0 - self.params.reverb_mix) + wet_r * self.params.reverb_mix;
-            buf_l[idx] = dry_l + wet_l * fb;
-            buf_r[idx] = dry_r + wet_r * fb;
-            *sample = (out_l, out_r);
-            idx = (idx + 1) % delay;
+        let mut wet = vec![(0.0, 0.0); send.len()];
+        for i in delay..send.len() {
+            let (echo, last) = (send[i - delay], wet[i - delay]);
+            wet[i] = (echo.0 + last.0 * fb, echo.1 + last.1 * fb);
+        }
+        wet
+    }
+
+    /// Linear ramps over the first `--fade-in-ms` and last `--fade-out-ms` of
+    /// the finished buffer. Loops are left alone so the seam stays intact.
+    fn apply_fades(&mut self) {
+        if self.params.loop_mode {
+            return;
+        }
+        let ms_to_samples = |ms: f32| (ms / 1000.0 * self.params.sample_rate as f32) as usize;
+        let fade_in = ms_to_samples(self.params.fade_in_ms);
+        let fade_out = ms_to_samples(self.params.fade_out_ms);
+        for pair in [&mut self.samples, &mut self.rear] {
+            let len = pair.len();
+            for (i, (left, right)) in pair.iter_mut().enumerate() {
+                let mut gain = 1.0;
+                if i < fade_in {
+                    gain *= i as f32 / fade_in as f32;
+                }
+                if len - i <= fade_out {
+                    gain *= (len - 1 - i) as f32 / fade_out as f32;
+                }
+                *left *= gain;
+                *right *= gain;
+            }
+        }
+    }
+
+    /// Adds a short click on every beat of `--tempo` for `--click`, higher
+    /// and louder on the first beat of each bar of `--time-sig`. Beats are
+    /// the signature's note value, so 6/8 clicks every eighth note.
+    fn apply_click(&mut self) {
+        if !self.params.click {
+            return;
+        }
+        let sr = self.params.sample_rate as f64;
+        let TimeSignature(per_bar, note) = self.params.time_sig;
+        let beat = self.params.tempo().seconds_per_beat() as f64 * 4.0 / note.max(1) as f64;
+        let length = (CLICK_SECONDS * sr) as usize;
+        let (first, end) = (self.first_sample, self.first_sample + self.samples.len());
+
+        let mut index = (first as f64 / sr / beat).ceil() as usize;
+        loop {
+            let start = (index as f64 * beat * sr).round() as usize;
+            if start >= end {
+                break;
+            }
+            let (freq, level) = if index.is_multiple_of(per_bar.max(1) as usize) {
+                (1500.0, 0.5)
+            } else {
+                (1000.0, 0.25)
+            };
+            for i in 0..length.min(end - start) {
+                // a Hann-windowed sine burst
+                let window = 0.5 - 0.5 * (TAU * i as f64 / length as f64).cos();
+                let click = ((TAU * freq * i as f64 / sr).sin() * window * level) as f32;
+                let (left, right) = &mut self.samples[start - first + i];
+                *left += click;
+                *right += click;
+            }
+            index += 1;
+        }
+    }
+
+    /// Blends the tail rendered past `num_samples` into the start with an
+    /// equal-power crossfade, then drops it, so the end flows into the start.
+    fn apply_loop_crossfade(&mut self) {
+        let len = self.num_samples as usize;
+        for pair in [&mut self.samples, &mut self.rear] {
+            let fade = pair.len().saturating_sub(len);
+            for i in 0..fade {
+                let x = (i as f32 + 0.5) / fade as f32 * FRAC_PI_2;
+                let (head, tail) = (pair[i], pair[len + i]);
+                pair[i] = (
+                    head.0 * x.sin() + tail.0 * x.cos(),
+                    head.1 * x.sin() + tail.1 * x.cos(),
+                );
+            }
+            pair.truncate(len);
         }
     }
 
@@ -508,49 +2828,266 @@ impl Generator {
         (*left, *right)
     }
 
-    fn generate(&mut self) -> Result<(), Error> {
-        let mut rng = rand::rng();
+    /// Independent RNG stream derived from the master seed, so each chunk
+    /// and noise layer draws the same noise no matter which thread renders
+    /// it. A small generator rather than `StdRng`, since the noise layers
+    /// draw from it on every sample.
+    fn stream_rng(&self, stream: u64) -> SmallRng {
+        SmallRng::seed_from_u64(self.seed ^ (stream + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15))
+    }
+
+    /// Renders the stateless layers (voices, white noise, grain cloud) of one
+    /// chunk of the front pair, or of the rear pair in quad output, or only
+    /// `layer` of them. The grain cloud only plays from the front.
+    fn render_chunk(&self, chunk: usize, rear: bool, layer: Option<Layer>, out: &mut [(f32, f32)]) {
+        let plays = |l: Layer| layer.is_none_or(|layer| layer == l);
+        let first_stream = if rear { REAR_STREAM } else { FILTER_STREAM + 1 };
+        let mut rng = self.stream_rng(first_stream + chunk as u64);
+        let start = self.first_sample + chunk * CHUNK_SIZE;
+        let mut voices = if plays(Layer::Voices) {
+            self.voices.clone()
+        } else {
+            Vec::new()
+        };
+        let factor = self.params.oversample.max(1);
+        let mut decimator = (factor > 1 && !voices.is_empty()).then(|| Decimator::new(factor));
+        // voices run ahead of the output by the filter's delay, from silence
+        // before the start of the piece
+        let mut next = match &decimator {
+            Some(decimator) => (start * factor) as isize - decimator.delay() as isize,
+            None => start as isize,
+        };
+        for voice in &mut voices {
+            voice.seek(next.max(0) as usize);
+        }
+        let mix = |voices: &mut [Voice]| {
+            voices.iter_mut().fold((0.0, 0.0), |(left, right), voice| {
+                let [front_l, front_r, rear_l, rear_r] = voice.synthesize();
+                if rear {
+                    (left + rear_l, right + rear_r)
+                } else {
+                    (left + front_l, right + front_r)
+                }
+            })
+        };
+
+        for (i, sample) in out.iter_mut().enumerate() {
+            let (mut left, mut right) = match &mut decimator {
+                Some(decimator) => {
+                    let newest = ((start + i) * factor + decimator.delay()) as isize;
+                    while next <= newest {
+                        decimator.push(if next < 0 {
+                            (0.0, 0.0)
+                        } else {
+                            mix(&mut voices)
+                        });
+                        next += 1;
+                    }
+                    decimator.output()
+                }
+                None => mix(&mut voices),
+            };
 
-        self.samples.reserve(self.num_samples as usize);
+            if plays(Layer::Noise) {
+                let noise = self.noise(start + i, &mut rng);
+                (left, right) = Self::mutate(&mut left, &mut right, &noise);
+            }
+
+            *sample = (left, right);
+        }
+        if !rear && plays(Layer::Grains) {
+            self.grains.render(start, out);
+        }
+    }
 
-        for i in 0..self.num_samples {
-            let t = i as f32 / self.params.sample_rate as f32;
-            let mut left = 0.0;
-            let mut right = 0.0;
+    /// Renders the stateless layers of one speaker pair into `out` in
+    /// parallel, or only `layer` of them
+    fn render_pair(
+        &self,
+        pool: Option<&rayon::ThreadPool>,
+        rear: bool,
+        layer: Option<Layer>,
+        out: &mut [(f32, f32)],
+    ) {
+        for (block, out) in out.chunks_mut(PROGRESS_INTERVAL).enumerate() {
+            if !rear && layer.is_none() {
+                self.report_progress(block * PROGRESS_INTERVAL);
+            }
 
-            for voice in &self.voices {
-                let (l_gain, r_gain) = voice.synthesize(t);
-                left += l_gain;
-                right += r_gain;
+            let first_chunk = block * PROGRESS_INTERVAL / CHUNK_SIZE;
+            let render = |(i, chunk): (usize, &mut [(f32, f32)])| {
+                self.render_chunk(first_chunk + i, rear, layer, chunk)
+            };
+            match pool {
+                Some(pool) => {
+                    pool.install(|| out.par_chunks_mut(CHUNK_SIZE).enumerate().for_each(render))
+                }
+                None => out.chunks_mut(CHUNK_SIZE).enumerate().for_each(render),
             }
+        }
+    }
+
+    /// Envelope and master gain at `time`
+    fn gain_at(&self, time: f32) -> f32 {
+        let master_gain = db_to_gain(self.params.gain_db);
+        if self.params.loop_mode {
+            master_gain
+        } else {
+            self.envelope(time) * master_gain
+        }
+    }
+
+    fn generate(&mut self) -> Result<(), Error> {
+        let pool = match self.params.threads {
+            1 => None,
+            threads => Some(
+                rayon::ThreadPoolBuilder::new()
+                    .num_threads(threads)
+                    .build()?,
+            ),
+        };
 
-            let noise = self.noise(&mut rng);
-            (left, right) = Self::mutate(&mut left, &mut right, &noise);
+        let len = self.num_samples as usize + self.params.crossfade_samples();
+        let mut samples = vec![(0.0, 0.0); len];
+        self.render_pair(pool.as_ref(), false, None, &mut samples);
+        let rear_len = if self.params.quad() { len } else { 0 };
+        let mut rear = vec![(0.0, 0.0); rear_len];
+        self.render_pair(pool.as_ref(), true, None, &mut rear);
 
-            let gran_texture = self.granular_noise(t, &mut rng);
-            (left, right) = Self::mutate(&mut left, &mut right, &gran_texture);
+        let mut stems = Vec::new();
+        if self.params.stems.is_some() {
+            for layer in [Layer::Voices, Layer::Noise, Layer::Grains] {
+                let mut stem = Stem {
+                    layer,
+                    samples: vec![(0.0, 0.0); len],
+                    rear: vec![(0.0, 0.0); rear_len],
+                };
+                self.render_pair(pool.as_ref(), false, Some(layer), &mut stem.samples);
+                self.render_pair(pool.as_ref(), true, Some(layer), &mut stem.rear);
+                stems.push(stem);
+            }
+            let wind = (self.params.wind_level > 0.0).then_some(Layer::Wind);
+            for layer in [Layer::Filtered].into_iter().chain(wind) {
+                stems.push(Stem {
+                    layer,
+                    samples: vec![(0.0, 0.0); len],
+                    rear: vec![(0.0, 0.0); rear_len],
+                });
+            }
+        }
+
+        let drive = self.params.saturation;
+        let mut rng = self.stream_rng(FILTER_STREAM);
+        let mut wind_rng = self.stream_rng(WIND_STREAM);
+        let freqs: Vec<f32> = self
+            .params
+            .resonators
+            .iter()
+            .map(|n| n.frequency())
+            .collect();
+        let mut resonators =
+            ResonatorBank::new(&freqs, self.params.resonator_decay, self.params.sample_rate);
+        let (first, sr) = (self.first_sample, self.params.sample_rate as f32);
+        let time = move |i: usize| (first + i) as f32 / sr;
+        for (i, sample) in samples.iter_mut().enumerate() {
+            let t = time(i);
+            let (mut left, mut right) = *sample;
 
-            let (l_filt, r_filt) = self.filtered_noise(t, &mut rng);
+            let mut filtered = self.filtered_noise(t, &mut rng);
+            if !resonators.is_empty() {
+                filtered = resonators.process(filtered);
+            }
+            let (l_filt, r_filt) = filtered;
             left += l_filt;
             right += r_filt;
+            let wind = self.wind_noise(t, &mut wind_rng);
+            left += wind.0;
+            right += wind.1;
+            for stem in &mut stems {
+                match stem.layer {
+                    Layer::Filtered => stem.samples[i] = filtered,
+                    Layer::Wind => stem.samples[i] = wind,
+                    _ => {}
+                }
+            }
 
-            let env = self.envelope(t);
-            (left, right) = Self::mutate(&mut left, &mut right, &env);
-            self.samples.push((left, right));
+            let gain = self.gain_at(t);
+            *sample = (
+                Self::saturate(left * gain, drive),
+                Self::saturate(right * gain, drive),
+            );
+        }
+        for (i, (left, right)) in rear.iter_mut().enumerate() {
+            let gain = self.gain_at(time(i));
+            *left = Self::saturate(*left * gain, drive);
+            *right = Self::saturate(*right * gain, drive);
         }
+        for stem in &mut stems {
+            for pair in [&mut stem.samples, &mut stem.rear] {
+                for (i, (left, right)) in pair.iter_mut().enumerate() {
+                    let gain = self.gain_at(time(i));
+                    *left *= gain;
+                    *right *= gain;
+                }
+            }
+        }
+        self.samples = samples;
+        self.rear = rear;
+        self.stems = stems;
+        self.report_progress(self.num_samples as usize);
 
         println!(
-            "Generated '{}' ({}s) with {} voices.",
-            self.params.filename, self.params.duration, self.params.voices
+            "Generated '{}' ({}s) with {} voices (seed {}).",
+            self.params.filename,
+            self.params.duration,
+            self.voices.len(),
+            self.seed
         );
 
         Ok(())
     }
 
-    fn run(&mut self) -> Result<(), Error> {
+    /// Plays the rendered buffer on the default audio output device,
+    /// blocking until playback finishes
+    #[cfg(feature = "playback")]
+    pub fn play(&self) -> Result<(), Error> {
+        playback::play(
+            self.interleaved_samples(),
+            self.params.channels(),
+            self.params.sample_rate,
+        )
+        .map_err(Error::PlaybackError)
+    }
+
+    #[cfg(not(feature = "playback"))]
+    pub fn play(&self) -> Result<(), Error> {
+        Err(Error::PlaybackError(
+            "--play needs a build with `--features playback`".to_string(),
+        ))
+    }
+
+    pub fn run(&mut self) -> Result<(), Error> {
         self.generate()?;
+        self.extend_tail();
+        self.apply_chorus();
+        if !self.stems.is_empty() {
+            // the reverb's stem is the wet part of the whole mix's reverb
+            self.stems.push(Stem {
+                layer: Layer::Reverb,
+                samples: self.samples.clone(),
+                rear: self.rear.clone(),
+            });
+        }
         self.apply_reverb();
-        self.write_wav()?;
+        self.apply_autopan();
+        self.apply_width();
+        self.apply_haas();
+        self.apply_loop_crossfade();
+        self.apply_click();
+        self.apply_fades();
+        self.write_output()?;
+        self.write_spectrogram()?;
+        self.write_stems()?;
         println!(
             "Generated '{}' with {} samples.",
             self.params.filename,
@@ -560,279 +3097,2853 @@ impl Generator {
     }
 }
 
-struct Voice {
+/// Clipping in a finished render, as printed by `--peak-report`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClipReport {
+    /// Output samples past full scale, which the integer formats clamp
+    pub clipped: usize,
+    /// Output samples over all channels
+    pub samples: usize,
+    /// Largest absolute sample, 1.0 being full scale
+    pub peak: f32,
+}
+
+impl fmt::Display for ClipReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let peak = info::dbfs(self.peak);
+        if self.clipped == 0 {
+            return write!(f, "no clipping, peak {peak:.1} dBFS");
+        }
+        write!(
+            f,
+            "clipped {} of {} samples ({:.3}%), peak {peak:+.1} dBFS",
+            self.clipped,
+            self.samples,
+            self.clipped as f64 / self.samples as f64 * 100.0
+        )
+    }
+}
+
+/// Resolved parameters of one voice, as printed by `--verbose`
+#[derive(Debug, Clone, PartialEq)]
+pub struct VoiceInfo {
+    pub index: usize,
+    /// Frequency in Hz (the starting one for gliding voices)
+    pub freq: f32,
+    /// Equal-tempered note closest to `freq`
+    pub note: Note,
+    /// How far `freq` lies from `note`, in cents
+    pub cents: f32,
+    pub lfo_rate: f32,
+    pub mod_depth: f32,
+    pub pan_rate: f32,
+}
+
+impl fmt::Display for VoiceInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "voice {:>2}: {:>8.2} Hz ({} {:+.0} cents), lfo {:.3} Hz, depth {:.2}, pan {:.3} Hz",
+            self.index,
+            self.freq,
+            self.note,
+            self.cents,
+            self.lfo_rate,
+            self.mod_depth,
+            self.pan_rate
+        )
+    }
+}
+
+/// A voice's chord in a `--progression`, chord `index` of `count` played in
+/// turn for `duration` seconds each, round and round
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ChordSlot {
+    index: usize,
+    count: usize,
+    duration: f64,
+    /// Seconds two neighbouring chords overlap, centred on the change
+    crossfade: f64,
+}
+
+/// When a voice sounds with `--voice-stagger`: fading in over `attack`
+/// seconds from `enter`, and out over `release` seconds ending at `leave`
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct VoiceEntry {
+    enter: f64,
+    leave: f64,
+    attack: f64,
+    release: f64,
+}
+
+impl VoiceEntry {
+    /// Gain at `time` seconds, linear in both ramps
+    fn level(&self, time: f64) -> f32 {
+        let ramp = |elapsed: f64, length: f64| {
+            if length > 0.0 {
+                (elapsed / length).clamp(0.0, 1.0)
+            } else if elapsed >= 0.0 {
+                1.0
+            } else {
+                0.0
+            }
+        };
+        ramp(time - self.enter, self.attack).min(ramp(self.leave - time, self.release)) as f32
+    }
+}
+
+impl ChordSlot {
+    /// Gain at `time` seconds: 1.0 through the chord's turn and 0.0 outside
+    /// it, with equal-power fades into the chords either side
+    fn level(&self, time: f64) -> f32 {
+        if self.count < 2 {
+            return 1.0;
+        }
+        let turn = (time / self.duration).floor().max(0.0);
+        let into = time - turn * self.duration;
+        let playing = turn as usize % self.count;
+        let half = self.crossfade / 2.0;
+        // how far through the fade into the next chord, 0.0 to 1.0
+        let (fading, toward) = if into < half && turn > 0.0 {
+            (0.5 + into / self.crossfade, playing)
+        } else if into > self.duration - half {
+            (
+                (into - (self.duration - half)) / self.crossfade,
+                (playing + 1) % self.count,
+            )
+        } else {
+            return if playing == self.index { 1.0 } else { 0.0 };
+        };
+        let from = (toward + self.count - 1) % self.count;
+        let angle = fading as f32 * FRAC_PI_2;
+        if self.index == toward {
+            angle.sin()
+        } else if self.index == from {
+            angle.cos()
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Sine modulator driving a voice's frequency in FM mode
+#[derive(Debug, Clone, Copy)]
+struct Modulator {
+    freq: f32,
+    /// Peak deviation as a multiple of `freq`
+    index: f32,
+}
+
+/// Wraps a phase that moved by less than one cycle back into [0, 2π)
+fn wrap_phase(phase: f64) -> f64 {
+    if phase >= TAU {
+        phase - TAU
+    } else if phase < 0.0 {
+        phase + TAU
+    } else {
+        phase
+    }
+}
+
+/// Phase accumulators (radians, in [0, 2π)) for each of a voice's oscillators
+#[derive(Debug, Clone, Copy, Default)]
+struct VoicePhases {
+    carrier: f64,
+    /// Right-ear carrier, only advanced in binaural mode
+    carrier_right: f64,
+    lfo: f64,
+    pan: f64,
+    depth: f64,
+    modulator: f64,
+}
+
+/// One randomized oscillator voice of a [`Generator`]
+#[derive(Debug, Clone)]
+pub struct Voice {
     freq: f32,
     lfo_rate: f32,
     mod_depth: f32,
     pan_rate: f32,
+    /// Linear gain weight applied to this voice's output
+    gain: f32,
+    /// Panning depth, 0.0 (center) to 1.0 (full width)
+    pan_spread: f32,
+    /// Fixed position in -1.0..=1.0; oscillates at `pan_rate` when `None`
+    static_pan: Option<f32>,
+    /// Fixed front (-1.0) to rear (1.0) position; oscillates at `depth_rate`
+    /// when `None`. Stereo voices sit at -1.0.
+    static_depth: Option<f32>,
+    depth_rate: f32,
+    /// Shared sine table used instead of `sin()` when `--fast-sine` is set
+    wavetable: Option<&'static [f32]>,
+    /// Right-ear frequency offset in Hz; 0.0 plays one panned carrier
+    binaural: f32,
+    /// Additive (frequency ratio, amplitude) pairs; a pure sine when empty
+    partials: Vec<(f32, f32)>,
+    /// Phase accumulator for each partial
+    partial_phases: Vec<f64>,
+    /// FM modulator bending the carrier frequency, if any
+    modulator: Option<Modulator>,
+    /// Path between scale notes that replaces `freq` with `--glide-rate`
+    glide: Option<Arc<GlidePath>>,
+    /// Turn in a `--progression` the voice sounds in; always on when `None`
+    chord_slot: Option<ChordSlot>,
+    /// The voice's own fades with `--voice-stagger`
+    entry: Option<VoiceEntry>,
+    phases: VoicePhases,
+    /// Sample the phases are at, which the glide path is looked up by
+    position: usize,
+    sample_rate: f32,
 }
 
 impl Voice {
-    fn synthesize(&self, t: f32) -> (f32, f32) {
-        let mod_env = (2.0 * PI * self.lfo_rate * t).sin() * 0.5 + 0.5;
-        let sample = (2.0 * PI * self.freq * t).sin() * (mod_env * self.mod_depth);
-        let pan = (2.0 * PI * self.pan_rate * t).sin();
+    /// Carrier frequency in Hz (the starting one for gliding voices)
+    pub fn freq(&self) -> f32 {
+        self.freq
+    }
+
+    /// Rate of the amplitude LFO in Hz
+    pub fn lfo_rate(&self) -> f32 {
+        self.lfo_rate
+    }
+
+    /// Depth of the amplitude LFO, 0.0 to 1.0
+    pub fn mod_depth(&self) -> f32 {
+        self.mod_depth
+    }
+
+    /// Rate of the stereo pan oscillation in Hz
+    pub fn pan_rate(&self) -> f32 {
+        self.pan_rate
+    }
+
+    fn sin(&self, phase: f32) -> f32 {
+        match self.wavetable {
+            Some(table) => wavetable::lookup(table, phase),
+            None => phase.sin(),
+        }
+    }
+
+    /// Amplitude envelope (LFO swell times depth and gain) at `time` seconds
+    fn envelope_at(&self, time: f64) -> f32 {
+        let lfo = (TAU * self.lfo_rate as f64 * time).sin() as f32;
+        (lfo * 0.5 + 0.5) * self.mod_depth * self.gain * self.level(time)
+    }
+
+    /// Level of the voice's chord in a `--progression` and its own
+    /// `--voice-stagger` fades at `time` seconds
+    fn level(&self, time: f64) -> f32 {
+        self.chord_slot.map_or(1.0, |slot| slot.level(time))
+            * self.entry.map_or(1.0, |entry| entry.level(time))
+    }
+
+    /// Stereo position in -1.0 (left) ..= 1.0 (right), scaled by the spread
+    fn pan(&self) -> f32 {
+        let position = match self.static_pan {
+            Some(position) => position,
+            None => self.sin(self.phases.pan as f32),
+        };
+        position * self.pan_spread
+    }
+
+    /// Front/rear position in -1.0 (front) ..= 1.0 (rear)
+    fn depth(&self) -> f32 {
+        match self.static_depth {
+            Some(position) => position,
+            None => self.sin(self.phases.depth as f32) * self.pan_spread,
+        }
+    }
+
+    /// Moves every oscillator to the phase it has at `sample`, as if it had
+    /// been advanced from zero. Phases are computed in f64 from the sample
+    /// index, so seeking far into a render stays exact.
+    ///
+    /// FM keeps a closed form because the modulator is a pure sine:
+    /// integrating `index * f_m * sin(2π f_m t)` gives `index * (1 - cos(2π f_m t))`.
+    fn seek(&mut self, sample: usize) {
+        let t = sample as f64 / self.sample_rate as f64;
+        let at = |freq: f32| (TAU * freq as f64 * t).rem_euclid(TAU);
+
+        let mut carrier = match &self.glide {
+            Some(glide) => glide.phase_at(t),
+            None => TAU * self.freq as f64 * t,
+        };
+        if let Some(m) = self.modulator {
+            carrier += m.index as f64 * (1.0 - (TAU * m.freq as f64 * t).cos());
+            self.phases.modulator = at(m.freq);
+        }
+        self.phases.carrier = carrier.rem_euclid(TAU);
+        for (phase, &(ratio, _)) in self.partial_phases.iter_mut().zip(&self.partials) {
+            *phase = (carrier * ratio as f64).rem_euclid(TAU);
+        }
+        if self.binaural != 0.0 {
+            let beat = TAU * self.binaural as f64 * t;
+            self.phases.carrier_right = (carrier + beat).rem_euclid(TAU);
+        }
+        self.phases.lfo = at(self.lfo_rate);
+        self.phases.pan = at(self.pan_rate);
+        self.phases.depth = at(self.depth_rate);
+        self.position = sample;
+    }
+
+    /// Advances every oscillator by one sample
+    fn advance(&mut self) {
+        let step = TAU / self.sample_rate as f64;
+        let mut freq = match &self.glide {
+            // the midpoint frequency integrates a glide exactly to first order
+            Some(glide) => glide.freq_at((self.position as f64 + 0.5) / self.sample_rate as f64),
+            None => self.freq as f64,
+        };
+        if let Some(m) = self.modulator {
+            let modulator = self.sin(self.phases.modulator as f32) as f64;
+            freq += (m.index * m.freq) as f64 * modulator;
+            self.phases.modulator = wrap_phase(self.phases.modulator + step * m.freq as f64);
+        }
+        // FM can push the carrier past a full cycle per sample at extreme indices
+        self.phases.carrier = (self.phases.carrier + step * freq).rem_euclid(TAU);
+        for (phase, &(ratio, _)) in self.partial_phases.iter_mut().zip(&self.partials) {
+            *phase = (*phase + step * freq * ratio as f64).rem_euclid(TAU);
+        }
+        if self.binaural != 0.0 {
+            let right = self.phases.carrier_right + step * (freq + self.binaural as f64);
+            self.phases.carrier_right = right.rem_euclid(TAU);
+        }
+        self.phases.lfo = wrap_phase(self.phases.lfo + step * self.lfo_rate as f64);
+        self.phases.pan = wrap_phase(self.phases.pan + step * self.pan_rate as f64);
+        self.phases.depth = wrap_phase(self.phases.depth + step * self.depth_rate as f64);
+        self.position += 1;
+    }
+
+    /// Carrier waveform at the current phase: the sum of the partials, or a
+    /// plain sine without any
+    fn tone(&self) -> f32 {
+        if self.partials.is_empty() {
+            return self.sin(self.phases.carrier as f32);
+        }
+        self.partials
+            .iter()
+            .zip(&self.partial_phases)
+            .map(|(&(_, amp), &phase)| self.sin(phase as f32) * amp)
+            .sum()
+    }
+
+    /// Renders the current sample as front left, front right, rear left and
+    /// rear right, and advances to the next one. The rear pair is silent
+    /// unless the voice has been moved back.
+    fn synthesize(&mut self) -> [f32; 4] {
+        let mod_env = self.sin(self.phases.lfo as f32) * 0.5 + 0.5;
+        let time = self.position as f64 / self.sample_rate as f64;
+        let amp = mod_env * self.mod_depth * self.gain * self.level(time);
+        if self.binaural != 0.0 {
+            let left = self.sin(self.phases.carrier as f32) * amp * 0.5;
+            let right = self.sin(self.phases.carrier_right as f32) * amp * 0.5;
+            self.advance();
+            return [left, right, 0.0, 0.0];
+        }
+
+        let sample = self.tone() * amp;
+        let pan = self.pan();
+        let depth = self.depth();
+        self.advance();
         let l_gain = (1.0 - pan) * 0.5;
         let r_gain = (1.0 + pan) * 0.5;
+        let front = (1.0 - depth) * 0.5;
+        let rear = (1.0 + depth) * 0.5;
 
-        (sample * l_gain, sample * r_gain)
+        [
+            sample * l_gain * front,
+            sample * r_gain * front,
+            sample * l_gain * rear,
+            sample * r_gain * rear,
+        ]
     }
 }
 
-fn v4_uuid() -> String {
-    uuid::Uuid::new_v4().to_string()
+/// Parses an automation curve such as "reverb_mix:0.1@0,0.6@120"
+fn parse_automation(s: &str) -> Result<Automation, String> {
+    let (name, points) = s
+        .split_once(':')
+        .ok_or_else(|| format!("automation '{s}' should be param:value@seconds,..."))?;
+    let target = match name.trim().replace('-', "_").as_str() {
+        "reverb_mix" => AutomationTarget::ReverbMix,
+        "noise_level" => AutomationTarget::NoiseLevel,
+        other => {
+            return Err(format!(
+                "can't automate '{other}', expected reverb_mix or noise_level"
+            ))
+        }
+    };
+    let mut breakpoints = points
+        .split(',')
+        .map(|point| {
+            let (value, time) = point
+                .split_once('@')
+                .ok_or_else(|| format!("breakpoint '{point}' should be value@seconds"))?;
+            let parse = |v: &str| {
+                v.trim()
+                    .parse::<f32>()
+                    .map_err(|err| format!("invalid breakpoint '{point}': {err}"))
+            };
+            Ok((parse(time)?, parse(value)?))
+        })
+        .collect::<Result<Vec<(f32, f32)>, String>>()?;
+    breakpoints.sort_by(|a, b| a.0.total_cmp(&b.0));
+    Ok(Automation {
+        target,
+        breakpoints,
+    })
 }
 
-fn generate_filename(root: &'static str, ext: OutputType) -> String {
-    let prefix = &root.replace(" ", "_").to_lowercase();
-    let extension = ext.as_str();
+/// Parses one "ratio:amplitude" partial
+fn parse_partial(s: &str) -> Result<(f32, f32), String> {
+    let (ratio, amp) = s
+        .split_once(':')
+        .ok_or_else(|| format!("partial '{s}' should be ratio:amplitude"))?;
+    let parse = |v: &str| {
+        v.trim()
+            .parse::<f32>()
+            .map_err(|err| format!("invalid partial '{s}': {err}"))
+    };
+    Ok((parse(ratio)?, parse(amp)?))
+}
+
+fn parse_render_range(s: &str) -> Result<(f32, f32), String> {
+    let (start, end) = s
+        .split_once(':')
+        .ok_or_else(|| format!("render range '{s}' should be start:end in seconds"))?;
+    let parse = |v: &str| {
+        v.trim()
+            .parse::<f32>()
+            .map_err(|err| format!("invalid render range '{s}': {err}"))
+    };
+    let (start, end) = (parse(start)?, parse(end)?);
+    if !(0.0 <= start && start < end) {
+        return Err(format!("render range '{s}' needs 0 <= start < end"));
+    }
+    Ok((start, end))
+}
+
+fn parse_preview(s: &str) -> Result<f32, String> {
+    match s.trim().parse::<f32>() {
+        Ok(seconds) if seconds > 0.0 => Ok(seconds),
+        Ok(_) => Err(format!("preview length '{s}' must be positive")),
+        Err(err) => Err(format!("invalid preview length '{s}': {err}")),
+    }
+}
+
+fn parse_channels(s: &str) -> Result<u16, String> {
+    match s {
+        "2" => Ok(2),
+        "4" => Ok(4),
+        _ => Err(format!(
+            "unsupported channel count '{s}', expected 2 or 4 (see --mono for one)"
+        )),
+    }
+}
+
+fn parse_time_signature(s: &str) -> Result<TimeSignature, String> {
+    let (num, den) = s
+        .split_once('/')
+        .ok_or_else(|| format!("expected a time signature like '3/4', got '{s}'"))?;
+    let parse = |part: &str| part.trim().parse::<u8>().ok();
+    parse(num)
+        .zip(parse(den))
+        .and_then(|(num, den)| TimeSignature::new(num, den))
+        .ok_or_else(|| format!("invalid time signature '{s}'"))
+}
+
+fn parse_oversample(s: &str) -> Result<usize, String> {
+    match s {
+        "1" => Ok(1),
+        "2" => Ok(2),
+        "4" => Ok(4),
+        _ => Err(format!(
+            "unsupported oversampling '{s}', expected 1, 2 or 4"
+        )),
+    }
+}
+
+fn parse_tempo(s: &str) -> Result<u16, String> {
+    let bpm: u16 = s.parse().map_err(|_| format!("invalid tempo '{s}'"))?;
+    match Tempo::new(bpm) {
+        Some(_) => Ok(bpm),
+        None => Err(format!(
+            "unsupported tempo '{s}', expected {} to {} BPM",
+            Tempo::MIN,
+            Tempo::MAX
+        )),
+    }
+}
+
+fn parse_bit_depth(s: &str) -> Result<u16, String> {
+    match s {
+        "16" => Ok(16),
+        "24" => Ok(24),
+        _ => Err(format!("unsupported bit depth '{s}', expected 16 or 24")),
+    }
+}
+
+/// Smoothing coefficient of a one-pole low-pass with the given cutoff:
+/// `1 - e^(-2π fc / fs)`
+fn one_pole_coefficient(cutoff_hz: f32, sample_rate: u32) -> f32 {
+    1.0 - (-TAU * cutoff_hz as f64 / sample_rate as f64).exp() as f32
+}
+
+/// Converts decibels to a linear amplitude factor
+fn db_to_gain(db: f32) -> f32 {
+    10.0f32.powf(db / 20.0)
+}
+
+fn v4_uuid() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+fn generate_filename(root: &'static str, ext: OutputType) -> String {
+    let prefix = &root.replace(" ", "_").to_lowercase();
+    let extension = ext.as_str();
+
+    format!("{}_{}.{}", v4_uuid(), prefix, extension)
+}
+
+/// Draws a progress bar on stderr, finishing the line once complete
+fn print_progress(fraction: f32) {
+    const WIDTH: usize = 40;
+    let filled = (fraction.clamp(0.0, 1.0) * WIDTH as f32) as usize;
+    eprint!(
+        "\r[{}{}] {:>3.0}%",
+        "#".repeat(filled),
+        " ".repeat(WIDTH - filled),
+        fraction * 100.0
+    );
+    if fraction >= 1.0 {
+        eprintln!();
+    }
+}
+
+fn print_presets() {
+    for name in PRESETS {
+        let preset = JsonConfig::preset(name).expect("listed presets exist");
+        let voicing = match &preset.chord {
+            Some(chord) => format!("chord {chord}"),
+            None => format!("{} voices", preset.voices),
+        };
+        println!(
+            "{name:<10} {voicing}, {} Hz, {}s, reverb {}, {} grains/s",
+            preset.base_freq, preset.duration, preset.reverb_mix, preset.grain_density
+        );
+    }
+}
+
+pub fn run() -> Result<(), Error> {
+    let cli = CLI::parse_with_preset(std::env::args_os());
+    if let Some(command) = cli.command {
+        return match command {
+            Command::Info { file } => {
+                println!("{file}\n{}", WavInfo::read(&file)?);
+                Ok(())
+            }
+            Command::Normalize {
+                input,
+                output,
+                target,
+                normalize_mode,
+            } => {
+                let target = target.unwrap_or(normalize_mode.default_target());
+                let gain = normalize::normalize_file(&input, &output, normalize_mode, target)?;
+                println!(
+                    "Normalized '{input}' to {target} dBFS ({:+.1} dB) as '{output}'.",
+                    20.0 * gain.log10()
+                );
+                Ok(())
+            }
+            Command::Process {
+                input,
+                output,
+                swap_channels,
+                invert_right,
+            } => {
+                let ops = process::ChannelOps {
+                    swap_channels,
+                    invert_right,
+                };
+                let spec = process::process_file(&input, &output, ops)?;
+                if spec.channels < 2 {
+                    eprintln!("warning: '{input}' is mono, copied it unchanged");
+                }
+                println!("Processed '{input}' into '{output}'.");
+                Ok(())
+            }
+            Command::Batch {
+                config_dir,
+                out_dir,
+                loudness_match,
+                loudness_target,
+            } => {
+                let report = batch::render_dir(&config_dir, &out_dir)?;
+                for (config, reason) in &report.failed {
+                    eprintln!("failed: {}: {reason}", config.display());
+                }
+                println!(
+                    "Rendered {} of {} configs into '{out_dir}'.",
+                    report.rendered.len(),
+                    report.rendered.len() + report.failed.len()
+                );
+                if loudness_match {
+                    let (wavs, others): (Vec<_>, Vec<_>) = report
+                        .rendered
+                        .iter()
+                        .partition(|path| path.extension().is_some_and(|ext| ext == "wav"));
+                    for path in others {
+                        eprintln!(
+                            "warning: --loudness-match only reads WAV, leaving '{}' as is",
+                            path.display()
+                        );
+                    }
+                    let level = normalize::match_loudness(&wavs, loudness_target)?;
+                    println!("Matched {} renders to {level:.1} dBFS RMS.", wavs.len());
+                }
+                match report.failed.len() {
+                    0 => Ok(()),
+                    failed => Err(Error::BatchFailed(failed)),
+                }
+            }
+        };
+    }
+    if cli.list_presets {
+        print_presets();
+        return Ok(());
+    }
+    let progress = cli.progress;
+    let play = cli.play;
+    let verbose = cli.verbose;
+    let peak_report = cli.peak_report;
+    let mut generator = Generator::new(cli.to_params()?)?;
+    if verbose {
+        for voice in generator.voice_info() {
+            println!("{voice}");
+        }
+    }
+    if progress {
+        generator.set_progress_callback(print_progress);
+    }
+    generator.run()?;
+    if peak_report {
+        println!("{}", generator.clip_report());
+    }
+    if play {
+        generator.play()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use procsynth_core::{ChordKind, PitchClass};
+    use std::f32::consts::PI;
+    fn cli() -> CLI {
+        CLI {
+            command: None,
+            config: None,
+            preset: None,
+            list_presets: false,
+            output: Some("test.wav".to_string()),
+            duration: 30.0,
+            sample_rate: 48000,
+            voices: 6,
+            base_freq: 440.0,
+            lfo_rate_range: "0.1:0.3".to_string(),
+            noise_level: 0.01,
+            mod_depth_range: "0.3:0.8".to_string(),
+            attack: 3.0,
+            release: 5.0,
+            reverb_mix: 0.4,
+            pan_spread: 1.0,
+            static_pan: false,
+            mono: false,
+            progress: false,
+            play: false,
+            verbose: false,
+            peak_report: false,
+            seed: None,
+            threads: 0,
+            fast_sine: false,
+            fm_index: 0.0,
+            fm_pairing: FmPairing::Pairs,
+            gain_db: 0.0,
+            voice_gains: Vec::new(),
+            loop_mode: false,
+            crossfade_seconds: 2.0,
+            grain_density: 8.0,
+            grain_duration: 0.08,
+            grain_pitch_jitter: 7.0,
+            chord: None,
+            binaural: 0.0,
+            tempo: 120,
+            lfo_sync: false,
+            format: None,
+            ogg_quality: 0.5,
+            bit_depth: 16,
+            partials: Vec::new(),
+            width: 1.0,
+            saturation: 0.0,
+            noise_cutoff_hz: 739.5,
+            fade_curve: FadeCurve::Linear,
+            reverb_hpf: 0.0,
+            chorus_depth: 0.0,
+            chorus_rate: 0.3,
+            autopan_depth: 0.0,
+            autopan_rate: 0.05,
+            output_channels: 2,
+            automation: Vec::new(),
+            max_duration: 3600.0,
+            detune_range: "0.8:1.2".to_string(),
+            detune_dist: DetuneDistribution::Uniform,
+            fade_in_ms: 0.0,
+            fade_out_ms: 0.0,
+            reverb_tail: 0.0,
+            scale: None,
+            glide_rate: 0.0,
+            noise_duck: 0.0,
+            haas_ms: 0.0,
+            spectrogram: None,
+            spectrogram_window: 2048,
+            spectrogram_hop: 512,
+            dither: false,
+            noise_shaping: false,
+            reverb_fade: 0.0,
+            resonators: Vec::new(),
+            resonator_decay: 2.0,
+            render_range: None,
+            preview: None,
+            progression: None,
+            chord_duration: 4.0,
+            stems: None,
+            oversample: 1,
+            voice_stagger: 0.0,
+            wind_level: 0.0,
+            wind_center: 800.0,
+            wind_sweep: 1.0,
+            click: false,
+            time_sig: TimeSignature(4, 4),
+            noise_type: NoiseType::White,
+            reverb_ir: None,
+            max_voices: 0,
+            voice_steal: StealPolicy::Oldest,
+            detune_cents: None,
+        }
+    }
+
+    fn params() -> GeneratorParams {
+        GeneratorParams {
+            filename: "test.wav".to_string(),
+            sample_rate: 44100,
+            duration: 20.0,
+            lfo_range: "0.05:0.2".to_string(),
+            mod_depth_range: "0.5:1.0".to_string(),
+            voices: 4,
+            base_freq: 330.0,
+            noise_level: 0.005,
+            attack: 5.0,
+            release: 10.0,
+            reverb_mix: 0.3,
+            pan_spread: 1.0,
+            static_pan: false,
+            mono: false,
+            seed: None,
+            threads: 0,
+            fast_sine: false,
+            fm_index: 0.0,
+            fm_pairing: FmPairing::Pairs,
+            gain_db: 0.0,
+            voice_gains: Vec::new(),
+            loop_mode: false,
+            crossfade_seconds: 2.0,
+            grain_density: 8.0,
+            grain_duration: 0.08,
+            grain_pitch_jitter: 7.0,
+            chord: None,
+            binaural: 0.0,
+            tempo: 120,
+            lfo_sync: false,
+            format: OutputType::WAV,
+            ogg_quality: 0.5,
+            bit_depth: 16,
+            partials: Vec::new(),
+            width: 1.0,
+            saturation: 0.0,
+            noise_cutoff_hz: 739.5,
+            fade_curve: FadeCurve::Linear,
+            reverb_hpf: 0.0,
+            chorus_depth: 0.0,
+            chorus_rate: 0.3,
+            autopan_depth: 0.0,
+            autopan_rate: 0.05,
+            output_channels: 2,
+            automation: Vec::new(),
+            max_duration: 3600.0,
+            detune_range: "0.8:1.2".to_string(),
+            detune_dist: DetuneDistribution::Uniform,
+            fade_in_ms: 0.0,
+            fade_out_ms: 0.0,
+            reverb_tail: 0.0,
+            scale: None,
+            glide_rate: 0.0,
+            noise_duck: 0.0,
+            haas_ms: 0.0,
+            spectrogram: None,
+            spectrogram_window: 2048,
+            spectrogram_hop: 512,
+            dither: false,
+            noise_shaping: false,
+            reverb_fade: 0.0,
+            resonators: Vec::new(),
+            resonator_decay: 2.0,
+            render_range: None,
+            progression: None,
+            chord_duration: 4.0,
+            stems: None,
+            oversample: 1,
+            voice_stagger: 0.0,
+            wind_level: 0.0,
+            wind_center: 800.0,
+            wind_sweep: 1.0,
+            click: false,
+            time_sig: TimeSignature(4, 4),
+            noise_type: NoiseType::White,
+            reverb_ir: None,
+            max_voices: 0,
+            voice_steal: StealPolicy::Oldest,
+            detune_cents: None,
+        }
+    }
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("{}_{}", v4_uuid(), name))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    fn voice() -> Voice {
+        Voice {
+            freq: 440.0,
+            lfo_rate: 0.1,
+            mod_depth: 0.5,
+            pan_rate: 0.02,
+            gain: 1.0,
+            binaural: 0.0,
+            partials: Vec::new(),
+            partial_phases: Vec::new(),
+            pan_spread: 1.0,
+            static_pan: None,
+            static_depth: Some(-1.0),
+            depth_rate: 0.0,
+            wavetable: None,
+            modulator: None,
+            glide: None,
+            chord_slot: None,
+            entry: None,
+            phases: VoicePhases::default(),
+            position: 0,
+            sample_rate: 44100.0,
+        }
+    }
+
+    /// Power of `freq` in `samples` (Goertzel), normalized by length
+    fn tone_power(samples: &[f32], freq: f32, sample_rate: f32) -> f32 {
+        let coeff = 2.0 * (2.0 * PI * freq / sample_rate).cos();
+        let (mut s1, mut s2) = (0.0f32, 0.0f32);
+        for &x in samples {
+            let s0 = x + coeff * s1 - s2;
+            s2 = s1;
+            s1 = s0;
+        }
+        (s1 * s1 + s2 * s2 - coeff * s1 * s2) / (samples.len() as f32).powi(2)
+    }
+
+    fn generator_for_envelope() -> Generator {
+        let params = params();
+
+        Generator {
+            params,
+            voices: vec![],
+            grains: GrainCloud::default(),
+            num_samples: 0,
+            samples: vec![],
+            rear: vec![],
+            seed: 0,
+            filter_coeff: 0.1,
+            filter_prev_l: 0.0,
+            filter_prev_r: 0.0,
+            wind: Wind::default(),
+            impulse_response: None,
+            progress: None,
+            first_sample: 0,
+            stems: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_output_type_as_str() {
+        assert_eq!(OutputType::MIDI.as_str(), "midi");
+        assert_eq!(OutputType::WAV.as_str(), "wav");
+        assert_eq!(OutputType::OGG.as_str(), "ogg");
+        assert_eq!(OutputType::FLAC.as_str(), "flac");
+    }
+
+    #[test]
+    fn test_output_type_resolve() {
+        use OutputType::*;
+        assert_eq!(OutputType::resolve(None, None), WAV);
+        assert_eq!(OutputType::resolve(None, Some("drone.OGG")), OGG);
+        assert_eq!(OutputType::resolve(None, Some("drone.wav")), WAV);
+        assert_eq!(OutputType::resolve(Some(OGG), Some("drone.wav")), OGG);
+
+        let params: GeneratorParams = parse_cli(["test", "--format", "ogg"]).into();
+        assert_eq!(params.format, OGG);
+        assert!(params.filename.ends_with(".ogg"));
+    }
+
+    #[cfg(feature = "ogg")]
+    #[test]
+    fn test_ogg_output_decodes() {
+        let filename = temp_path("render.ogg");
+        let params = GeneratorParams {
+            filename: filename.clone(),
+            duration: 1.0,
+            format: OutputType::OGG,
+            ..params()
+        };
+        let mut generator = Generator::new(params).unwrap();
+        generator.generate().unwrap();
+        generator.write_output().unwrap();
+        assert!(std::fs::metadata(&filename).unwrap().len() > 0);
+
+        let file = std::io::BufReader::new(File::open(&filename).unwrap());
+        let mut decoder = vorbis_rs::VorbisDecoder::<std::io::BufReader<File>>::new(file).unwrap();
+        assert_eq!(decoder.channels().get(), 2);
+        let mut frames = 0;
+        while let Some(block) = decoder.decode_audio_block().unwrap() {
+            frames += block.samples()[0].len();
+        }
+        let seconds = frames as f32 / decoder.sampling_frequency().get() as f32;
+        assert!((seconds - 1.0).abs() < 0.05);
+        std::fs::remove_file(&filename).unwrap();
+    }
+
+    #[test]
+    fn test_generate_filename_helper() {
+        let filename = generate_filename("test ambient", OutputType::WAV);
+        assert!(filename.contains("test_ambient"));
+        assert!(filename.ends_with(".wav"));
+        assert!(filename.len() > 20); // UUID + prefix + extension
+    }
+
+    /// Held by tests that set or depend on the `PROCSYNTH_*` variables
+    static ENV_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    /// Parses `args` under [`ENV_LOCK`], so `PROCSYNTH_*` variables set by
+    /// another test can't leak into the result
+    fn parse_cli<const N: usize>(args: [&str; N]) -> CLI {
+        let _env = ENV_LOCK.lock().unwrap();
+        CLI::parse_from(args)
+    }
+
+    /// [`parse_cli`] for arguments that may be rejected
+    fn try_parse_cli<const N: usize>(args: [&str; N]) -> Result<CLI, clap::Error> {
+        let _env = ENV_LOCK.lock().unwrap();
+        CLI::try_parse_from(args)
+    }
+
+    #[test]
+    fn test_cli_default_values() {
+        let cli = parse_cli(["procsynth"]);
+        assert_eq!(cli.duration, 60.0);
+        assert_eq!(cli.sample_rate, 44100);
+        assert_eq!(cli.voices, 4);
+        assert_eq!(cli.base_freq, 330.0);
+        assert_eq!(cli.lfo_rate_range, "0.05:0.2");
+        assert_eq!(cli.noise_level, 0.005);
+        assert_eq!(cli.mod_depth_range, "0.5:1.0");
+        assert_eq!(cli.attack, 5.0);
+        assert_eq!(cli.release, 10.0);
+        assert_eq!(cli.reverb_mix, 0.3);
+        assert!(cli.output.is_none());
+        // the grain cloud is opt-in, for the CLI and for configs without it
+        assert_eq!(cli.grain_density, 0.0);
+        assert_eq!(JsonConfig::default().grain_density, 0.0);
+    }
+
+    #[test]
+    fn test_presets_are_valid() {
+        for name in PRESETS {
+            let preset = JsonConfig::preset(name).unwrap();
+            let json = serde_json::to_string(&preset).unwrap();
+            let config: JsonConfig = serde_json::from_str(&json).unwrap();
+            let params = config.to_params().unwrap();
+            assert!(params.duration > 0.0);
+            assert!(params.voices > 0);
+            assert!(params.chord.is_some() == preset.chord.is_some());
+            assert!(Generator::new(params).is_ok());
+        }
+        assert!(JsonConfig::preset("nonexistent").is_none());
+    }
+
+    #[test]
+    fn test_preset_overridden_by_explicit_flags() {
+        let _env = ENV_LOCK.lock().unwrap();
+        let cli = CLI::parse_with_preset(["procsynth", "--preset", "glacial"]);
+        assert_eq!(cli.voices, 8);
+        assert_eq!(cli.lfo_rate_range, "0.005:0.02");
+        assert_eq!(cli.duration, 600.0);
+
+        let cli = CLI::parse_with_preset(["procsynth", "--preset", "glacial", "--voices", "3"]);
+        assert_eq!(cli.voices, 3);
+        assert_eq!(cli.duration, 600.0);
+
+        let cli = CLI::parse_with_preset(["procsynth", "--preset", "drone"]);
+        assert_eq!(cli.chord.unwrap().kind, ChordKind::Minor);
+    }
+
+    #[test]
+    fn test_env_var_defaults() {
+        let _env = ENV_LOCK.lock().unwrap();
+        std::env::set_var("PROCSYNTH_VOICES", "10");
+        let from_env: GeneratorParams = CLI::parse_from(["procsynth"]).into();
+        let explicit: GeneratorParams = CLI::parse_from(["procsynth", "--voices", "4"]).into();
+        let preset = CLI::parse_with_preset(["procsynth", "--preset", "glacial"]);
+        std::env::remove_var("PROCSYNTH_VOICES");
+
+        assert_eq!(from_env.voices, 10);
+        assert_eq!(explicit.voices, 4);
+        assert_eq!(preset.voices, 10);
+        assert_eq!(preset.duration, 600.0);
+    }
+
+    #[test]
+    fn test_cli_custom_values() {
+        let cli = parse_cli([
+            "procsynth",
+            "--output",
+            "test.wav",
+            "--duration",
+            "30.0",
+            "--sample-rate",
+            "48000",
+            "--voices",
+            "8",
+            "--base-freq",
+            "440.0",
+            "--lfo-rate-range",
+            "0.1:0.3",
+            "--noise-level",
+            "0.01",
+            "--mod-depth-range",
+            "0.3:0.8",
+            "--attack",
+            "3.0",
+            "--release",
+            "5.0",
+            "--reverb-mix",
+            "0.5",
+        ]);
+
+        assert_eq!(cli.output, Some("test.wav".to_string()));
+        assert_eq!(cli.duration, 30.0);
+        assert_eq!(cli.sample_rate, 48000);
+        assert_eq!(cli.voices, 8);
+        assert_eq!(cli.base_freq, 440.0);
+        assert_eq!(cli.lfo_rate_range, "0.1:0.3");
+        assert_eq!(cli.noise_level, 0.01);
+        assert_eq!(cli.mod_depth_range, "0.3:0.8");
+        assert_eq!(cli.attack, 3.0);
+        assert_eq!(cli.release, 5.0);
+        assert_eq!(cli.reverb_mix, 0.5);
+    }
+
+    #[test]
+    fn test_generator_params_conversion() {
+        let cli = cli();
+        let params = cli.to_params().unwrap();
+
+        assert_eq!(params.filename, "test.wav");
+        assert_eq!(params.duration, 30.0);
+        assert_eq!(params.sample_rate, 48000);
+        assert_eq!(params.voices, 6);
+        assert_eq!(params.base_freq, 440.0);
+        assert_eq!(params.lfo_range, "0.1:0.3");
+        assert_eq!(params.noise_level, 0.01);
+        assert_eq!(params.mod_depth_range, "0.3:0.8");
+        assert_eq!(params.attack, 3.0);
+        assert_eq!(params.release, 5.0);
+        assert_eq!(params.reverb_mix, 0.4);
+    }
+
+    #[test]
+    fn test_generator_params_num_samples() {
+        assert_eq!(params().num_samples(), 882000); // 44100 * 20.0
+    }
+
+    #[test]
+    fn test_generator_parse_range() {
+        assert_eq!(Generator::parse_range("0.1:0.3", 0.0, 1.0), (0.1, 0.3));
+        assert_eq!(Generator::parse_range("0.5", 0.0, 1.0), (0.5, 1.0));
+        assert_eq!(Generator::parse_range("invalid", 0.2, 0.8), (0.2, 0.8));
+        assert_eq!(Generator::parse_range("", 0.2, 0.8), (0.2, 0.8));
+    }
+
+    #[test]
+    fn test_generator_spec() {
+        let spec = Generator::spec(44100, 2, 16);
+        assert_eq!(spec.channels, 2);
+        assert_eq!(spec.sample_rate, 44100);
+        assert_eq!(spec.bits_per_sample, 16);
+        assert_eq!(spec.sample_format, hound::SampleFormat::Int);
+        assert_eq!(Generator::spec(44100, 2, 24).bits_per_sample, 24);
+    }
+
+    #[test]
+    fn test_quantize() {
+        assert_eq!(Generator::quantize(1.0, 16), i16::MAX as i32);
+        assert_eq!(Generator::quantize(-2.0, 16), i16::MIN as i32);
+        assert_eq!(Generator::quantize(0.5, 24), 4_194_303);
+        assert_eq!(Generator::quantize(0.0, 24), 0);
+    }
+
+    #[test]
+    fn test_dither_randomizes_quantization() {
+        let mut generator = generator_for_envelope();
+        generator.params.dither = true;
+        // a fade from a few LSBs down to silence
+        generator.samples = (0..4000)
+            .map(|i| {
+                let x = (i as f32 * 0.01).sin() * 8.0 / 32767.0 * (1.0 - i as f32 / 4000.0);
+                (x, x)
+            })
+            .collect();
+        let truncated: Vec<i32> = generator
+            .interleaved_samples()
+            .into_iter()
+            .map(|sample| Generator::quantize(sample, 16))
+            .collect();
+
+        let first = generator.quantized_samples();
+        generator.seed = 1;
+        let second = generator.quantized_samples();
+        assert_ne!(first, truncated);
+        assert_ne!(first, second);
+        for dithered in [&first, &second] {
+            for (d, t) in dithered.iter().zip(&truncated) {
+                assert!((d - t).abs() <= 1);
+            }
+        }
+
+        // 24-bit output is never dithered
+        generator.params.bit_depth = 24;
+        let wide = generator.quantized_samples();
+        generator.params.dither = false;
+        assert_eq!(wide, generator.quantized_samples());
+    }
+
+    #[test]
+    fn test_clip_report() {
+        let mut generator = generator_for_envelope();
+        generator.samples = vec![(0.5, -0.25); 100];
+        let clean = generator.clip_report();
+        assert_eq!(clean.clipped, 0);
+        assert_eq!(clean.samples, 200);
+        assert!(clean.to_string().starts_with("no clipping, peak -6.0 dBFS"));
+
+        generator.samples[10] = (1.5, 0.2);
+        generator.samples[20] = (-0.3, -1.2);
+        let hot = generator.clip_report();
+        assert_eq!(hot.clipped, 2);
+        assert_eq!(hot.peak, 1.5);
+        assert_eq!(
+            hot.to_string(),
+            "clipped 2 of 200 samples (1.000%), peak +3.5 dBFS"
+        );
+    }
+
+    #[test]
+    fn test_noise_shaping_moves_error_up() {
+        let mut generator = generator_for_envelope();
+        generator.params.mono = true;
+        generator.params.dither = true;
+        generator.samples = (0..8000)
+            .map(|i| {
+                let x = (2.0 * PI * 220.0 * i as f32 / 44100.0).sin() * 0.001;
+                (x, x)
+            })
+            .collect();
+        let ideal: Vec<f32> = generator
+            .interleaved_samples()
+            .iter()
+            .map(|s| s * i16::MAX as f32)
+            .collect();
+        // high-band over low-band energy of the quantization error, using
+        // the first difference and the two-point sum as crude filters
+        let tilt = |quantized: Vec<i32>| {
+            let error: Vec<f32> = quantized
+                .iter()
+                .zip(&ideal)
+                .map(|(&q, x)| q as f32 - x)
+                .collect();
+            let (high, low) = error.windows(2).fold((0.0, 0.0), |(h, l), w| {
+                (h + (w[1] - w[0]).powi(2), l + (w[1] + w[0]).powi(2))
+            });
+            high / low
+        };
+
+        let flat = tilt(generator.quantized_samples());
+        generator.params.noise_shaping = true;
+        let shaped = generator.quantized_samples();
+        assert!(shaped
+            .iter()
+            .zip(&ideal)
+            .all(|(&q, x)| (q as f32 - x).abs() < 6.0));
+        let shaped = tilt(shaped);
+        assert!(flat < 1.5, "{flat}");
+        assert!(shaped > flat * 4.0, "{shaped} vs {flat}");
+    }
+
+    #[test]
+    fn test_flac_output_round_trips() {
+        let filename = temp_path("render.flac");
+        let params = GeneratorParams {
+            filename: filename.clone(),
+            duration: 0.5,
+            format: OutputType::FLAC,
+            bit_depth: 24,
+            ..params()
+        };
+        let mut generator = Generator::new(params).unwrap();
+        generator.generate().unwrap();
+        generator.write_output().unwrap();
+
+        let mut reader = claxon::FlacReader::open(&filename).unwrap();
+        assert_eq!(reader.streaminfo().bits_per_sample, 24);
+        let decoded: Vec<i32> = reader.samples().map(|s| s.unwrap()).collect();
+        assert_eq!(decoded, generator.quantized_samples());
+        std::fs::remove_file(&filename).unwrap();
+    }
+
+    #[test]
+    fn test_mono_output_channels() {
+        let spec = Generator::spec(44100, 1, 16);
+        assert_eq!(spec.channels, 1);
+
+        let filename = temp_path("mono.wav");
+        let params = GeneratorParams {
+            filename: filename.clone(),
+            duration: 0.1,
+            attack: 0.01,
+            release: 0.01,
+            mono: true,
+            ..params()
+        };
+        let num_samples = params.num_samples();
+        let mut generator = Generator::new(params).unwrap();
+        generator.generate().unwrap();
+        generator.write_wav().unwrap();
+
+        let reader = hound::WavReader::open(&filename).unwrap();
+        assert_eq!(reader.spec().channels, 1);
+        assert_eq!(reader.len() as u64, num_samples);
+        std::fs::remove_file(&filename).unwrap();
+    }
+
+    #[test]
+    fn test_spectrogram_png() {
+        let filename = temp_path("spectrogram.wav");
+        let image = temp_path("spectrogram.png");
+        let params = GeneratorParams {
+            filename: filename.clone(),
+            duration: 0.5,
+            attack: 0.05,
+            release: 0.05,
+            spectrogram: Some(image.clone()),
+            spectrogram_window: 512,
+            spectrogram_hop: 256,
+            ..params()
+        };
+        Generator::new(params).unwrap().run().unwrap();
+
+        let decoder = png::Decoder::new(std::io::BufReader::new(File::open(&image).unwrap()));
+        let info = decoder.read_info().unwrap();
+        let (width, height) = (info.info().width, info.info().height);
+        assert_eq!(height, 257);
+        assert_eq!(width as usize, (22050 - 512) / 256 + 1);
+        std::fs::remove_file(&filename).unwrap();
+        std::fs::remove_file(&image).unwrap();
+    }
+
+    #[test]
+    fn test_stems_sum_to_the_mix() {
+        let filename = temp_path("stems.wav");
+        let dir = temp_path("stems");
+        let params = GeneratorParams {
+            filename: filename.clone(),
+            duration: 0.5,
+            attack: 0.05,
+            release: 0.05,
+            noise_level: 0.05,
+            grain_density: 20.0,
+            chorus_depth: 0.5,
+            reverb_tail: 0.2,
+            bit_depth: 24,
+            stems: Some(dir.clone()),
+            ..params()
+        };
+        Generator::new(params).unwrap().run().unwrap();
+
+        let read = |path: &Path| -> Vec<f32> {
+            let mut reader = hound::WavReader::open(path).unwrap();
+            let scale = ((1 << 23) - 1) as f32;
+            reader
+                .samples::<i32>()
+                .map(|s| s.unwrap() as f32 / scale)
+                .collect()
+        };
+        let mix = read(Path::new(&filename));
+        let mut sum = vec![0.0; mix.len()];
+        let names = ["voices", "noise", "grains", "filtered", "reverb"];
+        for name in names {
+            let stem = read(&Path::new(&dir).join(format!("{name}.wav")));
+            assert_eq!(stem.len(), mix.len(), "{name}");
+            assert!(stem.iter().any(|s| s.abs() > 1e-4), "{name} is silent");
+            for (total, s) in sum.iter_mut().zip(stem) {
+                *total += s;
+            }
+        }
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), names.len());
+        let error = mix
+            .iter()
+            .zip(&sum)
+            .fold(0.0f32, |max, (a, b)| max.max((a - b).abs()));
+        assert!(error < 1e-5, "{error}");
+
+        std::fs::remove_file(&filename).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_quad_output_channels() {
+        let filename = temp_path("quad.wav");
+        let params = GeneratorParams {
+            filename: filename.clone(),
+            duration: 0.1,
+            attack: 0.01,
+            release: 0.01,
+            output_channels: 4,
+            ..params()
+        };
+        let num_samples = params.num_samples();
+        let mut generator = Generator::new(params).unwrap();
+        generator.generate().unwrap();
+        generator.write_wav().unwrap();
+
+        let reader = hound::WavReader::open(&filename).unwrap();
+        assert_eq!(reader.spec().channels, 4);
+        assert_eq!(reader.len() as u64, 4 * num_samples);
+        std::fs::remove_file(&filename).unwrap();
+
+        assert!(parse_channels("3").is_err());
+        assert!(try_parse_cli(["procsynth", "--mono", "--channels", "4"]).is_err());
+    }
+
+    #[test]
+    fn test_quad_front_left_voice_stays_front() {
+        let mut voice = Voice {
+            static_pan: Some(-1.0),
+            static_depth: Some(-1.0),
+            ..voice()
+        };
+        let (mut front, mut rear) = (0.0, 0.0);
+        for _ in 0..4410 {
+            let [front_l, front_r, rear_l, rear_r] = voice.synthesize();
+            front += front_l * front_l + front_r * front_r;
+            rear += rear_l * rear_l + rear_r * rear_r;
+        }
+        assert!(front > 1.0);
+        assert!(rear < 1e-9);
+
+        // drifting quad voices reach the rear pair
+        let params = GeneratorParams {
+            output_channels: 4,
+            ..params()
+        };
+        let voices = Generator::generate_voices(&params, &mut rand::rng());
+        assert!(voices.iter().all(|v| v.static_depth.is_none()));
+    }
+
+    #[test]
+    fn test_zero_voices_renders_noise_bed() {
+        let params = GeneratorParams {
+            voices: 0,
+            duration: 0.5,
+            attack: 0.1,
+            release: 0.1,
+            grain_density: 0.0,
+            ..params()
+        };
+        let num_samples = params.num_samples() as usize;
+        let mut generator = Generator::new(params).unwrap();
+        assert!(generator.voices.is_empty());
+        generator.generate().unwrap();
+        generator.apply_reverb();
+
+        assert_eq!(generator.samples.len(), num_samples);
+        assert!(generator
+            .samples
+            .iter()
+            .all(|(l, r)| l.is_finite() && r.is_finite()));
+        let energy: f32 = generator.samples.iter().map(|(l, r)| l * l + r * r).sum();
+        assert!(energy > 0.0);
+    }
+
+    #[test]
+    fn test_info_reports_generated_wav() {
+        let filename = temp_path("info.wav");
+        let params = GeneratorParams {
+            filename: filename.clone(),
+            duration: 0.5,
+            attack: 0.1,
+            release: 0.1,
+            mono: true,
+            ..params()
+        };
+        let mut generator = Generator::new(params).unwrap();
+        generator.generate().unwrap();
+        generator.write_wav().unwrap();
+
+        let info = WavInfo::read(&filename).unwrap();
+        assert_eq!(info.channels, 1);
+        assert_eq!(info.sample_rate, 44100);
+        assert_eq!(info.duration, 0.5);
+        assert!(info.peak > 0.0 && info.peak <= 1.0);
+        assert!(info.rms > 0.0 && info.rms < info.peak);
+        std::fs::remove_file(&filename).unwrap();
+
+        let cli = parse_cli(["procsynth", "info", "drone.wav"]);
+        assert_eq!(
+            cli.command,
+            Some(Command::Info {
+                file: "drone.wav".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_normalize_quiet_file() {
+        let quiet = temp_path("quiet.wav");
+        let loud = temp_path("loud.wav");
+        let params = GeneratorParams {
+            filename: quiet.clone(),
+            duration: 0.5,
+            attack: 0.1,
+            release: 0.1,
+            gain_db: -20.0,
+            ..params()
+        };
+        let mut generator = Generator::new(params).unwrap();
+        generator.generate().unwrap();
+        generator.write_wav().unwrap();
+
+        let before = WavInfo::read(&quiet).unwrap();
+        assert!(info::dbfs(before.peak) < -15.0);
+        let gain =
+            normalize::normalize_file(&quiet, &loud, normalize::NormalizeMode::Peak, -1.0).unwrap();
+        assert!(gain > 1.0);
+        let after = WavInfo::read(&loud).unwrap();
+        assert!((info::dbfs(after.peak) - -1.0).abs() < 0.05);
+        assert_eq!(after.duration, before.duration);
+        assert_eq!(after.channels, before.channels);
+        std::fs::remove_file(&quiet).unwrap();
+        std::fs::remove_file(&loud).unwrap();
+
+        let cli = parse_cli(["procsynth", "normalize", "a.wav", "b.wav", "--target", "-3"]);
+        assert_eq!(
+            cli.command,
+            Some(Command::Normalize {
+                input: "a.wav".to_string(),
+                output: "b.wav".to_string(),
+                target: Some(-3.0),
+                normalize_mode: normalize::NormalizeMode::Peak,
+            })
+        );
+    }
+
+    #[test]
+    fn test_progress_callback() {
+        use std::sync::{Arc, Mutex};
+
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let params = GeneratorParams {
+            duration: 0.5,
+            ..params()
+        };
+        let mut generator = Generator::new(params).unwrap();
+        let sink = Arc::clone(&reports);
+        generator.set_progress_callback(move |fraction| sink.lock().unwrap().push(fraction));
+        generator.generate().unwrap();
+
+        let reports = reports.lock().unwrap();
+        assert!(!reports.is_empty());
+        assert!(*reports.last().unwrap() >= 0.99);
+        assert!(reports.windows(2).all(|w| w[0] <= w[1]));
+    }
+
+    #[test]
+    fn test_parallel_matches_serial() {
+        let render = |threads| {
+            let params = GeneratorParams {
+                duration: 0.5,
+                seed: Some(42),
+                threads,
+                ..params()
+            };
+            let mut generator = Generator::new(params).unwrap();
+            generator.generate().unwrap();
+            generator.samples
+        };
+
+        let serial = render(1);
+        assert_eq!(serial.len(), 22050);
+        assert_eq!(serial, render(4));
+        assert_eq!(serial, render(0));
+
+        // every noise layer, written out in full
+        let write = |threads| {
+            let filename = temp_path(&format!("threads-{threads}.wav"));
+            let params = GeneratorParams {
+                filename: filename.clone(),
+                duration: 0.5,
+                seed: Some(42),
+                threads,
+                output_channels: 4,
+                noise_level: 0.2,
+                wind_level: 0.3,
+                grain_density: 20.0,
+                ..params()
+            };
+            let mut generator = Generator::new(params).unwrap();
+            generator.generate().unwrap();
+            generator.write_wav().unwrap();
+            let bytes = std::fs::read(&filename).unwrap();
+            std::fs::remove_file(&filename).unwrap();
+            bytes
+        };
+        assert_eq!(write(1), write(3));
+    }
+
+    #[test]
+    fn test_loop_mode_is_seamless() {
+        let params = GeneratorParams {
+            duration: 1.0,
+            seed: Some(3),
+            base_freq: 110.0,
+            noise_level: 0.0,
+            loop_mode: true,
+            crossfade_seconds: 0.25,
+            ..params()
+        };
+        let mut generator = Generator::new(params).unwrap();
+        generator.generate().unwrap();
+        assert_eq!(generator.samples.len(), 44100 + 11025);
+        generator.apply_reverb();
+        generator.apply_loop_crossfade();
+
+        let samples = &generator.samples;
+        assert_eq!(samples.len(), 44100);
+        // No envelope: the first samples are already at full level
+        assert!(samples[..100].iter().any(|(l, _)| l.abs() > 0.01));
+
+        let max_step = samples
+            .windows(2)
+            .map(|w| (w[1].0 - w[0].0).abs().max((w[1].1 - w[0].1).abs()))
+            .fold(0.0f32, f32::max);
+        let (first, last) = (samples[0], samples[samples.len() - 1]);
+        assert!((first.0 - last.0).abs() <= max_step);
+        assert!((first.1 - last.1).abs() <= max_step);
+        assert!((first.0 - last.0).abs() < 0.05);
+        assert!((first.1 - last.1).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_chord_voices_match_chord_tones() {
+        let cli = parse_cli(["test", "--chord", "Cmaj"]);
+        let params: GeneratorParams = cli.into();
+        let voices = Generator::generate_voices(&params, &mut rand::rng());
+
+        let cents = |a: f32, b: f32| 1200.0 * (a / b).log2().abs();
+        let expected = [261.6256, 329.6276, 391.9954];
+        assert_eq!(voices.len(), 3);
+        for (voice, freq) in voices.iter().zip(expected) {
+            assert!(cents(voice.freq, freq) < 1.0);
+        }
+    }
+
+    #[test]
+    fn test_progression_plays_each_chord_in_turn() {
+        // two voices take the root and third of each chord, dropping the fifth
+        let progression = || GeneratorParams {
+            progression: Some("I,IV".to_string()),
+            scale: "C:major".parse().ok(),
+            chord_duration: 2.0,
+            voices: 2,
+            base_freq: 261.63,
+            duration: 4.0,
+            attack: 0.05,
+            release: 0.05,
+            noise_level: 0.0,
+            grain_density: 0.0,
+            seed: Some(4),
+            ..params()
+        };
+        let mut generator = Generator::new(progression()).unwrap();
+        assert_eq!(generator.voices().len(), 4);
+        generator.generate().unwrap();
+
+        let left: Vec<f32> = generator.samples.iter().map(|s| s.0).collect();
+        // clear of the second-long crossfades around 2 s and 4 s
+        let (first, second) = (&left[4410..52920], &left[114660..149940]);
+        let power = |samples, freqs: [f32; 2]| -> f32 {
+            freqs.iter().map(|&f| tone_power(samples, f, 44100.0)).sum()
+        };
+        let (c_major, f_major) = ([261.63, 329.63], [349.23, 440.0]);
+        assert!(power(first, c_major) > power(first, f_major) * 100.0);
+        assert!(power(second, f_major) > power(second, c_major) * 100.0);
+        assert!(power(first, c_major) > tone_power(first, 392.0, 44100.0) * 100.0);
+
+        let bad = GeneratorParams {
+            progression: Some("I,IX".to_string()),
+            ..progression()
+        };
+        assert!(matches!(
+            Generator::new(bad),
+            Err(Error::ProgressionError(_))
+        ));
+        assert!(try_parse_cli(["test", "--progression", "I,V"]).is_err());
+        let cli = parse_cli(["test", "--key", "A minor", "--progression", "i,iv"]);
+        assert_eq!(cli.scale.unwrap().root, PitchClass::A);
+    }
+
+    #[test]
+    fn test_voice_stagger_spreads_entries() {
+        let staggered = || GeneratorParams {
+            voices: 4,
+            voice_stagger: 3.0,
+            attack: 2.0,
+            release: 4.0,
+            duration: 20.0,
+            ..params()
+        };
+        let voices = Generator::generate_voices(&staggered(), &mut rand::rng());
+        // first sample at which each voice plays at full level
+        let full_at: Vec<usize> = voices
+            .iter()
+            .map(|voice| {
+                (0..20 * 100)
+                    .position(|i| voice.level(i as f64 / 100.0) >= 1.0)
+                    .unwrap()
+            })
+            .collect();
+        assert_eq!(full_at, [200, 300, 400, 500]);
+        assert_eq!(voices[3].level(1.0), 0.0);
+        assert!((voices[3].level(4.0) - 0.5).abs() < 1e-6);
+        // the last in is the first out
+        assert_eq!(voices[3].level(17.0), 0.0);
+        assert!((voices[0].level(18.0) - 0.5).abs() < 1e-6);
+
+        let looped = GeneratorParams {
+            loop_mode: true,
+            ..staggered()
+        };
+        let voices = Generator::generate_voices(&looped, &mut rand::rng());
+        assert!(voices.iter().all(|voice| voice.entry.is_none()));
+    }
+
+    #[test]
+    fn test_max_voices_caps_sounding_voices() {
+        // alternating loud and quiet voices, twice as many as the cap
+        let capped = |voice_steal| GeneratorParams {
+            voices: 8,
+            voice_gains: [1.0, 0.1].repeat(4),
+            max_voices: 4,
+            voice_steal,
+            ..params()
+        };
+        let voices = Generator::generate_voices(&capped(StealPolicy::Oldest), &mut rand::rng());
+        assert_eq!(voices.len(), 4);
+        assert_eq!(
+            voices.iter().map(|v| v.gain).collect::<Vec<_>>(),
+            [1.0, 0.1, 1.0, 0.1]
+        );
+        let voices = Generator::generate_voices(&capped(StealPolicy::Quietest), &mut rand::rng());
+        assert_eq!(voices.len(), 4);
+        // only the last note, which nothing came after to steal it, is quiet
+        assert_eq!(voices.iter().filter(|v| v.gain < 1.0).count(), 1);
+
+        // four-note chords overlap eight voices at each crossfade
+        let progression = |max_voices| GeneratorParams {
+            progression: Some("Imaj7,IV7,V7,vi7".to_string()),
+            scale: "C:major".parse().ok(),
+            chord_duration: 2.0,
+            voices: 4,
+            max_voices,
+            ..params()
+        };
+        let most_sounding = |max_voices| {
+            let voices = Generator::generate_voices(&progression(max_voices), &mut rand::rng());
+            (0..1600)
+                .map(|i| {
+                    let time = i as f64 / 100.0;
+                    voices.iter().filter(|v| v.level(time) > 0.0).count()
+                })
+                .max()
+                .unwrap()
+        };
+        assert_eq!(most_sounding(0), 8);
+        assert_eq!(most_sounding(6), 6);
+        assert_eq!(most_sounding(3), 3);
+    }
+
+    #[test]
+    fn test_wind_concentrates_around_center() {
+        let params = GeneratorParams {
+            voices: 0,
+            noise_level: 0.0,
+            grain_density: 0.0,
+            wind_level: 0.5,
+            wind_center: 1000.0,
+            wind_sweep: 0.0,
+            duration: 2.0,
+            attack: 0.01,
+            release: 0.01,
+            seed: Some(8),
+            ..params()
+        };
+        let mut generator = Generator::new(params).unwrap();
+        generator.generate().unwrap();
+        let left: Vec<f32> = generator.samples.iter().map(|s| s.0).collect();
+        let band = |freqs: &[f32]| -> f32 {
+            freqs
+                .iter()
+                .map(|&f| tone_power(&left, f, 44100.0))
+                .sum::<f32>()
+                / freqs.len() as f32
+        };
+        let center = band(&[950.0, 975.0, 1000.0, 1025.0, 1050.0]);
+        assert!(center > band(&[200.0, 225.0, 250.0, 275.0, 300.0]) * 10.0);
+        assert!(center > band(&[4800.0, 4900.0, 5000.0, 5100.0, 5200.0]) * 10.0);
+    }
+
+    #[test]
+    fn test_click_marks_every_beat() {
+        let params = GeneratorParams {
+            click: true,
+            tempo: 120,
+            time_sig: TimeSignature(4, 4),
+            voices: 0,
+            noise_level: 0.0,
+            grain_density: 0.0,
+            duration: 4.0,
+            ..params()
+        };
+        let mut generator = Generator::new(params).unwrap();
+        generator.generate().unwrap();
+        generator.apply_click();
+
+        // peak of each burst of sound, by where it starts
+        let mut clicks: Vec<(usize, f32)> = Vec::new();
+        let mut quiet = usize::MAX;
+        for (i, &(left, _)) in generator.samples.iter().enumerate() {
+            if left.abs() < 1e-3 {
+                quiet = quiet.saturating_add(1);
+                continue;
+            }
+            if quiet > 100 {
+                clicks.push((i, 0.0));
+            }
+            quiet = 0;
+            let peak = &mut clicks.last_mut().unwrap().1;
+            *peak = peak.max(left.abs());
+        }
+        assert_eq!(clicks.len(), 8);
+        // half a second apart, the first of each bar of 4 accented
+        for (n, &(start, peak)) in clicks.iter().enumerate() {
+            assert!(start.abs_diff(n * 22050) < 100, "{start}");
+            let accented = n % 4 == 0;
+            assert_eq!(peak > 0.4, accented, "{n}: {peak}");
+        }
+
+        assert_eq!(parse_time_signature("6/8"), Ok(TimeSignature(6, 8)));
+        assert!(parse_time_signature("4/3").is_err());
+        assert!(parse_time_signature("4").is_err());
+    }
+
+    #[test]
+    fn test_tempo_out_of_range_is_rejected() {
+        assert_eq!(parse_tempo("400"), Ok(400));
+        assert!(parse_tempo("19").is_err());
+        assert!(try_parse_cli(["procsynth", "--tempo", "0", "--click"]).is_err());
+        assert!(try_parse_cli(["procsynth", "--tempo", "401"]).is_err());
+
+        let result = Generator::new(GeneratorParams {
+            tempo: 0,
+            click: true,
+            ..params()
+        });
+        assert!(matches!(result, Err(Error::TempoOutOfRange(0))));
+    }
+
+    #[test]
+    fn test_value_noise_is_smooth_and_reproducible() {
+        let render = |seed| {
+            let params = GeneratorParams {
+                noise_type: NoiseType::Value,
+                noise_level: 0.5,
+                voices: 0,
+                grain_density: 0.0,
+                duration: 0.5,
+                seed: Some(seed),
+                ..params()
+            };
+            let generator = Generator::new(params).unwrap();
+            let mut out = vec![(0.0, 0.0); CHUNK_SIZE];
+            generator.render_chunk(2, false, Some(Layer::Noise), &mut out);
+            out.into_iter().map(|(left, _)| left).collect::<Vec<f32>>()
+        };
+        let noise = render(6);
+        assert_eq!(noise, render(6));
+        assert_ne!(noise, render(7));
+        assert!(noise.iter().any(|s| s.abs() > 0.05));
+        // at most a few percent of full scale from one sample to the next
+        for pair in noise.windows(2) {
+            assert!((pair[1] - pair[0]).abs() < 0.02, "{pair:?}");
+        }
+
+        // chunks meet seamlessly, since each sample depends only on its time
+        let seed = 6;
+        for sample in [0, 44_099, 1 << 30] {
+            let t = |s: usize| s as f64 / 44100.0;
+            let step = value_noise(seed, t(sample + 1)) - value_noise(seed, t(sample));
+            assert!(step.abs() < 0.05);
+        }
+    }
+
+    #[test]
+    fn test_parse_chord() {
+        let cli = parse_cli(["procsynth", "--chord", "F#m7"]);
+        let chord = cli.chord.unwrap();
+        assert_eq!(chord.root.pitch_cls, PitchClass::Fs);
+        assert_eq!(chord.kind, ChordKind::Minor7);
+        assert_eq!(
+            "Bb".parse::<Chord>().unwrap().root.pitch_cls,
+            PitchClass::As
+        );
+        assert_eq!("Cb".parse::<Chord>().unwrap().root.pitch_cls, PitchClass::B);
+        assert!(try_parse_cli(["procsynth", "--chord", "H"]).is_err());
+        assert!(try_parse_cli(["procsynth", "--chord", "Csus9"]).is_err());
+
+        // an octave above the default base frequency moves the chord up too
+        let freqs = Generator::chord_frequencies(&"A".parse().unwrap(), 880.0);
+        assert!((freqs[0] - 880.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_lfo_sync_uses_note_divisions() {
+        let params = GeneratorParams {
+            voices: 16,
+            lfo_sync: true,
+            ..params()
+        };
+        let voices = Generator::generate_voices(&params, &mut rand::rng());
+        assert!(voices
+            .iter()
+            .all(|v| [0.5, 1.0, 2.0, 4.0].contains(&v.lfo_rate)));
+        assert_eq!(Tempo(120).frequency(&Duration::Quarter), 2.0);
+    }
+
+    #[test]
+    fn test_master_gain_scales_peak() {
+        let peak = |gain_db| {
+            let params = GeneratorParams {
+                duration: 0.5,
+                seed: Some(7),
+                gain_db,
+                ..params()
+            };
+            let mut generator = Generator::new(params).unwrap();
+            generator.generate().unwrap();
+            generator
+                .samples
+                .iter()
+                .fold(0.0f32, |peak, (l, r)| peak.max(l.abs()).max(r.abs()))
+        };
+
+        assert!((db_to_gain(0.0) - 1.0).abs() < 1e-6);
+        let ratio = peak(-6.0) / peak(0.0);
+        assert!((ratio - 0.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_voice_gain_weights() {
+        let params = GeneratorParams {
+            voices: 3,
+            voice_gains: vec![0.5, 0.25],
+            ..params()
+        };
+        let voices = Generator::generate_voices(&params, &mut rand::rng());
+        let gains: Vec<f32> = voices.iter().map(|v| v.gain).collect();
+        assert_eq!(gains, [0.5, 0.25, 1.0]);
+
+        let mut full = Voice {
+            static_pan: Some(0.0),
+            ..voice()
+        };
+        let mut half = Voice {
+            gain: 0.5,
+            ..full.clone()
+        };
+        for _ in 0..100 {
+            let [fl, ..] = full.synthesize();
+            let [hl, ..] = half.synthesize();
+            assert!((hl - fl * 0.5).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_voice_synthesis() {
+        let mut voice = voice();
+
+        let [left, right, ..] = voice.synthesize();
+        // At t=0, sin(0) = 0, mod_env = 0.5, pan = 0, so l_gain = r_gain = 0.5
+        assert!((left - 0.0).abs() < 1e-6);
+        assert!((right - 0.0).abs() < 1e-6);
+
+        // Quarter period - should produce some non-zero output
+        voice.seek(44100 / (4 * 440));
+        let [left, right, ..] = voice.synthesize();
+
+        assert!(left.abs() > 0.0 || right.abs() > 0.0);
+    }
+
+    #[test]
+    fn test_phase_accumulator_stays_accurate() {
+        // Ten minutes in at 44.1 kHz
+        let n: u64 = 44100 * 600 + 12_345;
+        let mut voice = Voice {
+            freq: 330.0,
+            ..voice()
+        };
+        for _ in 0..n {
+            voice.advance();
+        }
+
+        // Exact reference: whole cycles drop out of 330 * n / 44100
+        let exact = TAU * ((330 * n) % 44100) as f64 / 44100.0;
+        let naive = (2.0 * PI * 330.0 * (n as f32 / 44100.0)).rem_euclid(2.0 * PI);
+
+        assert!((voice.phases.carrier - exact).abs() < 1e-6);
+        assert!((naive as f64 - exact).abs() > 1e-3);
+
+        voice.seek(n as usize);
+        assert!((voice.phases.carrier - exact).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_binaural_phase_offset() {
+        let mut voice = Voice {
+            binaural: 10.0,
+            ..voice()
+        };
+        // 25ms into a 10 Hz beat the ears are a quarter cycle apart
+        let n = 1102;
+        for _ in 0..n {
+            voice.synthesize();
+        }
+        let expected = TAU * 10.0 * n as f64 / 44100.0;
+        let diff = (voice.phases.carrier_right - voice.phases.carrier).rem_euclid(TAU);
+        assert!((diff - expected).abs() < 1e-6);
+
+        let mut seeked = voice.clone();
+        seeked.seek(n);
+        assert!((seeked.phases.carrier_right - voice.phases.carrier_right).abs() < 1e-6);
+
+        let params = GeneratorParams {
+            binaural: 10.0,
+            mono: true,
+            ..params()
+        };
+        let voices = Generator::generate_voices(&params, &mut rand::rng());
+        assert!(voices.iter().all(|v| v.binaural == 0.0));
+    }
+
+    #[test]
+    fn test_partials_add_harmonics() {
+        let params = GeneratorParams {
+            partials: vec![(1.0, 1.0), (2.0, 0.5)],
+            ..params()
+        };
+        let mut voice = Generator::generate_voices(&params, &mut rand::rng()).remove(0);
+        voice.freq = 440.0;
+        voice.static_pan = Some(0.0);
+        assert_eq!(voice.partials, [(1.0, 1.0 / 1.5), (2.0, 0.5 / 1.5)]);
+
+        let samples: Vec<f32> = (0..4410).map(|_| voice.synthesize()[0]).collect();
+        let fundamental = tone_power(&samples, 440.0, 44100.0);
+        let octave = tone_power(&samples, 880.0, 44100.0);
+        let between = tone_power(&samples, 660.0, 44100.0);
+        assert!(octave > between * 100.0);
+        // half the amplitude is a quarter of the power
+        assert!((octave / fundamental - 0.25).abs() < 0.05);
+
+        let mut seeked = voice.clone();
+        seeked.seek(4410);
+        for (a, b) in seeked.partial_phases.iter().zip(&voice.partial_phases) {
+            // 4410 samples is a whole number of cycles, so compare across the wrap
+            let diff = (a - b + std::f64::consts::PI).rem_euclid(TAU) - std::f64::consts::PI;
+            assert!(diff.abs() < 1e-6);
+        }
+
+        assert_eq!(parse_partial("2.76:0.4"), Ok((2.76, 0.4)));
+        assert!(parse_partial("3").is_err());
+    }
+
+    #[test]
+    fn test_oversample_removes_aliases() {
+        // odd harmonics of 5 kHz: the 7th and 9th fold back to 9.1 and 0.9 kHz
+        let render = |oversample| {
+            let params = GeneratorParams {
+                oversample,
+                voices: 1,
+                base_freq: 5000.0,
+                detune_range: "1.0:1.0".to_string(),
+                partials: (1..10)
+                    .step_by(2)
+                    .map(|n| (n as f32, 1.0 / n as f32))
+                    .collect(),
+                duration: 0.5,
+                noise_level: 0.0,
+                grain_density: 0.0,
+                seed: Some(3),
+                ..params()
+            };
+            let mut generator = Generator::new(params).unwrap();
+            generator.generate().unwrap();
+            let left: Vec<f32> = generator.samples.iter().map(|s| s.0).collect();
+            left[4410..17640].to_vec()
+        };
+        let (plain, oversampled) = (render(1), render(4));
+        for alias in [9100.0, 900.0] {
+            let before = tone_power(&plain, alias, 44100.0);
+            let after = tone_power(&oversampled, alias, 44100.0);
+            assert!(after * 1000.0 < before, "{alias}: {before} -> {after}");
+        }
+        let ratio = tone_power(&oversampled, 5000.0, 44100.0) / tone_power(&plain, 5000.0, 44100.0);
+        assert!((ratio - 1.0).abs() < 0.05, "{ratio}");
+
+        assert_eq!(parse_cli(["test", "--oversample", "4"]).oversample, 4);
+        assert!(try_parse_cli(["test", "--oversample", "3"]).is_err());
+    }
+
+    #[test]
+    fn test_fm_zero_index_matches_plain_sine() {
+        let mut plain = voice();
+        let mut fm = Voice {
+            modulator: Some(Modulator {
+                freq: 110.0,
+                index: 0.0,
+            }),
+            ..voice()
+        };
+
+        for _ in 0..5000 {
+            assert_eq!(plain.synthesize(), fm.synthesize());
+        }
+    }
+
+    #[test]
+    fn test_fm_index_broadens_spectrum() {
+        // 1 Hz bins: carrier at 440 Hz, modulator at 110 Hz, sidebands at 330/550 Hz
+        let sample_rate = 4096.0;
+        let render = |index| {
+            let mut voice = Voice {
+                lfo_rate: 0.0,
+                static_pan: Some(0.0),
+                modulator: Some(Modulator { freq: 110.0, index }),
+                sample_rate,
+                ..voice()
+            };
+            (0..4096)
+                .map(|_| voice.synthesize()[0])
+                .collect::<Vec<f32>>()
+        };
+
+        let sideband_ratio = |samples: &[f32]| {
+            let sidebands =
+                tone_power(samples, 330.0, sample_rate) + tone_power(samples, 550.0, sample_rate);
+            sidebands / tone_power(samples, 440.0, sample_rate)
+        };
+
+        assert!(sideband_ratio(&render(0.0)) < 1e-4);
+        assert!(sideband_ratio(&render(1.0)) > 0.1);
+    }
+
+    #[test]
+    fn test_fm_seek_matches_accumulated_phase() {
+        let mut voice = Voice {
+            modulator: Some(Modulator {
+                freq: 3.0,
+                index: 2.0,
+            }),
+            ..voice()
+        };
+        for _ in 0..4096 {
+            voice.synthesize();
+        }
+        let accumulated = voice.phases;
+        voice.seek(4096);
+        assert!((accumulated.carrier - voice.phases.carrier).abs() < 1e-3);
+        assert!((accumulated.modulator - voice.phases.modulator).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_glide_seek_matches_accumulated_phase() {
+        let scale: Scale = "A minor".parse().unwrap();
+        let targets = Generator::glide_targets(&scale, 440.0);
+        // D4 up to E5, a fifth either side
+        assert_eq!(targets.len(), 9);
+        assert!((targets[0] - 293.66).abs() < 0.01 && (targets[8] - 659.26).abs() < 0.01);
+        let mut voice = Voice {
+            glide: Some(Arc::new(GlidePath::new(
+                440.0,
+                &targets,
+                12.0,
+                2.0,
+                &mut StdRng::seed_from_u64(7),
+            ))),
+            ..voice()
+        };
+        for _ in 0..44100 {
+            voice.synthesize();
+        }
+        let accumulated = voice.phases.carrier;
+        voice.seek(44100);
+        let drift = (accumulated - voice.phases.carrier).abs();
+        assert!(drift.min(TAU - drift) < 1e-3);
+    }
+
+    #[test]
+    fn test_noise_ducks_under_loud_voices() {
+        let mut generator = generator_for_envelope();
+        generator.params.noise_level = 0.5;
+        // the swell peaks at 1 s and bottoms out at 3 s
+        generator.voices = vec![Voice {
+            lfo_rate: 0.25,
+            mod_depth: 1.0,
+            ..voice()
+        }];
+        let noise_power = |generator: &Generator, time: usize| {
+            let mut rng = StdRng::seed_from_u64(9);
+            (0..1000)
+                .map(|_| generator.noise(time * 44100, &mut rng).powi(2))
+                .sum::<f32>()
+        };
+        let (loud, quiet) = (noise_power(&generator, 1), noise_power(&generator, 3));
+        assert_eq!(loud, quiet);
+
+        generator.params.noise_duck = 0.8;
+        let (loud, quiet) = (noise_power(&generator, 1), noise_power(&generator, 3));
+        assert!(loud < quiet * 0.1);
+        assert!((generator.noise_duck(1.0) - 0.2).abs() < 1e-5);
+        assert!((generator.noise_duck(3.0) - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_voice_info_lists_every_voice() {
+        let mut verbose = params();
+        verbose.voices = 5;
+        verbose.seed = Some(11);
+        let generator = Generator::new(verbose).unwrap();
+        let info = generator.voice_info();
+        assert_eq!(info.len(), 5);
+        for (i, voice) in info.iter().enumerate() {
+            assert_eq!(voice.index, i);
+            assert!(voice.cents.abs() <= 50.0);
+            assert!(
+                (voice.note.frequency() * 2f32.powf(voice.cents / 1200.0) - voice.freq).abs()
+                    < 0.01
+            );
+            assert!(voice.to_string().starts_with(&format!("voice  {i}: ")));
+        }
+    }
+
+    #[test]
+    fn test_fm_pairing() {
+        let pairs = GeneratorParams {
+            voices: 4,
+            fm_index: 1.5,
+            ..params()
+        };
+        let voices = Generator::generate_voices(&pairs, &mut rand::rng());
+        let modulated: Vec<bool> = voices.iter().map(|v| v.modulator.is_some()).collect();
+        assert_eq!(modulated, [false, true, false, true]);
+        assert_eq!(voices[1].modulator.unwrap().freq, voices[0].freq);
+
+        let adjacent = GeneratorParams {
+            fm_pairing: FmPairing::Adjacent,
+            ..pairs
+        };
+        let voices = Generator::generate_voices(&adjacent, &mut rand::rng());
+        let modulated: Vec<bool> = voices.iter().map(|v| v.modulator.is_some()).collect();
+        assert_eq!(modulated, [false, true, true, true]);
+        assert_eq!(voices[3].modulator.unwrap().freq, voices[2].freq);
+
+        // the old name still parses, on the command line and in configs
+        let cli = parse_cli(["procsynth", "--fm-pairing", "chain"]);
+        assert_eq!(cli.fm_pairing, FmPairing::Adjacent);
+        let config: JsonConfig = serde_json::from_str(r#"{"fm_pairing": "chain"}"#).unwrap();
+        assert_eq!(config.fm_pairing, FmPairing::Adjacent);
+    }
+
+    #[test]
+    fn test_fast_sine_voice_tracks_exact_voice() {
+        let mut exact = voice();
+        let mut fast = Voice {
+            wavetable: Some(wavetable::sine_table()),
+            ..voice()
+        };
+
+        for _ in 0..1000 {
+            let [el, er, ..] = exact.synthesize();
+            let [fl, fr, ..] = fast.synthesize();
+            assert!((el - fl).abs() < 1e-4);
+            assert!((er - fr).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_voice_zero_pan_spread_is_centered() {
+        let mut voice = Voice {
+            pan_spread: 0.0,
+            ..voice()
+        };
+
+        for i in 1..100 {
+            voice.seek(i * 16_317);
+            let [left, right, ..] = voice.synthesize();
+            assert_eq!(left, right);
+        }
+    }
+
+    #[test]
+    fn test_static_pan_even_spacing() {
+        let params = GeneratorParams {
+            voices: 5,
+            static_pan: true,
+            ..params()
+        };
+        let mut voices = Generator::generate_voices(&params, &mut rand::rng());
+        let positions: Vec<f32> = voices.iter().map(|v| v.pan()).collect();
+        let expected = [-1.0, -0.5, 0.0, 0.5, 1.0];
+        for (pos, exp) in positions.iter().zip(expected) {
+            assert!((pos - exp).abs() < 1e-6);
+        }
+
+        // Positions don't move over time
+        for voice in &mut voices {
+            let start = voice.pan();
+            voice.seek(12 * 44100);
+            assert_eq!(start, voice.pan());
+        }
+
+        assert_eq!(Generator::static_pan_position(0, 1), 0.0);
+    }
+
+    #[test]
+    fn test_generator_envelope_attack_phase() {
+        let generator = generator_for_envelope();
+        assert_eq!(generator.envelope(0.0), 0.0);
+        assert_eq!(generator.envelope(2.5), 0.5);
+        assert_eq!(generator.envelope(5.0), 1.0);
+    }
+
+    #[test]
+    fn test_generator_envelope_sustain_phase() {
+        let generator = generator_for_envelope();
+        assert_eq!(generator.envelope(10.0), 1.0);
+    }
+
+    #[test]
+    fn test_generator_envelope_release_phase() {
+        let generator = generator_for_envelope();
+
+        // duration=20, release=10, so release starts at t=10
+        assert_eq!(generator.envelope(10.0), 1.0);
+        assert_eq!(generator.envelope(15.0), 0.5);
+        assert_eq!(generator.envelope(20.0), 0.0);
+    }
+
+    #[test]
+    fn test_generator_envelope_beyond_duration() {
+        let generator = generator_for_envelope();
+        assert_eq!(generator.envelope(25.0), 0.0);
+    }
+
+    #[test]
+    fn test_envelope_scales_the_signal() {
+        let params = GeneratorParams {
+            voices: 2,
+            duration: 2.0,
+            attack: 0.5,
+            release: 0.5,
+            noise_level: 0.0,
+            grain_density: 0.0,
+            seed: Some(3),
+            ..params()
+        };
+        let mut generator = Generator::new(params).unwrap();
+        generator.generate().unwrap();
+        let samples = &generator.samples;
+        // silent where the envelope is zero, rather than offset by it
+        assert_eq!(samples[0], (0.0, 0.0));
+        let peak = |range: std::ops::Range<usize>| {
+            samples[range]
+                .iter()
+                .map(|s| s.0.abs().max(s.1.abs()))
+                .fold(0.0, f32::max)
+        };
+        assert!(peak(0..441) < peak(44100..45000) * 0.05);
+        // an added envelope would leave a DC offset following its shape
+        let mean = samples.iter().map(|s| s.0 + s.1).sum::<f32>() / (2 * samples.len()) as f32;
+        assert!(mean.abs() < 0.01, "{mean}");
+    }
 
-    format!("{}_{}.{}", v4_uuid(), prefix, extension)
-}
+    #[test]
+    fn test_envelope_fade_curves() {
+        let mut generator = generator_for_envelope();
+        generator.params.attack = 10.0;
 
-pub fn run() -> Result<(), Error> {
-    let params = CLI::parse().to_params()?;
-    Generator::new(params).and_then(|mut r| r.run())?;
-    Ok(())
-}
+        generator.params.fade_curve = FadeCurve::Exponential;
+        assert!(generator.envelope(5.0) < 0.5);
+        assert_eq!(generator.envelope(10.0), 1.0);
+        // release mirrors the attack
+        assert!(generator.envelope(15.0) < 0.5);
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use clap::Parser;
-    fn cli() -> CLI {
-        CLI {
-            config: None,
-            output: Some("test.wav".to_string()),
-            duration: 30.0,
-            sample_rate: 48000,
-            voices: 6,
-            base_freq: 440.0,
-            lfo_rate_range: "0.1:0.3".to_string(),
-            noise_level: 0.01,
-            mod_depth_range: "0.3:0.8".to_string(),
-            attack: 3.0,
-            release: 5.0,
-            reverb_mix: 0.4,
-        }
+        generator.params.fade_curve = FadeCurve::Logarithmic;
+        assert!(generator.envelope(5.0) > 0.5);
+        assert!(generator.envelope(15.0) > 0.5);
+        assert_eq!(generator.envelope(0.0), 0.0);
     }
 
-    fn params() -> GeneratorParams {
-        GeneratorParams {
-            filename: "test.wav".to_string(),
-            sample_rate: 44100,
-            duration: 20.0,
-            lfo_range: "0.05:0.2".to_string(),
-            mod_depth_range: "0.5:1.0".to_string(),
-            voices: 4,
-            base_freq: 330.0,
-            noise_level: 0.005,
-            attack: 5.0,
-            release: 10.0,
-            reverb_mix: 0.3,
-        }
+    #[test]
+    fn test_fades_independent_of_envelope() {
+        let mut generator = generator_for_envelope();
+        generator.params.attack = 0.0;
+        generator.params.fade_in_ms = 10.0;
+        generator.params.fade_out_ms = 10.0;
+        generator.samples = vec![(0.5, -0.5); 44100];
+        generator.apply_fades();
+
+        let fade = 441;
+        assert_eq!(generator.samples[0], (0.0, 0.0));
+        assert!(generator.samples[fade / 2].0 > 0.2 && generator.samples[fade / 2].0 < 0.3);
+        assert_eq!(generator.samples[fade], (0.5, -0.5));
+        assert_eq!(generator.samples[44100 - fade - 1], (0.5, -0.5));
+        assert_eq!(generator.samples[44099], (0.0, 0.0));
+
+        generator.params.loop_mode = true;
+        generator.samples = vec![(0.5, -0.5); 44100];
+        generator.apply_fades();
+        assert_eq!(generator.samples[0], (0.5, -0.5));
     }
 
-    fn generator_for_envelope() -> Generator {
-        let params = params();
+    #[test]
+    fn test_haas_delays_right_channel() {
+        let mut generator = generator_for_envelope();
+        generator.params.haas_ms = 10.0;
+        let dry: Vec<(f32, f32)> = (0..4410)
+            .map(|i| {
+                let x = (2.0 * PI * 220.0 * i as f32 / 44100.0).sin();
+                (x, x)
+            })
+            .collect();
+        generator.samples = dry.clone();
+        generator.apply_haas();
 
-        Generator {
-            params,
-            voices: vec![],
-            num_samples: 0,
-            samples: vec![],
-            filter_prev_l: 0.0,
-            filter_prev_r: 0.0,
+        assert_eq!(generator.samples.len(), dry.len());
+        for (i, &(left, right)) in generator.samples.iter().enumerate() {
+            assert_eq!(left, dry[i].0);
+            let expected = if i < 441 { 0.0 } else { dry[i - 441].0 };
+            assert_eq!(right, expected);
         }
     }
 
     #[test]
-    fn test_output_type_as_str() {
-        assert_eq!(OutputType::MIDI.as_str(), "midi");
-        assert_eq!(OutputType::WAV.as_str(), "wav");
+    fn test_reverb_tail_rings_out() {
+        let mut generator = generator_for_envelope();
+        generator.params.reverb_mix = 0.5;
+        generator.params.reverb_tail = 1.0;
+        let dry: Vec<(f32, f32)> = (0..22050)
+            .map(|i| {
+                let x = (2.0 * PI * 220.0 * i as f32 / 44100.0).sin() * 0.5;
+                (x, x)
+            })
+            .collect();
+        generator.samples = dry.clone();
+        generator.extend_tail();
+        generator.apply_reverb();
+        assert_eq!(generator.samples.len(), dry.len() + 44100);
+
+        let energy = |range: std::ops::Range<usize>| {
+            generator.samples[range]
+                .iter()
+                .map(|(l, r)| l * l + r * r)
+                .sum::<f32>()
+        };
+        let early = energy(22050..33075);
+        let late = energy(55125..66150);
+        assert!(early > 0.0);
+        assert!(late < early * 0.1);
     }
 
     #[test]
-    fn test_generate_filename_helper() {
-        let filename = generate_filename("test ambient", OutputType::WAV);
-        assert!(filename.contains("test_ambient"));
-        assert!(filename.ends_with(".wav"));
-        assert!(filename.len() > 20); // UUID + prefix + extension
+    fn test_render_range_is_an_excerpt() {
+        let piece = |render_range| {
+            let mut generator = Generator::new(GeneratorParams {
+                sample_rate: 8000,
+                duration: 60.0,
+                noise_level: 0.0,
+                seed: Some(11),
+                render_range,
+                ..params()
+            })
+            .unwrap();
+            generator.generate().unwrap();
+            generator
+        };
+        let full = piece(None);
+        let excerpt = piece(Some((10.0, 20.0)));
+
+        assert_eq!(excerpt.samples.len(), 80000);
+        assert_eq!(excerpt.gain_at(10.0), full.gain_at(10.0));
+        for (i, (cut, whole)) in excerpt
+            .samples
+            .iter()
+            .zip(&full.samples[80000..])
+            .enumerate()
+        {
+            assert!((cut.0 - whole.0).abs() < 1e-3, "sample {i}");
+            assert!((cut.1 - whole.1).abs() < 1e-3, "sample {i}");
+        }
+
+        let past_the_end = GeneratorParams {
+            render_range: Some((90.0, 100.0)),
+            ..params()
+        };
+        assert!(Generator::new(past_the_end).is_err());
+        assert!(parse_render_range("20:10").is_err());
+        assert!(try_parse_cli(["procsynth", "--loop", "--render-range", "0:5"]).is_err());
     }
 
     #[test]
-    fn test_cli_default_values() {
-        let cli = CLI::parse_from(&["procsynth"]);
-        assert_eq!(cli.duration, 60.0);
-        assert_eq!(cli.sample_rate, 44100);
-        assert_eq!(cli.voices, 4);
-        assert_eq!(cli.base_freq, 330.0);
-        assert_eq!(cli.lfo_rate_range, "0.05:0.2");
-        assert_eq!(cli.noise_level, 0.005);
-        assert_eq!(cli.mod_depth_range, "0.5:1.0");
-        assert_eq!(cli.attack, 5.0);
-        assert_eq!(cli.release, 10.0);
-        assert_eq!(cli.reverb_mix, 0.3);
-        assert!(cli.output.is_none());
+    fn test_excerpt_reverb_follows_the_full_clock() {
+        let excerpt = |automation: &str, reverb_fade| {
+            let mut generator = Generator::new(GeneratorParams {
+                sample_rate: 8000,
+                duration: 60.0,
+                noise_level: 0.0,
+                seed: Some(11),
+                render_range: Some((10.0, 12.0)),
+                automation: vec![parse_automation(automation).unwrap()],
+                reverb_fade,
+                ..params()
+            })
+            .unwrap();
+            generator.generate().unwrap();
+            let dry = generator.samples.clone();
+            generator.apply_reverb();
+            (dry, generator.samples)
+        };
+
+        // fully wet until 5 s, dry after, so the excerpt stays dry
+        let (dry, mixed) = excerpt("reverb_mix:1@0,1@5,0@5.01", 0.0);
+        assert_eq!(dry, mixed);
+
+        // a fade over the first 5 s is long over by 10 s, so a fully wet mix
+        // starts out silent while the first echo is still on its way
+        let (dry, mixed) = excerpt("reverb_mix:1@0", 5.0);
+        assert!(dry[0].0.abs() > 0.0 || dry[0].1.abs() > 0.0);
+        assert_eq!(mixed[0], (0.0, 0.0));
     }
 
     #[test]
-    fn test_cli_custom_values() {
-        let cli = CLI::parse_from(&[
+    fn test_preview_keeps_the_full_envelope() {
+        let cli = parse_cli([
             "procsynth",
-            "--output",
-            "test.wav",
             "--duration",
-            "30.0",
+            "120",
             "--sample-rate",
-            "48000",
-            "--voices",
-            "8",
-            "--base-freq",
-            "440.0",
-            "--lfo-rate-range",
-            "0.1:0.3",
-            "--noise-level",
-            "0.01",
-            "--mod-depth-range",
-            "0.3:0.8",
-            "--attack",
-            "3.0",
-            "--release",
-            "5.0",
-            "--reverb-mix",
-            "0.5",
+            "8000",
+            "--preview",
+            "5",
         ]);
+        let mut generator = Generator::new(cli.into()).unwrap();
+        generator.generate().unwrap();
+        assert_eq!(generator.samples.len(), 40000);
 
-        assert_eq!(cli.output, Some("test.wav".to_string()));
-        assert_eq!(cli.duration, 30.0);
-        assert_eq!(cli.sample_rate, 48000);
-        assert_eq!(cli.voices, 8);
-        assert_eq!(cli.base_freq, 440.0);
-        assert_eq!(cli.lfo_rate_range, "0.1:0.3");
-        assert_eq!(cli.noise_level, 0.01);
-        assert_eq!(cli.mod_depth_range, "0.3:0.8");
-        assert_eq!(cli.attack, 3.0);
-        assert_eq!(cli.release, 5.0);
-        assert_eq!(cli.reverb_mix, 0.5);
+        // the 5 s attack ramps over the whole preview, with no release at its end
+        assert_eq!(generator.gain_at(0.0), 0.0);
+        assert_eq!(generator.gain_at(2.5), 0.5);
+        assert_eq!(generator.gain_at(5.0), 1.0);
+        let level = |range: std::ops::Range<usize>| {
+            generator.samples[range]
+                .iter()
+                .map(|(l, r)| l * l + r * r)
+                .sum::<f32>()
+        };
+        assert!(level(0..4000) < level(36000..40000));
+
+        assert!(try_parse_cli(["procsynth", "--preview", "0"]).is_err());
+        assert!(try_parse_cli(["procsynth", "--preview", "5", "--render-range", "0:5"]).is_err());
     }
 
     #[test]
-    fn test_generator_params_conversion() {
-        let cli = cli();
-        let params = cli.to_params().unwrap();
+    fn test_reverb_fade_opens_up() {
+        let dry: Vec<(f32, f32)> = (0..22050)
+            .map(|i| {
+                let x = (2.0 * PI * 220.0 * i as f32 / 44100.0).sin() * 0.5;
+                (x, -x)
+            })
+            .collect();
+        let render = |fade: f32| {
+            let mut generator = generator_for_envelope();
+            generator.params.reverb_mix = 0.5;
+            generator.params.reverb_fade = fade;
+            generator.samples = dry.clone();
+            generator.apply_reverb();
+            generator.samples
+        };
+        let full = render(0.0);
+        let faded = render(0.25);
 
-        assert_eq!(params.filename, "test.wav");
-        assert_eq!(params.duration, 30.0);
-        assert_eq!(params.sample_rate, 48000);
-        assert_eq!(params.voices, 6);
-        assert_eq!(params.base_freq, 440.0);
-        assert_eq!(params.lfo_range, "0.1:0.3");
-        assert_eq!(params.noise_level, 0.01);
-        assert_eq!(params.mod_depth_range, "0.3:0.8");
-        assert_eq!(params.attack, 3.0);
-        assert_eq!(params.release, 5.0);
-        assert_eq!(params.reverb_mix, 0.4);
+        // the 50 ms delay line is still empty at the start, so compare the
+        // dry gain too: halved by the full mix, barely touched by the fade
+        assert_eq!(faded[0], dry[0]);
+        assert!((faded[100].0 - dry[100].0).abs() < dry[100].0.abs() * 0.01);
+        assert!((full[100].0 - dry[100].0 * 0.5).abs() < 1e-6);
+        assert_ne!(faded[5000], full[5000]);
+        assert_eq!(faded[11025..], full[11025..]);
     }
 
     #[test]
-    fn test_generator_params_num_samples() {
-        assert_eq!(params().num_samples(), 882000); // 44100 * 20.0
+    fn test_reverb_hpf_keeps_bass_dry() {
+        let sr = 44100.0;
+        let dry: Vec<(f32, f32)> = (0..44100)
+            .map(|i| {
+                let x = (2.0 * PI * 50.0 * i as f32 / sr).sin() * 0.5;
+                (x, x)
+            })
+            .collect();
+        let wet_power = |hpf: f32| {
+            let mut generator = generator_for_envelope();
+            generator.params.reverb_mix = 1.0;
+            generator.params.reverb_hpf = hpf;
+            generator.samples = dry.clone();
+            generator.apply_reverb();
+            let left: Vec<f32> = generator.samples.iter().map(|s| s.0).collect();
+            tone_power(&left, 50.0, sr)
+        };
+
+        let dry_left: Vec<f32> = dry.iter().map(|s| s.0).collect();
+        let dry_power = tone_power(&dry_left, 50.0, sr);
+        let full = wet_power(0.0);
+        let filtered = wet_power(500.0);
+        assert!(filtered < dry_power * 0.1);
+        assert!(filtered < full * 0.1);
     }
 
     #[test]
-    fn test_generator_parse_range() {
-        assert_eq!(
-            Generator::parse_range(&"0.1:0.3".to_string(), 0.0, 1.0),
-            (0.1, 0.3)
-        );
-        assert_eq!(
-            Generator::parse_range(&"0.5".to_string(), 0.0, 1.0),
-            (0.5, 1.0)
-        );
-        assert_eq!(
-            Generator::parse_range(&"invalid".to_string(), 0.2, 0.8),
-            (0.2, 0.8)
-        );
-        assert_eq!(
-            Generator::parse_range(&"".to_string(), 0.2, 0.8),
-            (0.2, 0.8)
-        );
+    fn test_reverb_ir_convolves_the_send() {
+        let write_ir = |name: &str, samples: &[f32]| {
+            let path = temp_path(name);
+            let spec = WavSpec {
+                channels: 1,
+                sample_rate: 44100,
+                bits_per_sample: 32,
+                sample_format: hound::SampleFormat::Float,
+            };
+            let mut writer = WavWriter::create(&path, spec).unwrap();
+            for &sample in samples {
+                writer.write_sample(sample).unwrap();
+            }
+            writer.finalize().unwrap();
+            path
+        };
+        let dry: Vec<(f32, f32)> = (0..22050)
+            .map(|i| {
+                let x = (2.0 * PI * 220.0 * i as f32 / 44100.0).sin() * 0.5;
+                (x, -x)
+            })
+            .collect();
+        let wet = |ir: &str| {
+            let mut generator = Generator::new(GeneratorParams {
+                reverb_ir: Some(ir.to_string()),
+                reverb_mix: 1.0,
+                reverb_tail: 1.0,
+                ..params()
+            })
+            .unwrap();
+            generator.samples = dry.clone();
+            generator.extend_tail();
+            generator.apply_reverb();
+            generator.samples
+        };
+
+        // a unit impulse, even a quiet one, hands back the dry signal
+        let unit = write_ir("unit-ir.wav", &[0.25]);
+        let samples = wet(&unit);
+        for (a, b) in dry.iter().zip(&samples) {
+            assert!((a.0 - b.0).abs() < 1e-5 && (a.1 - b.1).abs() < 1e-5);
+        }
+        assert!(samples[dry.len()..].iter().all(|s| s.0.abs() < 1e-5));
+
+        let decay: Vec<f32> = (0..22050)
+            .map(|i| (-(i as f32) / 4410.0).exp() * (i as f32 * 0.7).sin())
+            .collect();
+        let hall = write_ir("hall-ir.wav", &decay);
+        let samples = wet(&hall);
+        let energy = |range: std::ops::Range<usize>| {
+            samples[range]
+                .iter()
+                .map(|(l, r)| l * l + r * r)
+                .sum::<f32>()
+        };
+        let (early, late) = (energy(22050..33075), energy(55125..66150));
+        assert!(early > 0.0);
+        assert!(late < early * 0.1);
+
+        std::fs::remove_file(unit).unwrap();
+        std::fs::remove_file(hall).unwrap();
+        assert!(matches!(
+            Generator::new(GeneratorParams {
+                reverb_ir: Some(temp_path("missing-ir.wav")),
+                ..params()
+            }),
+            Err(Error::HoundError(_))
+        ));
     }
 
     #[test]
-    fn test_generator_spec() {
-        let spec = Generator::spec(44100);
-        assert_eq!(spec.channels, 2);
-        assert_eq!(spec.sample_rate, 44100);
-        assert_eq!(spec.bits_per_sample, 16);
-        assert_eq!(spec.sample_format, hound::SampleFormat::Int);
+    fn test_chorus() {
+        let dry: Vec<(f32, f32)> = (0..44100)
+            .map(|i| {
+                let x = (2.0 * PI * 440.0 * i as f32 / 44100.0).sin() * 0.5;
+                (x, x)
+            })
+            .collect();
+        let mut generator = generator_for_envelope();
+        generator.samples = dry.clone();
+        generator.apply_chorus();
+        assert_eq!(generator.samples, dry);
+
+        generator.params.chorus_depth = 0.8;
+        generator.apply_chorus();
+        assert_ne!(generator.samples, dry);
+        // the swept channels decorrelate and nothing blows up
+        assert!(generator.samples.iter().any(|(l, r)| (l - r).abs() > 0.01));
+        assert!(generator
+            .samples
+            .iter()
+            .all(|(l, r)| l.abs() <= 0.5 && r.abs() <= 0.5));
     }
 
     #[test]
-    fn test_voice_synthesis() {
-        let voice = Voice {
-            freq: 440.0,
-            lfo_rate: 0.1,
-            mod_depth: 0.5,
-            pan_rate: 0.02,
+    fn test_autopan_moves_balance() {
+        // balance (R - L) over one-second windows of a centered constant signal
+        let balances = |depth: f32| {
+            let mut generator = generator_for_envelope();
+            generator.params.autopan_rate = 0.25;
+            generator.params.autopan_depth = depth;
+            generator.samples = vec![(0.5, 0.5); 44100 * 4];
+            generator.apply_autopan();
+            generator
+                .samples
+                .chunks(44100)
+                .map(|w| w.iter().map(|(l, r)| r - l).sum::<f32>() / w.len() as f32)
+                .collect::<Vec<f32>>()
         };
 
-        let (left, right) = voice.synthesize(0.0);
-        // At t=0, sin(0) = 0, mod_env = 0.5, pan = 0, so l_gain = r_gain = 0.5
-        assert!((left - 0.0).abs() < 1e-6);
-        assert!((right - 0.0).abs() < 1e-6);
+        assert!(balances(0.0).iter().all(|&b| b == 0.0));
+        let moving = balances(0.5);
+        // a quarter-hertz sweep leans right for two seconds, then left
+        assert!(moving[0] > 0.1 && moving[1] > 0.1);
+        assert!(moving[2] < -0.1 && moving[3] < -0.1);
+    }
 
-        // Quarter period - should produce some non-zero output
-        let (left, right) = voice.synthesize(1.0 / (4.0 * 440.0));
+    #[test]
+    fn test_automation_breakpoints() {
+        let automation = parse_automation("reverb_mix:0.1@0,0.6@120").unwrap();
+        assert_eq!(automation.target, AutomationTarget::ReverbMix);
+        assert_eq!(automation.value_at(0.0), 0.1);
+        assert!((automation.value_at(60.0) - 0.35).abs() < 1e-6);
+        assert_eq!(automation.value_at(120.0), 0.6);
+        assert_eq!(automation.value_at(500.0), 0.6);
+        assert!(parse_automation("tempo:1@0").is_err());
+        assert!(parse_automation("noise_level:0.1").is_err());
 
-        assert!(left.abs() > 0.0 || right.abs() > 0.0);
+        let cli = parse_cli([
+            "procsynth",
+            "--automate",
+            "noise-level:0.0@0,0.02@10",
+            "--automate",
+            "reverb_mix:0.1@0,0.6@120",
+        ]);
+        let automated = GeneratorParams::from(cli);
+        assert_eq!(automated.automated(AutomationTarget::NoiseLevel, 0.0), 0.0);
+        assert_eq!(
+            automated.automated(AutomationTarget::NoiseLevel, 10.0),
+            0.02
+        );
+        assert_eq!(automated.automated(AutomationTarget::ReverbMix, 120.0), 0.6);
+
+        // without a curve the fixed parameter applies
+        assert_eq!(params().automated(AutomationTarget::ReverbMix, 5.0), 0.3);
     }
 
     #[test]
-    fn test_generator_envelope_attack_phase() {
-        let generator = generator_for_envelope();
-        assert_eq!(generator.envelope(0.0), 0.0);
-        assert_eq!(generator.envelope(2.5), 0.5);
-        assert_eq!(generator.envelope(5.0), 1.0);
+    fn test_voices_accessor() {
+        let generator = Generator::new(GeneratorParams {
+            voices: 6,
+            detune_range: "0.9:1.1".to_string(),
+            ..params()
+        })
+        .unwrap();
+        let voices = generator.voices();
+        assert_eq!(voices.len(), 6);
+        for voice in voices {
+            assert!((330.0 * 0.9..=330.0 * 1.1).contains(&voice.freq()));
+            assert!((0.05..0.2).contains(&voice.lfo_rate()));
+            assert!((0.5..1.0).contains(&voice.mod_depth()));
+            assert!((0.01..0.05).contains(&voice.pan_rate()));
+        }
     }
 
     #[test]
-    fn test_generator_envelope_sustain_phase() {
-        let generator = generator_for_envelope();
-        assert_eq!(generator.envelope(10.0), 1.0);
+    fn test_detune_distribution() {
+        let unison = GeneratorParams {
+            voices: 8,
+            detune_range: "1.0:1.0".to_string(),
+            ..params()
+        };
+        let voices = Generator::generate_voices(&unison, &mut rand::rng());
+        assert!(voices.iter().all(|v| v.freq == 330.0));
+
+        let cluster = GeneratorParams {
+            voices: 1000,
+            detune_range: "0.9:1.1".to_string(),
+            detune_dist: DetuneDistribution::Gaussian,
+            ..params()
+        };
+        let voices = Generator::generate_voices(&cluster, &mut rand::rng());
+        let ratios: Vec<f32> = voices.iter().map(|v| v.freq / 330.0).collect();
+        let inside = ratios.iter().filter(|r| (0.9..=1.1).contains(*r)).count();
+        assert!(inside > 900);
+        // clustered around the middle: a uniform spread would put about half within ±0.05
+        let central = ratios.iter().filter(|r| (0.95..=1.05).contains(*r)).count();
+        assert!(central > 600);
     }
 
     #[test]
-    fn test_generator_envelope_release_phase() {
-        let generator = generator_for_envelope();
+    fn test_detune_cents() {
+        let (min, max) = Generator::cents_range(1200.0);
+        assert!((min - 0.5).abs() < 1e-6 && (max - 2.0).abs() < 1e-6);
+        assert_eq!(Generator::cents_range(-1200.0), (min, max));
 
-        // duration=20, release=10, so release starts at t=10
-        assert_eq!(generator.envelope(10.0), 1.0);
-        assert_eq!(generator.envelope(15.0), 0.5);
-        assert_eq!(generator.envelope(20.0), 0.0);
+        // the cents take the place of the ratio range
+        let unison = GeneratorParams {
+            voices: 100,
+            detune_range: "0.5:2.0".to_string(),
+            detune_cents: Some(7.0),
+            ..params()
+        };
+        let voices = Generator::generate_voices(&unison, &mut rand::rng());
+        let cents: Vec<f32> = voices
+            .iter()
+            .map(|v| 1200.0 * (v.freq / 330.0).log2())
+            .collect();
+        assert!(cents.iter().all(|c| c.abs() <= 7.0 + 1e-3));
+        // spread across the range rather than collapsed onto the base
+        assert!(cents.iter().any(|&c| c < -3.5) && cents.iter().any(|&c| c > 3.5));
     }
 
     #[test]
-    fn test_generator_envelope_beyond_duration() {
-        let generator = generator_for_envelope();
-        assert_eq!(generator.envelope(25.0), 0.0);
+    fn test_saturation_curve() {
+        assert_eq!(Generator::saturate(0.8, 0.0), 0.8);
+
+        for drive in [0.5, 1.0, 3.0, 10.0] {
+            let full = Generator::saturate(1.0, drive);
+            assert!(full <= 1.0 && Generator::saturate(-1.0, drive) >= -1.0);
+            // hotter input is squashed, never amplified
+            assert!(Generator::saturate(1.5, drive) < 1.5);
+            assert!(Generator::saturate(1.5, drive) >= full);
+
+            // tiny signals stay proportional to the input
+            let tiny = Generator::saturate(1e-4, drive);
+            let double = Generator::saturate(2e-4, drive);
+            assert!((double / tiny - 2.0).abs() < 1e-3);
+        }
     }
 
     #[test]
-    fn test_generator_burst_env_oscillation() {
-        assert!((Generator::burst_env(0.0) - 0.5).abs() < 1e-6);
-        let mut values = vec![];
-        for i in 0..100 {
-            let t = i as f32 * 0.01;
-            values.push(Generator::burst_env(t));
+    fn test_filtered_noise_is_stereo() {
+        let mut generator = generator_for_envelope();
+        generator.params.noise_level = 0.5;
+        let mut rng = StdRng::seed_from_u64(5);
+
+        let mut outputs = Vec::new();
+        for i in 0..64 {
+            outputs.push(generator.filtered_noise(i as f32, &mut rng));
         }
+        assert!(outputs.iter().skip(8).all(|(l, r)| l != r));
+        assert_ne!(generator.filter_prev_l, generator.filter_prev_r);
+    }
+
+    #[test]
+    fn test_noise_cutoff_coefficient() {
+        // the default cutoff matches the old fixed coefficient at 44.1 kHz
+        let a = one_pole_coefficient(739.5, 44100);
+        assert!((a - 0.1).abs() < 1e-4);
+
+        // doubling the rate halves the per-sample angle: 1 - sqrt(0.9)
+        let a = one_pole_coefficient(739.5, 88200);
+        assert!((a - (1.0 - 0.9f32.sqrt())).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_stereo_width() {
+        let mut generator = generator_for_envelope();
+        let input = vec![(0.5, -0.25), (0.1, 0.3), (-0.7, 0.2)];
 
-        let min_val = values.iter().fold(f32::INFINITY, |a, &b| a.min(b));
-        let max_val = values.iter().fold(f32::NEG_INFINITY, |a, &b| a.max(b));
+        generator.samples = input.clone();
+        generator.apply_width();
+        assert_eq!(generator.samples, input);
 
-        assert!(min_val >= 0.0);
-        assert!(max_val <= 1.0);
+        generator.params.width = 0.0;
+        generator.apply_width();
+        assert!(generator.samples.iter().all(|(l, r)| l == r));
+        assert_eq!(generator.samples[0], (0.125, 0.125));
+
+        generator.params.width = 2.0;
+        generator.samples = input;
+        generator.apply_width();
+        assert!((generator.samples[0].0 - 0.875).abs() < 1e-6);
+        assert!((generator.samples[0].1 + 0.625).abs() < 1e-6);
     }
 
     #[test]
@@ -858,6 +5969,33 @@ mod tests {
         assert!(generator.samples.is_empty());
     }
 
+    #[test]
+    fn test_duration_limits() {
+        // 50000 s at 96 kHz is more samples than fit in a u32
+        let huge = GeneratorParams {
+            duration: 50_000.0,
+            sample_rate: 96_000,
+            max_duration: f32::MAX,
+            ..params()
+        };
+        assert_eq!(huge.num_samples(), 4_800_000_000);
+        assert!(matches!(
+            Generator::new(huge),
+            Err(Error::TooManySamples(4_800_000_000))
+        ));
+
+        let absurd = GeneratorParams {
+            duration: 1_000_000.0,
+            ..params()
+        };
+        let err = Generator::new(absurd).err().unwrap();
+        assert!(matches!(err, Error::DurationTooLong { max, .. } if max == 3600.0));
+        assert_eq!(
+            err.to_string(),
+            "Duration Error: 1000000s is longer than --max-duration 3600s"
+        );
+    }
+
     #[test]
     fn test_error_wraps_hound_error() {
         let io_err = std::io::Error::new(std::io::ErrorKind::NotFound, "file not found");
@@ -868,6 +6006,96 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_json_config_rejects_invalid_values() {
+        let rejects = |config: JsonConfig, field: &str| {
+            let err = config.to_params().err().unwrap();
+            assert!(
+                err.to_string().starts_with(&format!("Invalid {field}:")),
+                "{err}"
+            );
+        };
+        rejects(
+            JsonConfig {
+                automation: vec!["reverb_mix:0.1@0".to_string(), "volume:1@0".to_string()],
+                ..JsonConfig::default()
+            },
+            "automation",
+        );
+        rejects(
+            JsonConfig {
+                scale: Some("C lydianish".to_string()),
+                ..JsonConfig::default()
+            },
+            "scale",
+        );
+        rejects(
+            JsonConfig {
+                resonators: vec!["C4".to_string(), "E#9x".to_string()],
+                ..JsonConfig::default()
+            },
+            "resonators",
+        );
+        rejects(
+            JsonConfig {
+                time_sig: "5/3".to_string(),
+                ..JsonConfig::default()
+            },
+            "time_sig",
+        );
+        for oversample in [0, 3, 1000] {
+            rejects(
+                JsonConfig {
+                    oversample,
+                    ..JsonConfig::default()
+                },
+                "oversample",
+            );
+        }
+        for bit_depth in [0, 8, 32] {
+            rejects(
+                JsonConfig {
+                    bit_depth,
+                    ..JsonConfig::default()
+                },
+                "bit_depth",
+            );
+        }
+        rejects(
+            JsonConfig {
+                glide_rate: 2.0,
+                ..JsonConfig::default()
+            },
+            "glide_rate",
+        );
+        for tempo in [0, 19, 401] {
+            rejects(
+                JsonConfig {
+                    tempo,
+                    ..JsonConfig::default()
+                },
+                "tempo",
+            );
+        }
+        for output_channels in [0, 1, 3] {
+            rejects(
+                JsonConfig {
+                    output_channels,
+                    ..JsonConfig::default()
+                },
+                "output_channels",
+            );
+        }
+        rejects(
+            JsonConfig {
+                mono: true,
+                output_channels: 4,
+                ..JsonConfig::default()
+            },
+            "output_channels",
+        );
+    }
+
     #[test]
     fn test_json_config_conversion() {
         let config = JsonConfig {
@@ -882,11 +6110,81 @@ mod tests {
             attack: 3.0,
             release: 5.0,
             reverb_mix: 0.4,
+            pan_spread: 1.0,
+            static_pan: false,
+            mono: false,
+            seed: None,
+            threads: 0,
+            fast_sine: false,
+            fm_index: 0.0,
+            fm_pairing: FmPairing::Pairs,
+            gain_db: 0.0,
+            voice_gains: Vec::new(),
+            loop_mode: false,
+            crossfade_seconds: 2.0,
+            grain_density: 8.0,
+            grain_duration: 0.08,
+            grain_pitch_jitter: 7.0,
+            chord: None,
+            binaural: 0.0,
+            tempo: 120,
+            lfo_sync: false,
+            format: None,
+            ogg_quality: 0.5,
+            bit_depth: 16,
+            partials: Vec::new(),
+            width: 1.0,
+            saturation: 0.0,
+            noise_cutoff_hz: 739.5,
+            fade_curve: FadeCurve::Linear,
+            reverb_hpf: 0.0,
+            chorus_depth: 0.0,
+            chorus_rate: 0.3,
+            autopan_depth: 0.0,
+            autopan_rate: 0.05,
+            output_channels: 2,
+            automation: Vec::new(),
+            max_duration: 3600.0,
+            detune_range: [0.8, 1.2],
+            detune_dist: DetuneDistribution::Uniform,
+            fade_in_ms: 0.0,
+            fade_out_ms: 0.0,
+            reverb_tail: 0.0,
+            scale: None,
+            glide_rate: 0.0,
+            noise_duck: 0.0,
+            haas_ms: 0.0,
+            spectrogram: None,
+            spectrogram_window: 2048,
+            spectrogram_hop: 512,
+            dither: false,
+            noise_shaping: false,
+            reverb_fade: 0.0,
+            resonators: Vec::new(),
+            resonator_decay: 2.0,
+            progression: None,
+            chord_duration: 4.0,
+            stems: None,
+            oversample: 1,
+            voice_stagger: 0.0,
+            wind_level: 0.0,
+            wind_center: 800.0,
+            wind_sweep: 1.0,
+            click: false,
+            time_sig: "6/8".to_string(),
+            noise_type: NoiseType::White,
+            reverb_ir: None,
+            max_voices: 0,
+            voice_steal: StealPolicy::Oldest,
+            detune_cents: None,
         };
 
-        let params = config.to_params();
+        let params = config.to_params().unwrap();
 
-        assert_eq!(params.filename, "test_json.wav");
+        // the config's output name gets a UUID prefix
+        let (prefix, name) = params.filename.split_once('_').unwrap();
+        assert!(uuid::Uuid::parse_str(prefix).is_ok());
+        assert_eq!(name, "test_json.wav");
         assert_eq!(params.duration, 45.0);
         assert_eq!(params.sample_rate, 48000);
         assert_eq!(params.voices, 8);
@@ -894,6 +6192,7 @@ mod tests {
         assert_eq!(params.lfo_range, "0.1:0.3");
         assert_eq!(params.noise_level, 0.02);
         assert_eq!(params.mod_depth_range, "0.3:0.8");
+        assert_eq!(params.time_sig, TimeSignature(6, 8));
         assert_eq!(params.attack, 3.0);
         assert_eq!(params.release, 5.0);
         assert_eq!(params.reverb_mix, 0.4);
@@ -914,4 +6213,14 @@ mod tests {
         assert_eq!(default_config.reverb_mix, 0.3);
         assert!(default_config.output.is_none());
     }
+
+    #[test]
+    fn test_partial_json_config() {
+        let config: JsonConfig = serde_json::from_str(r#"{"voices": 8}"#).unwrap();
+        assert_eq!(config.voices, 8);
+        let defaults = serde_json::to_value(JsonConfig::default()).unwrap();
+        let mut loaded = serde_json::to_value(config).unwrap();
+        loaded["voices"] = defaults["voices"].clone();
+        assert_eq!(loaded, defaults);
+    }
 }
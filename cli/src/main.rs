@@ -1,3 +1,6 @@
 fn main() {
-    println!("Hello, world!");
+    if let Err(err) = procsynth_cli::run() {
+        eprintln!("error: {err}");
+        std::process::exit(1);
+    }
 }
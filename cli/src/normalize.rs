@@ -0,0 +1,194 @@
+//! `procsynth normalize`: rescales an existing WAV file to a target level.
+//!
+//! The whole file is read, a single gain is worked out from its peak or its
+//! RMS level, and the result is written back out in the input's own format.
+//! RMS tracks perceived loudness far better than the peak, so it's the one
+//! to use when matching the level of several pieces, which
+//! [`match_loudness`] does for a whole batch at once.
+
+use crate::info::{dbfs, read_samples};
+use crate::{db_to_gain, Generator};
+use clap::ValueEnum;
+use hound::{SampleFormat, WavWriter};
+use std::path::Path;
+
+/// Largest gain normalization will apply (+40 dB), so near-silent files
+/// aren't blown up into amplified noise
+pub const MAX_GAIN: f32 = 100.0;
+
+/// Level measurement normalization aims at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum NormalizeMode {
+    /// Largest absolute sample
+    #[default]
+    Peak,
+    /// Root mean square over the whole file; peaks may clip if the target is high
+    Rms,
+}
+
+impl NormalizeMode {
+    /// Target level in dBFS used when none is given
+    pub fn default_target(self) -> f32 {
+        match self {
+            NormalizeMode::Peak => -1.0,
+            NormalizeMode::Rms => -18.0,
+        }
+    }
+
+    /// Level of `samples` as a linear amplitude
+    pub(crate) fn level(self, samples: &[f32]) -> f32 {
+        match self {
+            NormalizeMode::Peak => samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs())),
+            NormalizeMode::Rms => {
+                let power = samples.iter().map(|s| (s * s) as f64).sum::<f64>();
+                (power / samples.len().max(1) as f64).sqrt() as f32
+            }
+        }
+    }
+}
+
+/// Gain that brings `samples` to `target_db` dBFS as measured by `mode`,
+/// capped at [`MAX_GAIN`]; silence is left alone
+pub fn normalize_gain(samples: &[f32], mode: NormalizeMode, target_db: f32) -> f32 {
+    let level = mode.level(samples);
+    if level == 0.0 {
+        return 1.0;
+    }
+    (db_to_gain(target_db) / level).min(MAX_GAIN)
+}
+
+/// Highest peak, in dBFS, [`match_loudness`] lets a file reach when it picks
+/// the common level itself
+pub const MATCH_CEILING_DB: f32 = -1.0;
+
+/// Brings every file in `files` to one common RMS level, rewriting them in
+/// place, and returns that level in dBFS.
+///
+/// The level is `target_db` when given. Otherwise it's the files' average
+/// RMS level, lowered as far as needed to keep every file's peak at or below
+/// [`MATCH_CEILING_DB`], so the set plays back evenly without any of it
+/// clipping. Silent files are left alone.
+pub fn match_loudness(
+    files: &[impl AsRef<Path>],
+    target_db: Option<f32>,
+) -> Result<f32, hound::Error> {
+    // (RMS, peak) of each file, in dBFS; a whole album needn't fit in memory
+    let mut levels = Vec::with_capacity(files.len());
+    for file in files {
+        let (_, samples) = read_samples(file)?;
+        let rms = NormalizeMode::Rms.level(&samples);
+        if rms > 0.0 {
+            levels.push((dbfs(rms), dbfs(NormalizeMode::Peak.level(&samples))));
+        }
+    }
+    let target = target_db.unwrap_or_else(|| {
+        let average = levels.iter().map(|(rms, _)| rms).sum::<f32>() / levels.len().max(1) as f32;
+        levels
+            .iter()
+            .map(|(rms, peak)| MATCH_CEILING_DB - (peak - rms))
+            .fold(average, f32::min)
+    });
+    for file in files {
+        normalize_file(file, file, NormalizeMode::Rms, target)?;
+    }
+    Ok(target)
+}
+
+/// Normalizes `input` to `target_db` dBFS as measured by `mode` and writes
+/// it to `output`, returning the gain applied
+pub fn normalize_file(
+    input: impl AsRef<Path>,
+    output: impl AsRef<Path>,
+    mode: NormalizeMode,
+    target_db: f32,
+) -> Result<f32, hound::Error> {
+    let (spec, samples) = read_samples(input)?;
+    let gain = normalize_gain(&samples, mode, target_db);
+
+    let mut writer = WavWriter::create(output, spec)?;
+    for sample in samples {
+        match spec.sample_format {
+            SampleFormat::Float => writer.write_sample((sample * gain).clamp(-1.0, 1.0))?,
+            SampleFormat::Int => {
+                writer.write_sample(Generator::quantize(sample * gain, spec.bits_per_sample))?
+            }
+        }
+    }
+    writer.finalize()?;
+    Ok(gain)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_peak_gain() {
+        let peak_gain =
+            |samples: &[f32], target| normalize_gain(samples, NormalizeMode::Peak, target);
+        assert_eq!(peak_gain(&[0.25, -0.5, 0.1], 0.0), 2.0);
+        assert!((peak_gain(&[0.5], -6.0206) - 1.0).abs() < 1e-4);
+        assert_eq!(peak_gain(&[0.0; 16], -1.0), 1.0);
+    }
+
+    #[test]
+    fn test_rms_normalization_matches_loudness() {
+        // a sine and a sparse click train: very different peaks, equal RMS
+        let sine: Vec<f32> = (0..48000).map(|i| (i as f32 * 0.05).sin() * 0.1).collect();
+        let rms = NormalizeMode::Rms.level(&sine);
+        let clicks: Vec<f32> = (0..48000)
+            .map(|i| if i % 100 == 0 { rms * 10.0 } else { 0.0 })
+            .collect();
+        assert!(NormalizeMode::Peak.level(&clicks) > NormalizeMode::Peak.level(&sine) * 5.0);
+
+        let level_after = |samples: &[f32]| {
+            let gain = normalize_gain(samples, NormalizeMode::Rms, -20.0);
+            let scaled: Vec<f32> = samples.iter().map(|s| s * gain).collect();
+            NormalizeMode::Rms.level(&scaled)
+        };
+        let (a, b) = (level_after(&sine), level_after(&clicks));
+        assert!((a - b).abs() / a < 0.01);
+        assert!((a - db_to_gain(-20.0)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_normalizes_32_bit_integer_files() {
+        let id = uuid::Uuid::new_v4();
+        let input = std::env::temp_dir().join(format!("{id}_normalize_32_in.wav"));
+        let output = std::env::temp_dir().join(format!("{id}_normalize_32_out.wav"));
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 44100,
+            bits_per_sample: 32,
+            sample_format: SampleFormat::Int,
+        };
+        let mut writer = WavWriter::create(&input, spec).unwrap();
+        for i in 0..1000 {
+            writer
+                .write_sample(((i as f32 * 0.05).sin() * (i32::MAX / 4) as f32) as i32)
+                .unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let gain = normalize_file(&input, &output, NormalizeMode::Peak, 0.0).unwrap();
+        assert!((gain - 4.0).abs() < 1e-3);
+        let (spec, samples) = read_samples(&output).unwrap();
+        assert_eq!(spec.bits_per_sample, 32);
+        assert!((NormalizeMode::Peak.level(&samples) - 1.0).abs() < 1e-3);
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_gain_is_capped() {
+        let near_silent = [1e-7, -1e-7, 1e-7];
+        assert_eq!(
+            normalize_gain(&near_silent, NormalizeMode::Rms, -18.0),
+            MAX_GAIN
+        );
+        assert_eq!(
+            normalize_gain(&near_silent, NormalizeMode::Peak, -1.0),
+            MAX_GAIN
+        );
+    }
+}
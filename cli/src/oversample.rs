@@ -0,0 +1,80 @@
+//! Decimation for `--oversample`.
+//!
+//! Voices render at a multiple of the output rate so partials and FM
+//! sidebands past the output's Nyquist frequency are still generated
+//! correctly, rather than folding back down as aliases. A windowed-sinc
+//! low-pass then removes everything the output can't hold before every
+//! `factor`-th sample is kept.
+
+use std::f32::consts::PI;
+
+/// Zero crossings of the sinc kernel on each side, in output samples. The
+/// transition band narrows as this grows; 32 puts it at about 0.46-0.54 of
+/// the output rate, so what little does fold back lands above 20 kHz at
+/// 44.1 kHz.
+const HALF_WIDTH: usize = 32;
+
+/// Low-pass FIR filter over an oversampled stereo signal
+#[derive(Debug, Clone)]
+pub struct Decimator {
+    taps: Vec<f32>,
+    /// The last `taps.len()` samples, oldest first from `write`
+    history: Vec<(f32, f32)>,
+    write: usize,
+}
+
+impl Decimator {
+    pub fn new(factor: usize) -> Decimator {
+        let factor = factor.max(1);
+        let half = HALF_WIDTH * factor;
+        let len = 2 * half + 1;
+        let cutoff = 0.5 / factor as f32;
+        let mut taps: Vec<f32> = (0..len)
+            .map(|i| {
+                let n = i as f32 - half as f32;
+                let sinc = if n == 0.0 {
+                    2.0 * cutoff
+                } else {
+                    (2.0 * PI * cutoff * n).sin() / (PI * n)
+                };
+                // Blackman window
+                let x = 2.0 * PI * i as f32 / (len - 1) as f32;
+                sinc * (0.42 - 0.5 * x.cos() + 0.08 * (2.0 * x).cos())
+            })
+            .collect();
+        // unity gain at DC
+        let sum: f32 = taps.iter().sum();
+        for tap in &mut taps {
+            *tap /= sum;
+        }
+        Decimator {
+            history: vec![(0.0, 0.0); len],
+            taps,
+            write: 0,
+        }
+    }
+
+    /// How many oversampled samples the output lags the input by, half the
+    /// filter's length
+    pub fn delay(&self) -> usize {
+        self.taps.len() / 2
+    }
+
+    /// Adds the next oversampled sample
+    pub fn push(&mut self, sample: (f32, f32)) {
+        self.history[self.write] = sample;
+        self.write = (self.write + 1) % self.history.len();
+    }
+
+    /// The filtered signal [`Self::delay`] samples before the newest one
+    pub fn output(&self) -> (f32, f32) {
+        let len = self.history.len();
+        self.taps
+            .iter()
+            .enumerate()
+            .fold((0.0, 0.0), |(left, right), (i, tap)| {
+                let (l, r) = self.history[(self.write + i) % len];
+                (left + l * tap, right + r * tap)
+            })
+    }
+}
@@ -0,0 +1,112 @@
+//! Real-time playback of a rendered buffer for `--play`.
+//!
+//! The buffer is streamed to the default output device in whatever block
+//! sizes the device asks for. Nothing is resampled: if the device can't run
+//! at the render's sample rate, playback fails with an error listing the
+//! rates it does support, so the render can be repeated with `--sample-rate`.
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+use std::time::Duration;
+
+/// How often the calling thread checks whether playback has finished
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Stream configuration for `channels` interleaved channels at `sample_rate`
+pub fn stream_config(channels: u16, sample_rate: u32) -> cpal::StreamConfig {
+    cpal::StreamConfig {
+        channels,
+        sample_rate: cpal::SampleRate(sample_rate),
+        buffer_size: cpal::BufferSize::Default,
+    }
+}
+
+/// Finds an f32 output configuration on `device` matching the render
+fn matching_config(
+    device: &cpal::Device,
+    channels: u16,
+    sample_rate: u32,
+) -> Result<cpal::StreamConfig, String> {
+    let ranges: Vec<_> = device
+        .supported_output_configs()
+        .map_err(|err| err.to_string())?
+        .filter(|range| range.channels() == channels)
+        .filter(|range| range.sample_format() == cpal::SampleFormat::F32)
+        .collect();
+    let supported = ranges
+        .iter()
+        .any(|r| (r.min_sample_rate().0..=r.max_sample_rate().0).contains(&sample_rate));
+    if supported {
+        return Ok(stream_config(channels, sample_rate));
+    }
+
+    let rates: Vec<String> = ranges
+        .iter()
+        .map(|r| format!("{}-{} Hz", r.min_sample_rate().0, r.max_sample_rate().0))
+        .collect();
+    Err(format!(
+        "output device can't play {channels} channel(s) at {sample_rate} Hz (supported: {}); \
+         re-render with a matching --sample-rate",
+        if rates.is_empty() {
+            "none".to_string()
+        } else {
+            rates.join(", ")
+        }
+    ))
+}
+
+/// Plays interleaved `samples` on the default output device and blocks
+/// until they have all been handed to the device.
+pub fn play(samples: Vec<f32>, channels: u16, sample_rate: u32) -> Result<(), String> {
+    let device = cpal::default_host()
+        .default_output_device()
+        .ok_or("no default audio output device")?;
+    let config = matching_config(&device, channels, sample_rate)?;
+
+    let samples = Arc::new(samples);
+    let position = Arc::new(AtomicUsize::new(0));
+    let (source, cursor) = (Arc::clone(&samples), Arc::clone(&position));
+    let stream = device
+        .build_output_stream(
+            &config,
+            move |out: &mut [f32], _| {
+                let start = cursor.load(Ordering::Relaxed).min(source.len());
+                let end = (start + out.len()).min(source.len());
+                let (played, silence) = out.split_at_mut(end - start);
+                played.copy_from_slice(&source[start..end]);
+                silence.fill(0.0);
+                cursor.store(end, Ordering::Relaxed);
+            },
+            |err| eprintln!("playback error: {err}"),
+            None,
+        )
+        .map_err(|err| err.to_string())?;
+    stream.play().map_err(|err| err.to_string())?;
+
+    while position.load(Ordering::Relaxed) < samples.len() {
+        std::thread::sleep(POLL_INTERVAL);
+    }
+    // let the device drain its last buffer before the stream is dropped
+    std::thread::sleep(POLL_INTERVAL * 4);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stream_config_matches_render() {
+        let config = stream_config(2, 48000);
+        assert_eq!(config.channels, 2);
+        assert_eq!(config.sample_rate, cpal::SampleRate(48000));
+        assert_eq!(config.buffer_size, cpal::BufferSize::Default);
+
+        let mono = stream_config(1, 44100);
+        assert_eq!(mono.channels, 1);
+        assert_eq!(mono.sample_rate.0, 44100);
+    }
+}
@@ -0,0 +1,101 @@
+//! Voice allocation under `--max-voices`.
+//!
+//! Voices are requested one note at a time and the pool keeps no more than
+//! the cap sounding together. Voices in a `--progression` only sound during
+//! their chord's turn and the crossfades either side, so a note only
+//! competes with the voices of its own chord and its neighbours. When those
+//! already fill the cap, one of them is stolen for the new note: the oldest
+//! request, or the voice with the lowest average level.
+
+use crate::{ChordSlot, StealPolicy, Voice};
+
+/// Voices allocated so far, with the order they were requested in
+#[derive(Debug)]
+pub struct VoicePool {
+    /// Most voices sounding at once; 0 for no limit
+    max: usize,
+    policy: StealPolicy,
+    voices: Vec<Voice>,
+    /// Request number of each voice, for [`StealPolicy::Oldest`]
+    ages: Vec<usize>,
+    requested: usize,
+}
+
+impl VoicePool {
+    pub fn new(max: usize, policy: StealPolicy) -> VoicePool {
+        VoicePool {
+            max,
+            policy,
+            voices: Vec::new(),
+            ages: Vec::new(),
+            requested: 0,
+        }
+    }
+
+    /// Adds a voice for a new note, stealing as many as it takes to keep
+    /// every group that can sound with it under the cap
+    pub fn push(&mut self, voice: Voice) {
+        if self.max > 0 {
+            while let Some(group) = self
+                .groups(&voice)
+                .into_iter()
+                .find(|group| group.len() >= self.max)
+            {
+                let victim = self.victim(&group);
+                self.voices.remove(victim);
+                self.ages.remove(victim);
+            }
+        }
+        self.voices.push(voice);
+        self.ages.push(self.requested);
+        self.requested += 1;
+    }
+
+    /// Number of notes requested that lost their voice
+    pub fn stolen(&self) -> usize {
+        self.requested - self.voices.len()
+    }
+
+    pub fn into_voices(self) -> Vec<Voice> {
+        self.voices
+    }
+
+    /// Indices of the voices that sound at the same time as `voice`, one
+    /// list per moment that could fill up: the crossfades into and out of
+    /// its chord, or the whole piece for voices that always sound
+    fn groups(&self, voice: &Voice) -> Vec<Vec<usize>> {
+        let Some(slot) = voice.chord_slot else {
+            return vec![(0..self.voices.len()).collect()];
+        };
+        let with = |chords: &[usize]| -> Vec<usize> {
+            (0..self.voices.len())
+                .filter(|&i| {
+                    self.voices[i]
+                        .chord_slot
+                        .is_none_or(|other| chords.contains(&other.index))
+                })
+                .collect()
+        };
+        if slot.count < 2 || slot.crossfade <= 0.0 {
+            return vec![with(&[slot.index])];
+        }
+        let ChordSlot { index, count, .. } = slot;
+        vec![
+            with(&[(index + count - 1) % count, index]),
+            with(&[index, (index + 1) % count]),
+        ]
+    }
+
+    /// Index of the voice in `group` to give up for a new note
+    fn victim(&self, group: &[usize]) -> usize {
+        let loudness = |i: usize| self.voices[i].gain * self.voices[i].mod_depth;
+        match self.policy {
+            StealPolicy::Oldest => group.iter().copied().min_by_key(|&i| self.ages[i]),
+            StealPolicy::Quietest => group
+                .iter()
+                .copied()
+                .min_by(|&a, &b| loudness(a).total_cmp(&loudness(b))),
+        }
+        .expect("a full group is never empty")
+    }
+}
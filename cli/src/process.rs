@@ -0,0 +1,166 @@
+//! `procsynth process`: channel fixes on an existing WAV file.
+//!
+//! Samples are copied in the file's own format, integer or float, so
+//! swapping channels is lossless and inverting one only ever touches the
+//! single most negative integer value, which has no positive counterpart.
+
+use hound::{SampleFormat, WavReader, WavSpec, WavWriter};
+use std::path::Path;
+
+/// Channel operations applied by [`process_file`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChannelOps {
+    /// Exchange left and right
+    pub swap_channels: bool,
+    /// Flip the polarity of the right channel, after any swap
+    pub invert_right: bool,
+}
+
+impl ChannelOps {
+    /// Applies the operations to one frame, a left/right pair at a time, so
+    /// quad files get both their front and rear pairs changed
+    fn apply<T: Copy>(self, frame: &mut [T], invert: impl Fn(T) -> T) {
+        for pair in frame.chunks_exact_mut(2) {
+            if self.swap_channels {
+                pair.swap(0, 1);
+            }
+            if self.invert_right {
+                pair[1] = invert(pair[1]);
+            }
+        }
+    }
+}
+
+/// Copies `input` to `output` with `ops` applied, returning the format
+/// written. Mono files have no pair to work on and are copied unchanged.
+pub fn process_file(
+    input: impl AsRef<Path>,
+    output: impl AsRef<Path>,
+    ops: ChannelOps,
+) -> Result<WavSpec, hound::Error> {
+    let mut reader = WavReader::open(input)?;
+    let spec = reader.spec();
+    let channels = spec.channels.max(1) as usize;
+    let mut writer = WavWriter::create(output, spec)?;
+    match spec.sample_format {
+        SampleFormat::Float => {
+            let mut samples: Vec<f32> = reader.samples::<f32>().collect::<Result<_, _>>()?;
+            for frame in samples.chunks_exact_mut(channels) {
+                ops.apply(frame, |s: f32| -s);
+            }
+            for sample in samples {
+                writer.write_sample(sample)?;
+            }
+        }
+        SampleFormat::Int => {
+            // in i64 so 32-bit files don't overflow
+            let max = ((1i64 << (spec.bits_per_sample - 1)) - 1) as i32;
+            let mut samples: Vec<i32> = reader.samples::<i32>().collect::<Result<_, _>>()?;
+            for frame in samples.chunks_exact_mut(channels) {
+                ops.apply(frame, |s: i32| s.saturating_neg().min(max));
+            }
+            for sample in samples {
+                writer.write_sample(sample)?;
+            }
+        }
+    }
+    writer.finalize()?;
+    Ok(spec)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_stereo(path: &Path, frames: &[(i16, i16)]) {
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate: 44100,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+        let mut writer = WavWriter::create(path, spec).unwrap();
+        for &(left, right) in frames {
+            writer.write_sample(left).unwrap();
+            writer.write_sample(right).unwrap();
+        }
+        writer.finalize().unwrap();
+    }
+
+    fn read_stereo(path: &Path) -> Vec<(i16, i16)> {
+        let mut reader = WavReader::open(path).unwrap();
+        let samples: Vec<i16> = reader.samples::<i16>().map(Result::unwrap).collect();
+        samples.chunks_exact(2).map(|f| (f[0], f[1])).collect()
+    }
+
+    #[test]
+    fn test_swap_and_invert_channels() {
+        let id = uuid::Uuid::new_v4();
+        let input = std::env::temp_dir().join(format!("{id}_process_in.wav"));
+        let output = std::env::temp_dir().join(format!("{id}_process_out.wav"));
+        let frames: Vec<(i16, i16)> = (0..1000)
+            .map(|i| (i * 30, -(i * 7) - 1))
+            .chain([(i16::MIN, i16::MAX), (i16::MAX, i16::MIN)])
+            .collect();
+        write_stereo(&input, &frames);
+
+        let swap = ChannelOps {
+            swap_channels: true,
+            ..ChannelOps::default()
+        };
+        let spec = process_file(&input, &output, swap).unwrap();
+        assert_eq!(spec.channels, 2);
+        let swapped = read_stereo(&output);
+        assert_eq!(swapped.len(), frames.len());
+        for (before, after) in frames.iter().zip(&swapped) {
+            assert_eq!(after.0, before.1);
+            assert_eq!(after.1, before.0);
+        }
+
+        let invert = ChannelOps {
+            invert_right: true,
+            ..ChannelOps::default()
+        };
+        process_file(&input, &output, invert).unwrap();
+        let inverted = read_stereo(&output);
+        assert_eq!(inverted[10], (300, 71));
+        // the most negative sample saturates rather than wrapping
+        assert_eq!(inverted[1001].1, i16::MAX);
+        assert!(frames.iter().zip(&inverted).all(|(a, b)| a.0 == b.0));
+
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_inverts_32_bit_files() {
+        let id = uuid::Uuid::new_v4();
+        let input = std::env::temp_dir().join(format!("{id}_process_32_in.wav"));
+        let output = std::env::temp_dir().join(format!("{id}_process_32_out.wav"));
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate: 44100,
+            bits_per_sample: 32,
+            sample_format: SampleFormat::Int,
+        };
+        let mut writer = WavWriter::create(&input, spec).unwrap();
+        for sample in [5, -7, 0, i32::MIN] {
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let invert = ChannelOps {
+            invert_right: true,
+            ..ChannelOps::default()
+        };
+        process_file(&input, &output, invert).unwrap();
+        let samples: Vec<i32> = WavReader::open(&output)
+            .unwrap()
+            .samples::<i32>()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(samples, [5, 7, 0, i32::MAX]);
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+}
@@ -0,0 +1,125 @@
+//! Tuned comb-filter resonators for `--resonators`.
+//!
+//! Each resonator is a feedback delay line one period of its note long, so
+//! anything fed into it rings at that pitch and its harmonics, Karplus-Strong
+//! style. The feedback is set from a decay time rather than directly, so low
+//! and high notes die away over the same number of seconds.
+
+/// Feedback comb filter ringing at one frequency
+#[derive(Debug, Clone)]
+struct Comb {
+    buffer: Vec<f32>,
+    write: usize,
+    /// Delay in samples; fractional, so the pitch isn't rounded to a whole period
+    delay: f32,
+    feedback: f32,
+    /// Gain that brings the resonance of white noise back to the input level
+    gain: f32,
+}
+
+impl Comb {
+    fn new(freq: f32, decay: f32, sample_rate: u32) -> Comb {
+        let sr = sample_rate as f32;
+        let delay = (sr / freq.max(1.0)).max(1.0);
+        // -60 dB after `decay` seconds, `decay * freq` trips round the loop
+        let feedback = 10f32.powf(-3.0 * delay / (sr * decay.max(1e-3)));
+        Comb {
+            buffer: vec![0.0; delay as usize + 2],
+            write: 0,
+            delay,
+            feedback,
+            gain: (1.0 - feedback * feedback).sqrt(),
+        }
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        let len = self.buffer.len();
+        // linear interpolation between the two samples around `delay`
+        let back = self.delay.floor() as usize;
+        let frac = self.delay - back as f32;
+        let a = self.buffer[(self.write + len - back) % len];
+        let b = self.buffer[(self.write + len - back - 1) % len];
+        let output = input + self.feedback * (a + (b - a) * frac);
+        self.buffer[self.write] = output;
+        self.write = (self.write + 1) % len;
+        output * self.gain
+    }
+}
+
+/// A bank of resonators for each stereo channel, mixed in parallel
+#[derive(Debug, Clone, Default)]
+pub struct ResonatorBank {
+    left: Vec<Comb>,
+    right: Vec<Comb>,
+}
+
+impl ResonatorBank {
+    /// Resonators at each of `freqs` Hz, ringing for `decay` seconds
+    pub fn new(freqs: &[f32], decay: f32, sample_rate: u32) -> ResonatorBank {
+        let combs: Vec<Comb> = freqs
+            .iter()
+            .map(|&freq| Comb::new(freq, decay, sample_rate))
+            .collect();
+        ResonatorBank {
+            left: combs.clone(),
+            right: combs,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.left.is_empty()
+    }
+
+    /// Passes one stereo sample through the bank, averaging the resonators
+    pub fn process(&mut self, (left, right): (f32, f32)) -> (f32, f32) {
+        let scale = (self.left.len().max(1) as f32).recip();
+        let mix = |combs: &mut [Comb], input: f32| {
+            combs
+                .iter_mut()
+                .map(|comb| comb.process(input))
+                .sum::<f32>()
+                * scale
+        };
+        (mix(&mut self.left, left), mix(&mut self.right, right))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use std::f32::consts::PI;
+
+    /// Power of `samples` at `freq` Hz, from a single DFT bin
+    fn power_at(samples: &[f32], freq: f32, sample_rate: f32) -> f32 {
+        let (re, im) = samples
+            .iter()
+            .enumerate()
+            .fold((0.0, 0.0), |(re, im), (i, &s)| {
+                let phase = 2.0 * PI * freq * i as f32 / sample_rate;
+                (re + s * phase.cos(), im + s * phase.sin())
+            });
+        (re * re + im * im) / samples.len() as f32
+    }
+
+    #[test]
+    fn test_noise_rings_at_the_tuned_pitch() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let mut bank = ResonatorBank::new(&[261.63], 2.0, 44100);
+        let output: Vec<f32> = (0..44100)
+            .map(|_| bank.process((rng.random_range(-0.1..0.1), 0.0)).0)
+            .collect();
+
+        let peak = power_at(&output, 261.63, 44100.0);
+        let between = power_at(&output, 261.63 * 1.5, 44100.0);
+        let below = power_at(&output, 200.0, 44100.0);
+        assert!(peak > between * 20.0, "{peak} vs {between}");
+        assert!(peak > below * 20.0, "{peak} vs {below}");
+        // the second harmonic rings too
+        assert!(power_at(&output, 523.26, 44100.0) > between * 20.0);
+
+        // and the level stays near the input noise rather than blowing up
+        let rms = (output.iter().map(|s| s * s).sum::<f32>() / output.len() as f32).sqrt();
+        assert!(rms > 0.01 && rms < 0.2, "{rms}");
+    }
+}
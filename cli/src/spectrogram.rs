@@ -0,0 +1,112 @@
+//! `--spectrogram`: a PNG heatmap of the rendered mix.
+//!
+//! The mono sum is cut into Hann-windowed frames `hop` samples apart, and
+//! each frame's magnitude spectrum becomes one column of the image, low
+//! frequencies at the bottom. Levels are drawn over a fixed range below the
+//! loudest bin, so filters, noise colour and FM sidebands are easy to compare
+//! between renders.
+
+use rustfft::{num_complex::Complex, FftPlanner};
+use std::f32::consts::PI;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+/// Smallest FFT window accepted; anything shorter isn't worth looking at
+pub const MIN_WINDOW: usize = 16;
+
+/// Levels this far below the loudest bin are drawn black
+const RANGE_DB: f32 = 96.0;
+
+/// Magnitude spectra in dB of each frame, `window / 2 + 1` bins per frame
+pub fn stft(samples: &[f32], window: usize, hop: usize) -> Vec<Vec<f32>> {
+    let window = window.max(MIN_WINDOW);
+    let hop = hop.max(1);
+    let fft = FftPlanner::new().plan_fft_forward(window);
+    let hann: Vec<f32> = (0..window)
+        .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / window as f32).cos())
+        .collect();
+    let frames = samples.len().saturating_sub(window) / hop + 1;
+    let mut buffer = vec![Complex::default(); window];
+    (0..frames)
+        .map(|frame| {
+            let start = frame * hop;
+            for (i, bin) in buffer.iter_mut().enumerate() {
+                let sample = samples.get(start + i).copied().unwrap_or(0.0);
+                *bin = Complex::new(sample * hann[i], 0.0);
+            }
+            fft.process(&mut buffer);
+            buffer[..=window / 2]
+                .iter()
+                .map(|bin| 20.0 * (bin.norm() + 1e-12).log10())
+                .collect()
+        })
+        .collect()
+}
+
+/// Maps 0.0..=1.0 onto black, purple, orange and pale yellow
+fn heat(level: f32) -> [u8; 3] {
+    const STOPS: [[f32; 3]; 4] = [
+        [0.0, 0.0, 0.0],
+        [120.0, 30.0, 140.0],
+        [240.0, 120.0, 30.0],
+        [255.0, 250.0, 200.0],
+    ];
+    let x = level.clamp(0.0, 1.0) * (STOPS.len() - 1) as f32;
+    let i = (x as usize).min(STOPS.len() - 2);
+    let t = x - i as f32;
+    std::array::from_fn(|c| (STOPS[i][c] + (STOPS[i + 1][c] - STOPS[i][c]) * t) as u8)
+}
+
+/// Writes the spectrogram of `samples` to `path` as an RGB PNG with one
+/// column per frame and one row per frequency bin
+pub fn write(
+    path: impl AsRef<Path>,
+    samples: &[f32],
+    window: usize,
+    hop: usize,
+) -> Result<(), png::EncodingError> {
+    let frames = stft(samples, window, hop);
+    let (width, height) = (frames.len(), frames[0].len());
+    let max = frames
+        .iter()
+        .flatten()
+        .fold(f32::MIN, |max, &db| max.max(db));
+
+    let mut pixels = Vec::with_capacity(width * height * 3);
+    for row in (0..height).rev() {
+        for frame in &frames {
+            pixels.extend(heat(1.0 - (max - frame[row]) / RANGE_DB));
+        }
+    }
+
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&pixels)?;
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sine_peaks_at_its_bin() {
+        // 1 kHz at 8 kHz lands exactly on bin 32 of a 256-point window
+        let sine: Vec<f32> = (0..4000)
+            .map(|i| (2.0 * PI * 1000.0 * i as f32 / 8000.0).sin())
+            .collect();
+        let frames = stft(&sine, 256, 128);
+        assert_eq!(frames.len(), (4000 - 256) / 128 + 1);
+        for frame in &frames {
+            assert_eq!(frame.len(), 129);
+            let loudest = (0..frame.len())
+                .max_by(|&a, &b| frame[a].total_cmp(&frame[b]))
+                .unwrap();
+            assert_eq!(loudest, 32);
+        }
+    }
+}
@@ -0,0 +1,45 @@
+//! Smooth deterministic noise for `--noise-type value`.
+//!
+//! Value noise: a random value at each point of a regular lattice in time,
+//! taken from a hash of the seed and the point's index rather than an RNG,
+//! with a smooth curve between neighbouring points. A few octaves of it are
+//! summed, each twice as fast and half as loud as the last, so the texture
+//! has detail at several scales while staying continuous. Any sample can be
+//! computed on its own, so the noise is the same however the render is split.
+
+/// Lattice rate of the slowest octave in Hz
+const BASE_RATE: f64 = 200.0;
+
+/// Number of octaves summed
+const OCTAVES: u32 = 4;
+
+/// Value noise at `time` seconds, within -1.0..=1.0
+pub fn value_noise(seed: u64, time: f64) -> f32 {
+    let (mut sum, mut amplitude, mut total) = (0.0, 1.0, 0.0);
+    for octave in 0..OCTAVES {
+        let position = time * BASE_RATE * (1 << octave) as f64;
+        let index = position.floor();
+        let octave_seed = seed ^ (octave as u64 + 1).wrapping_mul(0xA076_1D64_78BD_642F);
+        let a = lattice(octave_seed, index as i64);
+        let b = lattice(octave_seed, index as i64 + 1);
+        sum += (a + (b - a) * fade(position - index)) * amplitude;
+        total += amplitude;
+        amplitude *= 0.5;
+    }
+    (sum / total) as f32
+}
+
+/// Perlin's quintic easing, flat at both ends so the curve's slope is
+/// continuous across lattice points
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+/// Value in -1.0..1.0 at lattice point `index`, from a SplitMix64 hash
+fn lattice(seed: u64, index: i64) -> f64 {
+    let mut z = seed.wrapping_add((index as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^= z >> 31;
+    (z >> 11) as f64 / (1u64 << 52) as f64 - 1.0
+}
@@ -1,6 +1,8 @@
-use crate::{Duration, Dynamic, Note};
+use crate::{Duration, Dynamic, Mode, Note, PitchClass, Scale};
+use rand::Rng;
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Event {
     /// A musical note with pitch, duration, and dynamics
     Note(Note, Duration, Dynamic),
@@ -13,12 +15,367 @@ pub trait Generator {
     fn generate(&self) -> Vec<Event>;
 }
 
+/// Attack, decay, sustain and release shaping each note when events are
+/// played as audio, so notes fade in and out instead of clicking on and off.
+///
+/// Times are in seconds and `sustain` is a level from 0.0 to 1.0. The
+/// release begins when the note's duration ends and runs on past it, into a
+/// following rest or under the next note for a legato overlap.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NoteEnvelope {
+    pub attack: f32,
+    pub decay: f32,
+    pub sustain: f32,
+    pub release: f32,
+}
+
+impl Default for NoteEnvelope {
+    /// A 10 ms attack and 50 ms decay to 0.8, released over 100 ms
+    fn default() -> Self {
+        NoteEnvelope {
+            attack: 0.01,
+            decay: 0.05,
+            sustain: 0.8,
+            release: 0.1,
+        }
+    }
+}
+
+impl NoteEnvelope {
+    /// Level `elapsed` seconds after the start of a note held for `held`
+    /// seconds. A note let go before reaching its sustain releases from
+    /// wherever its attack or decay had got to, so it never jumps.
+    pub fn level(&self, elapsed: f32, held: f32) -> f32 {
+        if elapsed < 0.0 {
+            return 0.0;
+        }
+        let held = held.max(0.0);
+        if elapsed <= held {
+            return self.held_level(elapsed);
+        }
+        let released = elapsed - held;
+        if released >= self.release {
+            return 0.0;
+        }
+        self.held_level(held) * (1.0 - released / self.release)
+    }
+
+    /// How long a note held for `held` seconds sounds, its release included
+    pub fn length(&self, held: f32) -> f32 {
+        held.max(0.0) + self.release.max(0.0)
+    }
+
+    /// Level `elapsed` seconds into a note that's still held
+    fn held_level(&self, elapsed: f32) -> f32 {
+        let sustain = self.sustain.clamp(0.0, 1.0);
+        if elapsed < self.attack {
+            elapsed / self.attack
+        } else if elapsed < self.attack + self.decay {
+            1.0 - (1.0 - sustain) * (elapsed - self.attack) / self.decay
+        } else {
+            sustain
+        }
+    }
+}
+
+/// Snaps the onsets of `events`, played back to back, to the nearest
+/// multiple of `grid`, and resizes every event to fill the gap to the next.
+///
+/// Onsets are rounded from their exact cumulative time rather than one
+/// duration at a time, so rounding never drifts over a long sequence, and
+/// the total length is rounded the same way. A note that would shrink to
+/// nothing keeps one grid step and nudges what follows along; a rest that
+/// would is dropped. Events one grid step long get `grid` itself as their
+/// duration, longer ones a [`Duration::Custom`] multiple of it.
+pub fn quantize(events: &[Event], grid: Duration) -> Vec<Event> {
+    let step = grid.beats() as f64;
+    if step <= 0.0 || !step.is_finite() {
+        return events.to_vec();
+    }
+    let duration = |steps: i64| match steps {
+        1 => grid.clone(),
+        _ => Duration::Custom((steps as f64 * step) as f32),
+    };
+    let length = |event: &Event| match event {
+        Event::Note(_, duration, _) | Event::Rest(duration) => duration.beats() as f64,
+    };
+    let snap = |beats: f64| (beats / step).round() as i64;
+
+    let mut onsets = Vec::with_capacity(events.len() + 1);
+    let mut time = 0.0;
+    for event in events {
+        onsets.push(snap(time));
+        time += length(event);
+    }
+    onsets.push(snap(time));
+
+    let mut quantized = Vec::with_capacity(events.len());
+    // grid steps taken so far, ahead of the rounded onsets after a nudge
+    let mut cursor = 0;
+    for (i, event) in events.iter().enumerate() {
+        let end = onsets[i + 1].max(cursor);
+        match event {
+            Event::Note(note, _, dynamic) => {
+                let end = end.max(cursor + 1);
+                quantized.push(Event::Note(*note, duration(end - cursor), *dynamic));
+                cursor = end;
+            }
+            Event::Rest(_) if end > cursor => {
+                quantized.push(Event::Rest(duration(end - cursor)));
+                cursor = end;
+            }
+            Event::Rest(_) => {}
+        }
+    }
+    quantized
+}
+
+/// Swings `events`, played back to back: the first eighth of every beat is
+/// lengthened and the second shortened by the same amount.
+///
+/// `amount` moves the off-beat eighth from halfway through the beat (0.0,
+/// straight) to two thirds of the way (0.5, triplet swing), and as far as
+/// five sixths at 1.0; it's clamped to that range. Time is warped within
+/// each beat and beats themselves never move, so notes of a beat or more
+/// keep their length and the total is unchanged. Finer subdivisions stretch
+/// with the half of the beat they fall in.
+pub fn swing(events: &[Event], amount: f32) -> Vec<Event> {
+    let split = 0.5 + amount.clamp(0.0, 1.0) as f64 / 3.0;
+    let warp = |beats: f64| {
+        let (beat, frac) = (beats.floor(), beats.fract());
+        let frac = if frac < 0.5 {
+            frac * 2.0 * split
+        } else {
+            split + (frac - 0.5) * 2.0 * (1.0 - split)
+        };
+        beat + frac
+    };
+
+    let mut swung = Vec::with_capacity(events.len());
+    let mut time = 0.0;
+    for event in events {
+        let duration = match event {
+            Event::Note(_, duration, _) | Event::Rest(duration) => duration,
+        };
+        let end = time + duration.beats() as f64;
+        let length = warp(end) - warp(time);
+        let duration = if (length - duration.beats() as f64).abs() < 1e-6 {
+            duration.clone()
+        } else {
+            Duration::Custom(length as f32)
+        };
+        swung.push(match event {
+            Event::Note(note, _, dynamic) => Event::Note(*note, duration, *dynamic),
+            Event::Rest(_) => Event::Rest(duration),
+        });
+        time = end;
+    }
+    swung
+}
+
+/// Nudges every note of `events`, played back to back, up to
+/// `max_jitter_beats` earlier or later, at random.
+///
+/// A note played early cuts short whatever comes before it; one played late
+/// leaves a short rest in front of it, folded into the rest before if there
+/// is one. Rests themselves stay put. Each note moves less than half the way
+/// to its neighbours' onsets, so events never swap or shrink to nothing, and
+/// the last event still ends where it did, keeping the total length.
+pub fn humanize_timing(events: &[Event], max_jitter_beats: f32, rng: &mut impl Rng) -> Vec<Event> {
+    let length = |event: &Event| match event {
+        Event::Note(_, duration, _) | Event::Rest(duration) => duration.beats() as f64,
+    };
+    let jitter = max_jitter_beats.max(0.0) as f64;
+
+    let mut onsets = Vec::with_capacity(events.len() + 1);
+    let mut time = 0.0;
+    for event in events {
+        onsets.push(time);
+        time += length(event);
+    }
+    onsets.push(time);
+
+    let moved: Vec<f64> = (0..events.len())
+        .map(|i| {
+            let onset = onsets[i];
+            if !matches!(events[i], Event::Note(..)) {
+                return onset;
+            }
+            let before = i.checked_sub(1).map_or(0.0, |prev| onset - onsets[prev]);
+            let after = onsets[i + 1] - onset;
+            let (early, late) = (jitter.min(before / 2.0), jitter.min(after / 2.0));
+            if early + late > 0.0 {
+                onset + rng.random_range(-early..=late)
+            } else {
+                onset
+            }
+        })
+        .collect();
+
+    let resize = |duration: &Duration, beats: f64| {
+        if (beats - duration.beats() as f64).abs() < 1e-6 {
+            duration.clone()
+        } else {
+            Duration::Custom(beats as f32)
+        }
+    };
+    let mut humanized = Vec::with_capacity(events.len());
+    for (i, event) in events.iter().enumerate() {
+        let late = moved[i] - onsets[i];
+        if late > 0.0 {
+            match humanized.last_mut() {
+                Some(Event::Rest(rest)) => *rest = Duration::Custom(rest.beats() + late as f32),
+                _ => humanized.push(Event::Rest(Duration::Custom(late as f32))),
+            }
+        }
+        // up to the next onset, or its original one if that note was late
+        let end = moved
+            .get(i + 1)
+            .map_or(onsets[i + 1], |&next| next.min(onsets[i + 1]));
+        humanized.push(match event {
+            Event::Note(note, duration, dynamic) => {
+                Event::Note(*note, resize(duration, end - moved[i]), *dynamic)
+            }
+            Event::Rest(duration) => Event::Rest(resize(duration, end - moved[i])),
+        });
+    }
+    humanized
+}
+
+/// Krumhansl-Kessler probe-tone ratings of each pitch class, counted in
+/// semitones above the tonic, in a major key
+const MAJOR_PROFILE: [f32; 12] = [
+    6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88,
+];
+
+/// Krumhansl-Kessler probe-tone ratings in a minor key
+const MINOR_PROFILE: [f32; 12] = [
+    6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17,
+];
+
+/// Guesses the key of `events` with the Krumhansl-Schmuckler algorithm.
+///
+/// The time each pitch class sounds, in beats, is correlated with the
+/// major and minor key profiles rotated to each of the 12 tonics, and the
+/// best match wins: a major or natural minor scale. Relative keys share
+/// their notes, so which of the two comes out rests on the weight given to
+/// the tonic, third and fifth. Without any notes the answer is C major.
+pub fn detect_key(events: &[Event]) -> Scale {
+    let mut durations = [0.0f32; 12];
+    for event in events {
+        if let Event::Note(note, duration, _) = event {
+            durations[note.pitch_cls.midi_base() as usize] += duration.beats();
+        }
+    }
+
+    let mut best = (f32::MIN, PitchClass::C, Mode::Major);
+    for tonic in 0..12 {
+        for (profile, mode) in [
+            (&MAJOR_PROFILE, Mode::Major),
+            (&MINOR_PROFILE, Mode::NaturalMinor),
+        ] {
+            let rotated: Vec<f32> = (0..12).map(|pc| profile[(pc + 12 - tonic) % 12]).collect();
+            let score = correlation(&durations, &rotated);
+            if score > best.0 {
+                best = (score, PitchClass::from(tonic as u8), mode);
+            }
+        }
+    }
+    Scale {
+        root: best.1,
+        mode: best.2,
+    }
+}
+
+/// Pearson correlation of `a` and `b`, 0.0 when either is constant
+fn correlation(a: &[f32], b: &[f32]) -> f32 {
+    let mean = |xs: &[f32]| xs.iter().sum::<f32>() / xs.len() as f32;
+    let (mean_a, mean_b) = (mean(a), mean(b));
+    let (mut cov, mut var_a, mut var_b) = (0.0, 0.0, 0.0);
+    for (x, y) in a.iter().zip(b) {
+        let (dx, dy) = (x - mean_a, y - mean_b);
+        cov += dx * dy;
+        var_a += dx * dx;
+        var_b += dy * dy;
+    }
+    if var_a == 0.0 || var_b == 0.0 {
+        0.0
+    } else {
+        cov / (var_a * var_b).sqrt()
+    }
+}
+
+/// `pulses` onsets spread as evenly as possible over `steps` steps, the
+/// Euclidean rhythm E(pulses, steps).
+///
+/// Onsets play `note` and the other steps rest, every step lasting
+/// `duration`. E(3, 8) is the tresillo, `x..x..x.`, and E(5, 8) the cinquillo,
+/// `x.xx.xx.`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EuclideanRhythm {
+    pub pulses: usize,
+    pub steps: usize,
+    pub note: Note,
+    /// Length of each step
+    pub duration: Duration,
+    pub dynamic: Dynamic,
+}
+
+impl EuclideanRhythm {
+    /// E(`pulses`, `steps`) on `note`, played mezzo-forte; `pulses` is capped at `steps`
+    pub fn new(pulses: usize, steps: usize, note: Note, duration: Duration) -> Self {
+        EuclideanRhythm {
+            pulses: pulses.min(steps),
+            steps,
+            note,
+            duration,
+            dynamic: Dynamic::MezzoForte,
+        }
+    }
+
+    /// Whether each step is an onset, by Bjorklund's algorithm: start with
+    /// one group per onset and one per rest, then keep appending remainder
+    /// groups to the leading groups until at most one remainder is left.
+    pub fn pattern(&self) -> Vec<bool> {
+        let pulses = self.pulses.min(self.steps);
+        let mut groups = vec![vec![true]; pulses];
+        let mut remainders = vec![vec![false]; self.steps - pulses];
+        while remainders.len() > 1 && !groups.is_empty() {
+            let paired = groups.len().min(remainders.len());
+            let leftover = if groups.len() > paired {
+                groups.split_off(paired)
+            } else {
+                remainders.split_off(paired)
+            };
+            for (group, remainder) in groups.iter_mut().zip(remainders) {
+                group.extend(remainder);
+            }
+            remainders = leftover;
+        }
+        groups.into_iter().chain(remainders).flatten().collect()
+    }
+}
+
+impl Generator for EuclideanRhythm {
+    fn generate(&self) -> Vec<Event> {
+        self.pattern()
+            .into_iter()
+            .map(|onset| match onset {
+                true => Event::Note(self.note, self.duration.clone(), self.dynamic),
+                false => Event::Rest(self.duration.clone()),
+            })
+            .collect()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::{
         super::{Octave, PitchClass},
         *,
     };
+    use rand::{rngs::StdRng, SeedableRng};
 
     #[test]
     fn event_creation() {
@@ -46,4 +403,230 @@ mod tests {
             _ => panic!("Expected Rest event"),
         }
     }
+
+    fn euclid(pulses: usize, steps: usize) -> String {
+        let note = Note {
+            pitch_cls: PitchClass::C,
+            octave: Octave::new(4),
+        };
+        EuclideanRhythm::new(pulses, steps, note, Duration::Eighth)
+            .generate()
+            .iter()
+            .map(|event| match event {
+                Event::Note(..) => 'x',
+                Event::Rest(_) => '.',
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_quantize_to_a_grid() {
+        let c4 = Note {
+            pitch_cls: PitchClass::C,
+            octave: Octave::new(4),
+        };
+        let note = |beats| Event::Note(c4, Duration::Custom(beats), Dynamic::MezzoForte);
+        let beats = |events: &[Event]| -> f32 {
+            events
+                .iter()
+                .map(|e| match e {
+                    Event::Note(_, d, _) | Event::Rest(d) => d.beats(),
+                })
+                .sum()
+        };
+
+        let loose = [
+            note(0.48),
+            note(0.53),
+            Event::Rest(Duration::Custom(0.97)),
+            note(0.51),
+            note(1.51),
+        ];
+        let snapped = quantize(&loose, Duration::Eighth);
+        assert_eq!(beats(&snapped), 4.0);
+        assert_eq!(
+            snapped,
+            [
+                Event::Note(c4, Duration::Eighth, Dynamic::MezzoForte),
+                Event::Note(c4, Duration::Eighth, Dynamic::MezzoForte),
+                Event::Rest(Duration::Custom(1.0)),
+                Event::Note(c4, Duration::Eighth, Dynamic::MezzoForte),
+                Event::Note(c4, Duration::Custom(1.5), Dynamic::MezzoForte),
+            ]
+        );
+
+        // a grace note keeps a step, and the next note gives it up
+        let grace = quantize(&[note(0.1), note(0.9)], Duration::Eighth);
+        assert_eq!(grace.len(), 2);
+        assert_eq!(beats(&grace), 1.0);
+        assert!(quantize(&[Event::Rest(Duration::Custom(0.1))], Duration::Eighth).is_empty());
+
+        // rounding each 0.3 beats to 0.25 alone would lose 50 beats over 1000 notes
+        let long: Vec<Event> = (0..1000).map(|_| note(0.3)).collect();
+        let snapped = quantize(&long, Duration::Sixteenth);
+        assert_eq!(snapped.len(), 1000);
+        assert!((beats(&snapped) - 300.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_swing_pairs_eighths() {
+        let c4 = Note {
+            pitch_cls: PitchClass::C,
+            octave: Octave::new(4),
+        };
+        let note = |duration| Event::Note(c4, duration, Dynamic::MezzoForte);
+        let length = |event: &Event| match event {
+            Event::Note(_, d, _) | Event::Rest(d) => d.beats(),
+        };
+
+        let straight = [
+            note(Duration::Eighth),
+            note(Duration::Eighth),
+            note(Duration::Quarter),
+            note(Duration::Eighth),
+            Event::Rest(Duration::Eighth),
+        ];
+        let swung = swing(&straight, 0.33);
+        assert_eq!(swung.len(), straight.len());
+        let (long, short) = (length(&swung[0]), length(&swung[1]));
+        assert!((long - 0.61).abs() < 1e-6, "{long}");
+        assert!((short - 0.39).abs() < 1e-6, "{short}");
+        assert!((long + short - 1.0).abs() < 1e-6);
+        // the quarter note is left alone, and the rest swings like a note
+        assert_eq!(swung[2], straight[2]);
+        assert!((length(&swung[3]) - 0.61).abs() < 1e-6);
+        assert!(matches!(swung[4], Event::Rest(_)));
+        let total: f32 = swung.iter().map(length).sum();
+        assert!((total - 3.0).abs() < 1e-5);
+
+        // triplet swing, and no swing at all
+        let triplet = swing(&straight[..2], 0.5);
+        assert!((length(&triplet[0]) - 2.0 / 3.0).abs() < 1e-6);
+        assert_eq!(swing(&straight, 0.0), straight);
+
+        // a syncopated quarter, off the beat to off the beat, keeps its length
+        let syncopated = swing(&[note(Duration::Eighth), note(Duration::Quarter)], 0.5);
+        assert_eq!(syncopated[1], note(Duration::Quarter));
+    }
+
+    #[test]
+    fn test_humanize_timing_stays_in_bounds() {
+        let c4 = Note {
+            pitch_cls: PitchClass::C,
+            octave: Octave::new(4),
+        };
+        let note = |duration| Event::Note(c4, duration, Dynamic::MezzoForte);
+        let length = |event: &Event| match event {
+            Event::Note(_, d, _) | Event::Rest(d) => d.beats(),
+        };
+        // onsets of the notes alone
+        let note_onsets = |events: &[Event]| {
+            let mut time = 0.0;
+            let mut onsets = Vec::new();
+            for event in events {
+                if matches!(event, Event::Note(..)) {
+                    onsets.push(time);
+                }
+                time += length(event);
+            }
+            onsets
+        };
+
+        let mut rng = StdRng::seed_from_u64(12);
+        let events: Vec<Event> = (0..200)
+            .map(|i| match i % 5 {
+                0 => Event::Rest(Duration::Eighth),
+                1 => note(Duration::Sixteenth),
+                _ => note(Duration::Quarter),
+            })
+            .collect();
+        let humanized = humanize_timing(&events, 0.05, &mut rng);
+
+        let (before, after) = (note_onsets(&events), note_onsets(&humanized));
+        assert_eq!(before.len(), after.len());
+        assert!(before
+            .iter()
+            .zip(&after)
+            .all(|(b, a)| (a - b).abs() <= 0.05 + 1e-4));
+        assert!(before.iter().zip(&after).any(|(b, a)| (a - b).abs() > 0.01));
+        assert!(humanized.iter().all(|e| length(e) > 0.0));
+        let total = |events: &[Event]| events.iter().map(length).sum::<f32>();
+        assert!((total(&humanized) - total(&events)).abs() < 1e-3);
+
+        // the sixteenths can only move a thirty-second either way
+        let tight = humanize_timing(&vec![note(Duration::Sixteenth); 8], 1.0, &mut rng);
+        assert!(tight.iter().all(|e| length(e) > 0.0));
+        assert_eq!(humanize_timing(&events, 0.0, &mut rng), events);
+    }
+
+    #[test]
+    fn test_note_envelope_has_no_hard_edges() {
+        let envelope = NoteEnvelope::default();
+        let sr = 44100.0;
+        // one half-second note rendered with its tail
+        let held = 0.5;
+        let samples: Vec<f32> = (0..(envelope.length(held) * sr) as usize + 10)
+            .map(|i| envelope.level(i as f32 / sr, held))
+            .collect();
+        assert_eq!(samples[0], 0.0);
+        assert_eq!(*samples.last().unwrap(), 0.0);
+        assert!(samples.windows(2).all(|w| (w[1] - w[0]).abs() < 0.01));
+        // up over the attack, down over the release
+        let attack = (envelope.attack * sr) as usize;
+        assert!(samples[..attack].windows(2).all(|w| w[1] > w[0]));
+        let end = (held * sr) as usize;
+        assert!(samples[end + 1..end + 4000].windows(2).all(|w| w[1] < w[0]));
+        // the release rings on past the note, into whatever follows
+        assert!(envelope.level(held + 0.05, held) > 0.0);
+        assert_eq!(envelope.level(-0.01, held), 0.0);
+
+        // let go mid-attack, the release starts from where the attack got to
+        let short = 0.005;
+        assert!((envelope.level(short + 1e-4, short) - 0.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_detect_key() {
+        let melody = |notes: &str| -> Vec<Event> {
+            notes
+                .split_whitespace()
+                .map(|name| {
+                    let note: Note = name.parse().unwrap();
+                    Event::Note(note, Duration::Quarter, Dynamic::MezzoForte)
+                })
+                .collect()
+        };
+        let key = |root, mode| Scale { root, mode };
+
+        // nothing but the notes of the C major scale
+        let c_major = melody("C4 E4 G4 C5 B4 D5 G4 F4 A4 E4 D4 C4 G4 E4 C4");
+        let detected = detect_key(&c_major);
+        assert!(
+            detected == key(PitchClass::C, Mode::Major)
+                || detected == key(PitchClass::A, Mode::NaturalMinor),
+            "{detected:?}"
+        );
+
+        // a sharpened leading tone points to the minor key
+        let a_minor = melody("A3 C4 E4 A4 G#4 A4 E4 D4 C4 B3 G#3 A3");
+        assert_eq!(detect_key(&a_minor), key(PitchClass::A, Mode::NaturalMinor));
+        let g_major = melody("G3 B3 D4 G4 F#4 E4 D4 C4 B3 A3 F#3 G3");
+        assert_eq!(detect_key(&g_major), key(PitchClass::G, Mode::Major));
+        assert_eq!(
+            detect_key(&[Event::Rest(Duration::Whole)]),
+            key(PitchClass::C, Mode::Major)
+        );
+    }
+
+    #[test]
+    fn test_euclidean_rhythm() {
+        assert_eq!(euclid(3, 8), "x..x..x.");
+        assert_eq!(euclid(5, 8), "x.xx.xx.");
+        assert_eq!(euclid(4, 12), "x..x..x..x..");
+        assert_eq!(euclid(7, 16), "x..x.x.x..x.x.x.");
+        assert_eq!(euclid(0, 4), "....");
+        assert_eq!(euclid(4, 4), "xxxx");
+        assert_eq!(euclid(9, 4), "xxxx");
+        assert_eq!(euclid(0, 0), "");
+    }
 }
@@ -7,6 +7,88 @@
 
 pub mod events;
 pub mod melody;
+pub mod prelude;
+pub mod track;
+
+use rand::{distr::weighted::WeightedIndex, prelude::Distribution, Rng};
+use std::fmt;
+use std::str::FromStr;
+
+/// Error returned when a mode name, chord symbol or similar can't be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError(String);
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Error returned when per-degree weights can't be used to pick a scale note.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WeightsError {
+    /// One weight is needed per scale degree
+    Length { expected: usize, found: usize },
+    /// Weights were negative, not finite, or all zero
+    Invalid,
+}
+
+impl fmt::Display for WeightsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WeightsError::Length { expected, found } => {
+                write!(
+                    f,
+                    "expected {expected} weights, one per degree, found {found}"
+                )
+            }
+            WeightsError::Invalid => {
+                f.write_str("weights must be non-negative with at least one above zero")
+            }
+        }
+    }
+}
+
+impl std::error::Error for WeightsError {}
+
+/// Error returned when a custom mode's intervals don't make up one octave.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModeError {
+    /// The seven steps must add up to 12 semitones
+    Sum(u16),
+    /// A zero step would repeat the previous pitch class
+    ZeroStep,
+}
+
+impl fmt::Display for ModeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModeError::Sum(sum) => {
+                write!(f, "mode intervals sum to {sum} semitones instead of 12")
+            }
+            ModeError::ZeroStep => f.write_str("mode intervals must all be at least 1 semitone"),
+        }
+    }
+}
+
+impl std::error::Error for ModeError {}
+
+/// Error returned when a chord tone has no octave inside a voicing register.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VoicingError {
+    /// The chord tone that didn't fit
+    pub pitch_cls: PitchClass,
+}
+
+impl fmt::Display for VoicingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no {:?} lies within the register", self.pitch_cls)
+    }
+}
+
+impl std::error::Error for VoicingError {}
 
 /// A pitch class is a set of all pitches that are a whole number
 /// of octaves apart. For example, all C notes (C0, C1, C2, C4, etc.)
@@ -17,8 +99,10 @@ pub mod melody;
 ///
 /// Each pitch class maps can be mapped to (MIDI number % 12):
 /// - C = 0, C♯ = 1, D = 2, etc.
+///
 /// TODO: Handle enharmonic equivalents (e.g., C♯ vs D♭)
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PitchClass {
     /// C natural (0 semitones from C)
     C,
@@ -46,10 +130,20 @@ pub enum PitchClass {
     B,
 }
 
-impl Into<u8> for &PitchClass {
-    fn into(self) -> u8 {
+/// Writes the note name with a `#` for sharps, as in "F#".
+impl fmt::Display for PitchClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const NAMES: [&str; 12] = [
+            "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+        ];
+        f.write_str(NAMES[self.midi_base() as usize])
+    }
+}
+
+impl From<&PitchClass> for u8 {
+    fn from(value: &PitchClass) -> Self {
         use PitchClass::*;
-        match self {
+        match value {
             C => 0,
             Cs => 1,
             D => 2,
@@ -80,7 +174,7 @@ impl From<u8> for PitchClass {
             9 => PitchClass::A,
             10 => PitchClass::As,
             11 => PitchClass::B,
-            0 | _ => PitchClass::C,
+            _ => PitchClass::C,
         }
     }
 }
@@ -89,6 +183,55 @@ impl PitchClass {
     pub fn midi_base(&self) -> u8 {
         self.into()
     }
+
+    /// Returns the next pitch class clockwise on the circle of fifths,
+    /// a perfect fifth (7 semitones) up.
+    pub fn fifth_up(self) -> PitchClass {
+        PitchClass::from((self.midi_base() + 7) % 12)
+    }
+
+    /// Returns the next pitch class counter-clockwise on the circle of
+    /// fifths, a perfect fifth down (a fourth up).
+    pub fn fifth_down(self) -> PitchClass {
+        PitchClass::from((self.midi_base() + 5) % 12)
+    }
+
+    /// Splits a leading note name with an optional sharp (`#`, `♯`) or flat
+    /// (`b`, `♭`) off `s`, returning the pitch class and the rest of `s`.
+    fn split_prefix(s: &str) -> Result<(PitchClass, &str), ParseError> {
+        let mut chars = s.chars();
+        let natural: u8 = match chars.next().map(|c| c.to_ascii_uppercase()) {
+            Some('C') => 0,
+            Some('D') => 2,
+            Some('E') => 4,
+            Some('F') => 5,
+            Some('G') => 7,
+            Some('A') => 9,
+            Some('B') => 11,
+            _ => return Err(ParseError(format!("invalid note name in '{s}'"))),
+        };
+        let rest = chars.as_str();
+        let mut accidental = rest.chars();
+        let pitch = match accidental.next() {
+            Some('#' | '♯') => natural + 1,
+            Some('b' | '♭') => natural + 11,
+            _ => return Ok((PitchClass::from(natural), rest)),
+        };
+        Ok((PitchClass::from(pitch % 12), accidental.as_str()))
+    }
+}
+
+/// Parses a note name such as "C", "F#" or "Bb"; flats are read as their
+/// enharmonic sharps.
+impl FromStr for PitchClass {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match PitchClass::split_prefix(s.trim())? {
+            (pitch_cls, "") => Ok(pitch_cls),
+            _ => Err(ParseError(format!("invalid note name '{s}'"))),
+        }
+    }
 }
 
 /// Represents an octave in the musical pitch system.
@@ -103,13 +246,35 @@ impl PitchClass {
 /// - C4 to B4: MIDI 60-71 (middle octave, contains middle C)
 /// - C8 to G8: MIDI 108-127 (highest MIDI range)
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Octave(i8);
 
 impl Octave {
+    /// Lowest octave with MIDI notes (C-1 is MIDI 0)
+    pub const MIN: i8 = -1;
+    /// Highest octave with MIDI notes. Only C9 to G9 (MIDI 120-127) exist;
+    /// the notes above G9 in this octave are past the end of the MIDI range.
+    pub const MAX: i8 = 9;
+
+    /// Creates an octave without checking it against the MIDI range, so
+    /// notes built from it may have out-of-range MIDI numbers. Prefer
+    /// [`Octave::try_new`] or [`Octave::clamped`] for untrusted input.
     pub fn new(octave_num: i8) -> Self {
         Octave(octave_num)
     }
 
+    /// Creates an octave if it lies in the MIDI range, -1 to 9.
+    pub fn try_new(octave_num: i8) -> Option<Self> {
+        (Self::MIN..=Self::MAX)
+            .contains(&octave_num)
+            .then_some(Octave(octave_num))
+    }
+
+    /// Creates an octave, clamping it into the MIDI range, -1 to 9.
+    pub fn clamped(octave_num: i8) -> Self {
+        Octave(octave_num.clamp(Self::MIN, Self::MAX))
+    }
+
     /// Returns the octave number as a signed 8-bit integer.
     ///
     /// Negative octaves are valid in MIDI (e.g., C-1 = MIDI 0).
@@ -123,6 +288,7 @@ impl Octave {
 /// A note combines a [`PitchClass`] (which note: C, D, E, etc.) with
 /// an [`Octave`]  to create a specific pitch.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Note {
     /// The pitch class (C, D, E, F, G, A, B, and their sharps)
     pub pitch_cls: PitchClass,
@@ -130,19 +296,173 @@ pub struct Note {
     pub octave: Octave,
 }
 
-impl Into<u8> for Note {
-    fn into(self) -> u8 {
-        let midi_base: i16 = self.pitch_cls.midi_base().into();
-        let octave: i16 = ((self.octave.num() + 1) * 12).into();
+impl From<Note> for u8 {
+    fn from(note: Note) -> Self {
+        let midi_base: i16 = note.pitch_cls.midi_base().into();
+        let octave: i16 = ((note.octave.num() + 1) * 12).into();
 
         (midi_base + octave) as u8
     }
 }
 
+/// Writes scientific pitch notation, as in "A4" or "C#-1".
+impl fmt::Display for Note {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.pitch_cls, self.octave.num())
+    }
+}
+
+/// Parses a note name with its octave such as "C4", "F#2" or "Bb-1"; the
+/// octave must be in the MIDI range and flats are read as enharmonic sharps.
+impl FromStr for Note {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (pitch_cls, octave) = PitchClass::split_prefix(s.trim())?;
+        let octave = octave
+            .parse()
+            .ok()
+            .and_then(Octave::try_new)
+            .ok_or_else(|| ParseError(format!("invalid octave in note '{s}'")))?;
+        Ok(Note { pitch_cls, octave })
+    }
+}
+
 impl Note {
     pub fn as_midi_number(self) -> u8 {
         self.into()
     }
+
+    /// Returns the note's frequency in Hz in twelve-tone equal temperament,
+    /// tuned to A4 = 440 Hz.
+    pub fn frequency(self) -> f32 {
+        self.frequency_with_tuning(440.0)
+    }
+
+    /// Returns the equal-tempered note nearest to `hz` (A4 = 440 Hz) and how
+    /// far `hz` lies from it in cents, positive when `hz` is sharp.
+    ///
+    /// The note is clamped to the MIDI range, so anything below about 8 Hz
+    /// comes back as C-1 and anything above about 12.5 kHz as G9, with the
+    /// cents measured from that note.
+    pub fn nearest_from_frequency(hz: f32) -> (Note, f32) {
+        let exact = 69.0 + 12.0 * (hz / 440.0).log2();
+        let midi = if exact.is_nan() {
+            0
+        } else {
+            exact.round().clamp(0.0, 127.0) as u8
+        };
+        let note = Note {
+            pitch_cls: PitchClass::from(midi % 12),
+            octave: Octave((midi / 12) as i8 - 1),
+        };
+        (note, (exact - midi as f32) * 100.0)
+    }
+
+    /// Like [`Note::frequency`], with A4 tuned to `a4_hz` instead.
+    pub fn frequency_with_tuning(self, a4_hz: f32) -> f32 {
+        a4_hz * 2f32.powf((self.as_midi_number() as f32 - 69.0) / 12.0)
+    }
+
+    /// The same pitch class `delta` octaves up (or down when negative),
+    /// clamped to the MIDI range of octaves, -1 to 9.
+    pub fn shift_octave(self, delta: i8) -> Note {
+        let octave =
+            (self.octave.num() as i16 + delta as i16).clamp(Octave::MIN as i16, Octave::MAX as i16);
+        Note {
+            octave: Octave(octave as i8),
+            ..self
+        }
+    }
+
+    /// Returns the signed distance in semitones from this note to `other`,
+    /// positive when `other` is higher.
+    pub fn interval_to(&self, other: &Note) -> i16 {
+        other.as_midi_number() as i16 - self.as_midi_number() as i16
+    }
+}
+
+/// A simple interval, spanning at most an octave.
+///
+/// Larger distances are compound intervals: a simple interval plus some
+/// number of whole octaves, as returned by [`Interval::from_semitones`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interval {
+    /// 0 semitones
+    Unison,
+    /// 1 semitone
+    MinorSecond,
+    /// 2 semitones
+    MajorSecond,
+    /// 3 semitones
+    MinorThird,
+    /// 4 semitones
+    MajorThird,
+    /// 5 semitones
+    PerfectFourth,
+    /// 6 semitones (augmented fourth / diminished fifth)
+    Tritone,
+    /// 7 semitones
+    PerfectFifth,
+    /// 8 semitones
+    MinorSixth,
+    /// 9 semitones
+    MajorSixth,
+    /// 10 semitones
+    MinorSeventh,
+    /// 11 semitones
+    MajorSeventh,
+    /// 12 semitones
+    Octave,
+}
+
+impl Interval {
+    /// Classifies a semitone distance, ignoring its direction, as a simple
+    /// interval and the number of octaves added on top of it. A ninth (14
+    /// semitones) is a major second plus one octave; an octave itself is
+    /// still simple.
+    pub fn from_semitones(semitones: i16) -> (Interval, u16) {
+        use Interval::*;
+        let distance = semitones.unsigned_abs();
+        if distance == 0 {
+            return (Unison, 0);
+        }
+        let simple = match (distance - 1) % 12 + 1 {
+            1 => MinorSecond,
+            2 => MajorSecond,
+            3 => MinorThird,
+            4 => MajorThird,
+            5 => PerfectFourth,
+            6 => Tritone,
+            7 => PerfectFifth,
+            8 => MinorSixth,
+            9 => MajorSixth,
+            10 => MinorSeventh,
+            11 => MajorSeventh,
+            _ => Octave,
+        };
+        (simple, (distance - 1) / 12)
+    }
+
+    /// Returns the size of this interval in semitones.
+    pub fn semitones(self) -> u8 {
+        use Interval::*;
+        match self {
+            Unison => 0,
+            MinorSecond => 1,
+            MajorSecond => 2,
+            MinorThird => 3,
+            MajorThird => 4,
+            PerfectFourth => 5,
+            Tritone => 6,
+            PerfectFifth => 7,
+            MinorSixth => 8,
+            MajorSixth => 9,
+            MinorSeventh => 10,
+            MajorSeventh => 11,
+            Octave => 12,
+        }
+    }
 }
 
 /// Represents the duration of a musical note or rest.
@@ -159,8 +479,13 @@ impl Note {
 /// Duration modifiers:
 /// - **Dotted**: Adds half the duration (e.g., dotted quarter = 1.5 beats)
 /// - **Triplet**: Divides duration by 3 (e.g., quarter triplet = 1/3 beat)
+/// - **Tied**: Two durations held as one note (e.g., quarter tied to eighth = 1.5 beats)
 /// - **Custom**: Arbitrary duration in beats
-#[derive(Debug, Clone, Copy, PartialEq)]
+///
+/// The modifiers own their base durations, so they can wrap durations
+/// computed at runtime, such as a dotted `Custom(0.75)`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Duration {
     /// Whole note (4 beats in 4/4 time)
     Whole,
@@ -173,15 +498,36 @@ pub enum Duration {
     /// Sixteenth note (0.25 beats in 4/4 time)
     Sixteenth,
     /// Dotted duration (adds 50% to the base duration)
-    Dotted(&'static Duration),
+    Dotted(Box<Duration>),
     /// Triplet duration (divides duration by 3)
-    Triplet(&'static Duration),
+    Triplet(Box<Duration>),
+    /// Two durations tied together (their lengths add up)
+    Tied(Box<Duration>, Box<Duration>),
     /// Custom duration in beats (floating point)
     Custom(f32),
 }
 
+impl Duration {
+    /// Returns the length of this duration in quarter-note beats.
+    pub fn beats(&self) -> f32 {
+        use Duration::*;
+        match self {
+            Whole => 4.0,
+            Half => 2.0,
+            Quarter => 1.0,
+            Eighth => 0.5,
+            Sixteenth => 0.25,
+            Dotted(base) => base.beats() * 1.5,
+            Triplet(base) => base.beats() / 3.0,
+            Tied(first, second) => first.beats() + second.beats(),
+            Custom(beats) => *beats,
+        }
+    }
+}
+
 /// Represents dynamic markings that indicate the loudness/intensity of musical notes.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Dynamic {
     /// pp - Very soft (MIDI velocity ~16)
     Pianissimo,
@@ -199,9 +545,9 @@ pub enum Dynamic {
     Custom(u8),
 }
 
-impl Into<u8> for Dynamic {
-    fn into(self) -> u8 {
-        match self {
+impl From<Dynamic> for u8 {
+    fn from(dynamic: Dynamic) -> Self {
+        match dynamic {
             Dynamic::Pianissimo => 16,
             Dynamic::Piano => 32,
             Dynamic::MezzoPiano => 48,
@@ -217,6 +563,14 @@ impl Dynamic {
     pub fn as_midi_velocity(self) -> u8 {
         self.into()
     }
+
+    /// Returns the MIDI velocity moved by a random amount within `±amount`,
+    /// clamped to the valid 0-127 range, so repeated notes don't all strike
+    /// with the same force.
+    pub fn humanize(self, amount: u8, rng: &mut impl Rng) -> u8 {
+        let jitter = rng.random_range(-(amount as i16)..=amount as i16);
+        (self.as_midi_velocity() as i16 + jitter).clamp(0, 127) as u8
+    }
 }
 
 /// Represents musical modes, which define the interval patterns for scales.
@@ -225,6 +579,7 @@ impl Dynamic {
 /// - 1 = half step (e.g., C to C♯)
 /// - 2 = whole step (e.g., C to D)
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Mode {
     /// Ionian mode - same as Major (bright, happy)
     Ionian,
@@ -245,13 +600,13 @@ pub enum Mode {
     /// Locrian mode - diminished scale (dissonant, unstable)
     Locrian,
     /// Custom mode with user-defined intervals
-    Custom(&'static [u8; 7]),
+    Custom([u8; 7]),
 }
 
 impl Mode {
     /// Returns the interval pattern for this mode as an array of semitones.
     /// The pattern always contains 7 intervals that sum to 12 (one octave).
-    pub fn intervals(self) -> &'static [u8] {
+    pub fn intervals(&self) -> &[u8] {
         match self {
             Mode::Ionian | Mode::Major => &[2, 2, 1, 2, 2, 2, 1],
             Mode::Dorian => &[2, 1, 2, 2, 2, 1, 2],
@@ -263,6 +618,55 @@ impl Mode {
             Mode::Custom(intervals) => intervals,
         }
     }
+
+    /// Checks that the intervals span exactly one octave in seven non-zero
+    /// steps. The built-in modes always do; a custom mode that doesn't would
+    /// build a scale that wraps early or repeats pitch classes.
+    pub fn validate(&self) -> Result<(), ModeError> {
+        let intervals = self.intervals();
+        if intervals.contains(&0) {
+            return Err(ModeError::ZeroStep);
+        }
+        match intervals.iter().map(|&step| step as u16).sum() {
+            12 => Ok(()),
+            sum => Err(ModeError::Sum(sum)),
+        }
+    }
+}
+
+/// Parses a mode by name, ignoring case and any `_`, `-` or spaces, so
+/// "Dorian", "natural_minor" and "Natural Minor" are all accepted. "minor"
+/// is read as the natural minor. Custom modes have no name to parse.
+impl FromStr for Mode {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name: String = s
+            .chars()
+            .filter(|c| !matches!(c, '_' | '-' | ' '))
+            .collect::<String>()
+            .to_lowercase();
+        match name.as_str() {
+            "ionian" => Ok(Mode::Ionian),
+            "major" => Ok(Mode::Major),
+            "dorian" => Ok(Mode::Dorian),
+            "phrygian" => Ok(Mode::Phrygian),
+            "lydian" => Ok(Mode::Lydian),
+            "mixolydian" => Ok(Mode::Mixolydian),
+            "aeolian" => Ok(Mode::Aeolian),
+            "naturalminor" | "minor" => Ok(Mode::NaturalMinor),
+            "locrian" => Ok(Mode::Locrian),
+            _ => Err(ParseError(format!("unknown mode '{s}'"))),
+        }
+    }
+}
+
+impl TryFrom<&str> for Mode {
+    type Error = ParseError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        s.parse()
+    }
 }
 
 /// # Common Scales
@@ -272,6 +676,7 @@ impl Mode {
 /// - **G Major**: G, A, B, C, D, E, F♯ (one sharp)
 /// - **D Dorian**: D, E, F, G, A, B, C (minor scale with raised 6th)
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Scale {
     /// The root note (tonic) of the scale
     pub root: PitchClass,
@@ -283,6 +688,8 @@ impl Scale {
     /// Returns a vector containing the seven scale degrees (notes)
     /// starting from the root note and following the mode's interval
     /// pattern.
+    ///
+    /// The mode isn't checked; use [`Scale::try_notes`] for custom modes.
     pub fn notes(self, octave: Octave) -> Vec<Note> {
         let intervals = self.mode.intervals();
 
@@ -305,6 +712,196 @@ impl Scale {
 
         notes
     }
+
+    /// Like [`Scale::notes`], but fails instead of building a malformed scale
+    /// when the mode doesn't pass [`Mode::validate`].
+    pub fn try_notes(self, octave: Octave) -> Result<Vec<Note>, ModeError> {
+        self.mode.validate()?;
+        Ok(self.notes(octave))
+    }
+
+    /// Returns every note of the scale from `low` to `high` inclusive,
+    /// across as many octaves as that takes, in ascending order.
+    ///
+    /// Endpoints outside the scale are left out, and `low` above `high`
+    /// gives no notes.
+    pub fn notes_in_range(&self, low: Note, high: Note) -> Vec<Note> {
+        let classes: Vec<PitchClass> = self
+            .clone()
+            .notes(Octave::new(0))
+            .iter()
+            .map(|note| note.pitch_cls)
+            .collect();
+        // semitones above C-1, wide enough for any octave number
+        let semitones =
+            |note: Note| (note.octave.num() as i16 + 1) * 12 + note.pitch_cls.midi_base() as i16;
+        (semitones(low)..=semitones(high))
+            .map(|semitone| Note {
+                pitch_cls: PitchClass::from(semitone.rem_euclid(12) as u8),
+                octave: Octave::new((semitone.div_euclid(12) - 1) as i8),
+            })
+            .filter(|note| classes.contains(&note.pitch_cls))
+            .collect()
+    }
+
+    /// Returns the chord a Roman numeral names in this key, with its root in
+    /// octave 4.
+    ///
+    /// The numeral picks the scale degree its root sits on, and its case the
+    /// quality: "IV" is a major triad, "vi" a minor one. A trailing "°" (or
+    /// "o") makes a diminished triad, "+" an augmented one, "7" a dominant
+    /// or minor seventh by case, and "maj7" a major seventh. A leading "b" or
+    /// "#" lowers or raises the root a semitone off the scale, as in "bVII".
+    pub fn roman_chord(&self, numeral: &str) -> Result<Chord, ParseError> {
+        let s = numeral.trim();
+        let (shift, rest) = if let Some(rest) = s.strip_prefix(['b', '♭']) {
+            (11, rest)
+        } else if let Some(rest) = s.strip_prefix(['#', '♯']) {
+            (1, rest)
+        } else {
+            (0, s)
+        };
+        let letters = rest
+            .find(|c: char| !matches!(c, 'I' | 'V' | 'i' | 'v'))
+            .unwrap_or(rest.len());
+        let (roman, quality) = rest.split_at(letters);
+        let degree = match roman.to_ascii_uppercase().as_str() {
+            "I" => 0,
+            "II" => 1,
+            "III" => 2,
+            "IV" => 3,
+            "V" => 4,
+            "VI" => 5,
+            "VII" => 6,
+            _ => return Err(ParseError(format!("invalid Roman numeral '{numeral}'"))),
+        };
+        let major = roman.chars().all(|c| c.is_ascii_uppercase());
+        if !major && roman.chars().any(|c| c.is_ascii_uppercase()) {
+            return Err(ParseError(format!(
+                "mixed case in Roman numeral '{numeral}'"
+            )));
+        }
+        let kind = match (quality, major) {
+            ("", true) => ChordKind::Major,
+            ("", false) => ChordKind::Minor,
+            ("°" | "o", _) => ChordKind::Diminished,
+            ("+", _) => ChordKind::Augmented,
+            ("7", true) => ChordKind::Dominant7,
+            ("7", false) => ChordKind::Minor7,
+            ("maj7" | "M7", _) => ChordKind::Major7,
+            _ => return Err(ParseError(format!("unknown chord quality in '{numeral}'"))),
+        };
+        let notes = self.clone().notes(Octave(4));
+        let degree = notes
+            .get(degree)
+            .ok_or_else(|| ParseError(format!("no degree for '{numeral}' in this mode")))?;
+        Ok(Chord {
+            root: Note {
+                pitch_cls: PitchClass::from((degree.pitch_cls.midi_base() + shift) % 12),
+                octave: Octave(4),
+            },
+            kind,
+        })
+    }
+
+    /// Returns the chords of a comma-separated progression of Roman numerals
+    /// such as "I,vi,IV,V", see [`Scale::roman_chord`].
+    pub fn progression(&self, numerals: &str) -> Result<Vec<Chord>, ParseError> {
+        numerals
+            .split(',')
+            .map(|numeral| self.roman_chord(numeral))
+            .collect()
+    }
+
+    /// Returns the relative minor: the Aeolian scale a minor third below the
+    /// tonic, sharing every note with the major scale on this tonic.
+    ///
+    /// The scale's own mode is ignored, so for a non-major scale this is the
+    /// relative minor of its parallel major (D Dorian gives B Aeolian).
+    pub fn relative_minor(&self) -> Scale {
+        Scale {
+            root: PitchClass::from((self.root.midi_base() + 9) % 12),
+            mode: Mode::Aeolian,
+        }
+    }
+
+    /// Picks a random note of the scale in `octave`, choosing degree `i` with
+    /// probability proportional to `weights[i]`. Weighting the tonic and
+    /// dominant, for example, keeps random melodies anchored to the key.
+    pub fn weighted_random_note(
+        &self,
+        weights: &[f32],
+        rng: &mut impl Rng,
+        octave: Octave,
+    ) -> Result<Note, WeightsError> {
+        let notes = self.clone().notes(octave);
+        if weights.len() != notes.len() {
+            return Err(WeightsError::Length {
+                expected: notes.len(),
+                found: weights.len(),
+            });
+        }
+        let index = WeightedIndex::new(weights).map_err(|_| WeightsError::Invalid)?;
+        Ok(notes[index.sample(rng)])
+    }
+
+    /// Returns the closely related keys: the keys a fifth above and below in
+    /// the same mode, followed by the relative minor (or, for Aeolian and
+    /// natural minor scales, the relative major).
+    pub fn neighboring_keys(&self) -> Vec<Scale> {
+        let relative = match self.mode {
+            Mode::Aeolian | Mode::NaturalMinor => self.relative_major(),
+            _ => self.relative_minor(),
+        };
+        vec![
+            Scale {
+                root: self.root.fifth_up(),
+                mode: self.mode,
+            },
+            Scale {
+                root: self.root.fifth_down(),
+                mode: self.mode,
+            },
+            relative,
+        ]
+    }
+
+    /// Returns the relative major: the major scale a minor third above the
+    /// tonic, sharing every note with the natural minor scale on this tonic.
+    ///
+    /// Like [`Scale::relative_minor`] the mode is ignored, so this is the
+    /// relative major of the parallel minor.
+    pub fn relative_major(&self) -> Scale {
+        Scale {
+            root: PitchClass::from((self.root.midi_base() + 3) % 12),
+            mode: Mode::Major,
+        }
+    }
+
+    /// Returns the parallel scale in `mode`: the same tonic with a different
+    /// mode, as C Dorian is to C major. Chords borrowed from a parallel scale
+    /// are the usual source of modal interchange.
+    pub fn parallel(&self, mode: Mode) -> Scale {
+        Scale {
+            root: self.root,
+            mode,
+        }
+    }
+
+    /// Returns the degrees on which `other` has a different note, as
+    /// `(degree, this scale's note, other's note)` with degrees counted from
+    /// 0 at the tonic. Comparing parallel scales shows which notes a change
+    /// of mode alters.
+    pub fn altered_degrees(&self, other: &Scale) -> Vec<(usize, PitchClass, PitchClass)> {
+        let ours = self.clone().notes(Octave(4));
+        let theirs = other.clone().notes(Octave(4));
+        ours.iter()
+            .zip(&theirs)
+            .enumerate()
+            .filter(|(_, (a, b))| a.pitch_cls != b.pitch_cls)
+            .map(|(degree, (a, b))| (degree, a.pitch_cls, b.pitch_cls))
+            .collect()
+    }
 }
 
 /// Represents the quality/type of a chord, defining its harmonic character.
@@ -333,7 +930,8 @@ impl Scale {
 /// - 3 = minor third, 4 = major third
 /// - 7 = perfect fifth
 /// - 10 = minor seventh, 11 = major seventh
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ChordKind {
     /// Major triad - bright, happy (root, major 3rd, perfect 5th)
     Major,
@@ -350,7 +948,7 @@ pub enum ChordKind {
     /// Dominant seventh chord - bluesy, creates tension (major triad + minor 7th)
     Dominant7,
     /// Custom chord with user-defined intervals
-    Custom(&'static [u8]),
+    Custom(Vec<u8>),
 }
 
 impl ChordKind {
@@ -361,7 +959,7 @@ impl ChordKind {
     /// - 3 = minor third, 4 = major third
     /// - 7 = perfect fifth
     /// - 10 = minor seventh, 11 = major seventh
-    pub fn intervals(self) -> &'static [u8] {
+    pub fn intervals(&self) -> &[u8] {
         use ChordKind::*;
         match self {
             Major => &[0, 4, 7],
@@ -374,6 +972,54 @@ impl ChordKind {
             Custom(intervals) => intervals,
         }
     }
+
+    /// Returns how many notes the chord has, the root included.
+    pub fn note_count(&self) -> usize {
+        self.intervals().len()
+    }
+
+    /// Returns true for three-note chords.
+    pub fn is_triad(&self) -> bool {
+        self.note_count() == 3
+    }
+
+    /// Returns true for four-note chords, a triad with a seventh on top.
+    pub fn is_seventh(&self) -> bool {
+        self.note_count() == 4
+    }
+}
+
+/// Parses a chord quality as written after the root in a chord symbol.
+///
+/// Symbols are case sensitive so "M7" (major seventh) and "m7" (minor
+/// seventh) stay distinct; spelled-out names such as "min7" or "Major" are
+/// not. An empty quality is a major triad, as in "C".
+impl FromStr for ChordKind {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use ChordKind::*;
+        let kind = match s {
+            "" | "M" => Major,
+            "m" | "-" => Minor,
+            "°" | "o" => Diminished,
+            "+" => Augmented,
+            "M7" | "Δ" | "Δ7" => Major7,
+            "m7" | "-7" => Minor7,
+            "7" => Dominant7,
+            _ => match s.to_lowercase().as_str() {
+                "maj" | "major" => Major,
+                "min" | "minor" => Minor,
+                "dim" | "diminished" => Diminished,
+                "aug" | "augmented" => Augmented,
+                "maj7" | "major7" => Major7,
+                "min7" | "minor7" => Minor7,
+                "dom7" | "dominant7" => Dominant7,
+                _ => return Err(ParseError(format!("unknown chord quality '{s}'"))),
+            },
+        };
+        Ok(kind)
+    }
 }
 
 /// Represents a musical chord with a specific root note and chord quality.
@@ -394,6 +1040,7 @@ impl ChordKind {
 /// - **G7**: G + B + D + F (creates tension, wants to resolve to C)
 /// - **Dm7**: D + F + A + C (jazzy, smooth)
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Chord {
     /// The root note that forms the foundation of the chord
     pub root: Note,
@@ -423,6 +1070,94 @@ impl Chord {
             })
             .collect()
     }
+
+    /// Voices the chord inside the register from `low` to `high` (inclusive):
+    /// each chord tone is moved by whole octaves to its lowest position at or
+    /// above `low`, and the result is sorted by pitch. Inversions fall out
+    /// naturally, so A major between C4 and C5 becomes C♯4, E4, A4.
+    ///
+    /// Fails if any tone has no octave inside the register, which can only
+    /// happen when it spans less than an octave.
+    pub fn voicing_within(&self, low: Note, high: Note) -> Result<Vec<Note>, VoicingError> {
+        let (low, high) = (low.as_midi_number(), high.as_midi_number());
+        let mut voiced = self
+            .notes()
+            .into_iter()
+            .map(|note| {
+                let offset = (note.pitch_cls.midi_base() + 12 - low % 12) % 12;
+                let midi = low as u16 + offset as u16;
+                if midi > high as u16 {
+                    return Err(VoicingError {
+                        pitch_cls: note.pitch_cls,
+                    });
+                }
+                Ok(Note {
+                    pitch_cls: note.pitch_cls,
+                    octave: Octave(midi as i8 / 12 - 1),
+                })
+            })
+            .collect::<Result<Vec<Note>, _>>()?;
+        voiced.sort_by_key(|note| note.as_midi_number());
+        voiced.dedup();
+        Ok(voiced)
+    }
+
+    /// Returns the frequency in Hz of each note of the chord, tuned to
+    /// A4 = 440 Hz.
+    pub fn frequencies(&self) -> Vec<f32> {
+        self.frequencies_with_tuning(440.0)
+    }
+
+    /// Like [`Chord::frequencies`], with A4 tuned to `a4_hz` instead.
+    pub fn frequencies_with_tuning(&self, a4_hz: f32) -> Vec<f32> {
+        self.notes()
+            .into_iter()
+            .map(|note| note.frequency_with_tuning(a4_hz))
+            .collect()
+    }
+}
+
+/// Parses a chord symbol such as "C", "F#m", "Bbdim", "Cmaj7" or "G7": a
+/// root note followed by a [`ChordKind`] quality. The root is placed in
+/// octave 4.
+impl FromStr for Chord {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let symbol = s.trim();
+        let (pitch_cls, quality) = PitchClass::split_prefix(symbol)
+            .map_err(|_| ParseError(format!("invalid chord root in '{s}'")))?;
+        let kind = quality
+            .parse()
+            .map_err(|err| ParseError(format!("{err} in '{s}'")))?;
+        Ok(Chord {
+            root: Note {
+                pitch_cls,
+                octave: Octave(4),
+            },
+            kind,
+        })
+    }
+}
+
+/// Parses a key such as "D dorian", "F# minor" or "Bb": a root note
+/// followed by a [`Mode`] name, which defaults to major when left out. The
+/// two may also be joined by a colon, as in "C:major".
+impl FromStr for Scale {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (root, mode) = PitchClass::split_prefix(s.trim())
+            .map_err(|_| ParseError(format!("invalid scale root in '{s}'")))?;
+        let mode = mode.strip_prefix(':').unwrap_or(mode);
+        let mode = match mode.trim() {
+            "" => Mode::Major,
+            name => name
+                .parse()
+                .map_err(|err| ParseError(format!("{err} in '{s}'")))?,
+        };
+        Ok(Scale { root, mode })
+    }
 }
 
 pub type Key = Scale;
@@ -438,13 +1173,132 @@ pub type Key = Scale;
 /// - **2/4**: Two quarter-note beats per measure (march time)
 /// - **6/8**: Six eighth-note beats per measure (compound time)
 /// - **5/4**: Five quarter-note beats per measure (irregular meter)
+///
+/// [`TimeSignature::new`] checks that the signature makes sense; the tuple
+/// constructor takes any pair unchecked.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct TimeSignature(pub u8, pub u8);
 
+impl TimeSignature {
+    /// `num`/`den`, or `None` unless there's at least one beat and the beat
+    /// is a whole, half, quarter, eighth or sixteenth note
+    pub fn new(num: u8, den: u8) -> Option<TimeSignature> {
+        (num >= 1 && matches!(den, 1 | 2 | 4 | 8 | 16)).then_some(TimeSignature(num, den))
+    }
+
+    /// Length of a measure in quarter-note beats: 3.0 for both 3/4 and 6/8
+    pub fn beats_per_measure(&self) -> f32 {
+        self.0 as f32 * 4.0 / self.1.max(1) as f32
+    }
+}
+
 /// Represents the tempo (speed) of music in beats per minute (BPM).
+///
+/// [`Tempo::new`] and `From<u16>` keep the tempo within [`Tempo::MIN`] and
+/// [`Tempo::MAX`]. The tuple constructor takes any value unchecked; the
+/// conversions to seconds treat 0 BPM as 1 rather than dividing by zero.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Tempo(pub u16);
 
+impl Tempo {
+    /// Slowest tempo [`Tempo::new`] accepts
+    pub const MIN: u16 = 20;
+    /// Fastest tempo [`Tempo::new`] accepts
+    pub const MAX: u16 = 400;
+
+    /// A tempo of `bpm`, or `None` outside [`Self::MIN`]..=[`Self::MAX`]
+    pub fn new(bpm: u16) -> Option<Tempo> {
+        (Self::MIN..=Self::MAX).contains(&bpm).then_some(Tempo(bpm))
+    }
+
+    /// Returns how many times per second a note of `duration` repeats at this tempo.
+    ///
+    /// At 120 BPM a quarter note lasts half a second, so it repeats at 2 Hz.
+    pub fn frequency(self, duration: &Duration) -> f32 {
+        self.0 as f32 / 60.0 / duration.beats()
+    }
+
+    /// Length of one quarter-note beat in seconds
+    pub fn seconds_per_beat(self) -> f32 {
+        60.0 / self.0.max(1) as f32
+    }
+}
+
+/// Tempo changing over a piece: a list of `(beat, tempo)` breakpoints, each
+/// tempo holding from its beat until the next.
+///
+/// Gradual changes, accelerando or ritardando, are approximated with a
+/// breakpoint every beat or so. A map always has a breakpoint at beat 0, so a
+/// map of one entry is a constant tempo.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TempoMap {
+    /// Sorted by beat, the first at 0.0
+    breakpoints: Vec<(f32, Tempo)>,
+}
+
+impl TempoMap {
+    /// A constant `tempo`
+    pub fn new(tempo: Tempo) -> Self {
+        TempoMap {
+            breakpoints: vec![(0.0, tempo)],
+        }
+    }
+
+    /// Changes to `tempo` from `beat` on, replacing any change already at
+    /// that beat. Beats before 0 are taken as 0.
+    pub fn insert(&mut self, beat: f32, tempo: Tempo) {
+        let beat = beat.max(0.0);
+        match self.breakpoints.iter().position(|&(b, _)| b >= beat) {
+            Some(i) if self.breakpoints[i].0 == beat => self.breakpoints[i].1 = tempo,
+            Some(i) => self.breakpoints.insert(i, (beat, tempo)),
+            None => self.breakpoints.push((beat, tempo)),
+        }
+    }
+
+    /// Builder form of [`Self::insert`]
+    pub fn with_change(mut self, beat: f32, tempo: Tempo) -> Self {
+        self.insert(beat, tempo);
+        self
+    }
+
+    pub fn breakpoints(&self) -> &[(f32, Tempo)] {
+        &self.breakpoints
+    }
+
+    /// Tempo in effect at `beat`
+    pub fn tempo_at(&self, beat: f32) -> Tempo {
+        let index = self.breakpoints.partition_point(|&(b, _)| b <= beat);
+        self.breakpoints[index.saturating_sub(1)].1
+    }
+
+    /// Absolute time in seconds of `beat`, adding up the length of every
+    /// beat before it at the tempo it's played at
+    pub fn seconds_at(&self, beat: f32) -> f32 {
+        let mut seconds = 0.0;
+        for (i, &(start, tempo)) in self.breakpoints.iter().enumerate() {
+            let end = self.breakpoints.get(i + 1).map_or(f32::INFINITY, |b| b.0);
+            if beat <= end {
+                return seconds + (beat - start) * tempo.seconds_per_beat();
+            }
+            seconds += (end - start) * tempo.seconds_per_beat();
+        }
+        seconds
+    }
+}
+
+/// Clamps `bpm` into [`Tempo::MIN`]..=[`Tempo::MAX`]
+impl From<u16> for Tempo {
+    fn from(bpm: u16) -> Self {
+        Tempo(bpm.clamp(Tempo::MIN, Tempo::MAX))
+    }
+}
+
+impl From<Tempo> for TempoMap {
+    fn from(tempo: Tempo) -> Self {
+        TempoMap::new(tempo)
+    }
+}
+
 /// Represents common tempo markings used in music notation.
 pub enum TempoMarkings {
     /// Very slow tempo (40-60 BPM) - solemn, stately
@@ -481,9 +1335,9 @@ impl TempoMarkings {
     }
 }
 
-impl Into<Tempo> for TempoMarkings {
-    fn into(self) -> Tempo {
-        let (min_bpm, max_bpm) = self.bpm_range();
+impl From<TempoMarkings> for Tempo {
+    fn from(marking: TempoMarkings) -> Self {
+        let (min_bpm, max_bpm) = marking.bpm_range();
         // Use the average BPM for the tempo marking
         Tempo((min_bpm + max_bpm) / 2)
     }
@@ -505,6 +1359,44 @@ mod tests {
         assert_eq!(PitchClass::from(255), PitchClass::C);
     }
 
+    #[test]
+    fn test_circle_of_fifths() {
+        assert_eq!(PitchClass::C.fifth_up(), PitchClass::G);
+        assert_eq!(PitchClass::B.fifth_up(), PitchClass::Fs);
+        assert_eq!(PitchClass::C.fifth_down(), PitchClass::F);
+
+        let mut pitch = PitchClass::C;
+        let mut visited = Vec::new();
+        for _ in 0..12 {
+            visited.push(pitch);
+            pitch = pitch.fifth_up();
+        }
+        assert_eq!(pitch, PitchClass::C);
+        visited.sort_by_key(|p| p.midi_base());
+        visited.dedup();
+        assert_eq!(visited.len(), 12);
+
+        for _ in 0..12 {
+            pitch = pitch.fifth_down();
+        }
+        assert_eq!(pitch, PitchClass::C);
+    }
+
+    #[test]
+    fn test_neighboring_keys() {
+        let c_major = Scale {
+            root: PitchClass::C,
+            mode: Mode::Major,
+        };
+        let neighbors = c_major.neighboring_keys();
+        let roots: Vec<_> = neighbors.iter().map(|s| s.root).collect();
+        assert_eq!(roots, [PitchClass::G, PitchClass::F, PitchClass::A]);
+        assert_eq!(neighbors[2].mode, Mode::Aeolian);
+
+        let a_minor = c_major.relative_minor();
+        assert_eq!(a_minor.neighboring_keys()[2], c_major);
+    }
+
     #[test]
     fn test_octave_num() {
         let octave = Octave::new(4);
@@ -514,6 +1406,18 @@ mod tests {
         assert_eq!(negative_octave.num(), -1);
     }
 
+    #[test]
+    fn test_octave_range() {
+        assert_eq!(Octave::try_new(10), None);
+        assert_eq!(Octave::try_new(-2), None);
+        assert_eq!(Octave::try_new(9), Some(Octave::new(9)));
+        assert_eq!(Octave::try_new(-1), Some(Octave::new(-1)));
+
+        assert_eq!(Octave::clamped(20), Octave::new(9));
+        assert_eq!(Octave::clamped(-5), Octave::new(-1));
+        assert_eq!(Octave::clamped(4), Octave::new(4));
+    }
+
     #[test]
     fn test_note_to_midi_number() {
         // Middle C (C4)
@@ -547,6 +1451,67 @@ mod tests {
         assert_eq!(b9.as_midi_number(), 131);
     }
 
+    #[test]
+    fn test_note_frequency() {
+        let a4 = Note {
+            pitch_cls: PitchClass::A,
+            octave: Octave::new(4),
+        };
+        assert!((a4.frequency() - 440.0).abs() < 1e-3);
+
+        let middle_c = Note {
+            pitch_cls: PitchClass::C,
+            octave: Octave::new(4),
+        };
+        assert!((middle_c.frequency() - 261.6256).abs() < 1e-3);
+
+        let a3 = Note {
+            pitch_cls: PitchClass::A,
+            octave: Octave::new(3),
+        };
+        assert!((a3.frequency() - 220.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_note_interval() {
+        let c4 = Note {
+            pitch_cls: PitchClass::C,
+            octave: Octave::new(4),
+        };
+        let g4 = Note {
+            pitch_cls: PitchClass::G,
+            octave: Octave::new(4),
+        };
+        assert_eq!(c4.interval_to(&g4), 7);
+        assert_eq!(g4.interval_to(&c4), -7);
+        assert_eq!(Interval::from_semitones(7), (Interval::PerfectFifth, 0));
+        assert_eq!(Interval::from_semitones(-7), (Interval::PerfectFifth, 0));
+
+        // compound intervals
+        let d5 = Note {
+            pitch_cls: PitchClass::D,
+            octave: Octave::new(5),
+        };
+        assert_eq!(c4.interval_to(&d5), 14);
+        assert_eq!(Interval::from_semitones(14), (Interval::MajorSecond, 1));
+        assert_eq!(Interval::from_semitones(12), (Interval::Octave, 0));
+        assert_eq!(Interval::from_semitones(24), (Interval::Octave, 1));
+        assert_eq!(Interval::from_semitones(0), (Interval::Unison, 0));
+        assert_eq!(Interval::MinorThird.semitones(), 3);
+    }
+
+    #[test]
+    fn test_note_shift_octave() {
+        let c4: Note = "C4".parse().unwrap();
+        assert_eq!(c4.shift_octave(1), "C5".parse().unwrap());
+        assert_eq!(c4.shift_octave(-2), "C2".parse().unwrap());
+        assert_eq!(c4.shift_octave(0), c4);
+        assert_eq!(c4.interval_to(&c4.shift_octave(3)), 36);
+        // the octave stays in the MIDI range
+        assert_eq!(c4.shift_octave(100).octave.num(), Octave::MAX);
+        assert_eq!(c4.shift_octave(i8::MIN).octave.num(), Octave::MIN);
+    }
+
     #[test]
     fn test_dynamic_to_midi_velocity() {
         assert_eq!(Dynamic::Pianissimo.as_midi_velocity(), 16);
@@ -554,13 +1519,194 @@ mod tests {
         assert_eq!(Dynamic::Custom(100).as_midi_velocity(), 100);
     }
 
+    #[test]
+    fn test_dynamic_humanize() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let mut rng = StdRng::seed_from_u64(3);
+        let velocities: Vec<u8> = (0..500)
+            .map(|_| Dynamic::Forte.humanize(10, &mut rng))
+            .collect();
+        assert!(velocities.iter().all(|v| (70..=90).contains(v)));
+        assert!(velocities.iter().any(|&v| v != 80));
+
+        // jitter is clamped at both ends of the velocity range
+        for _ in 0..500 {
+            assert!(Dynamic::Custom(125).humanize(20, &mut rng) <= 127);
+            assert!(Dynamic::Custom(2).humanize(20, &mut rng) <= 22);
+        }
+        assert_eq!(Dynamic::Piano.humanize(0, &mut rng), 32);
+    }
+
     #[test]
     fn test_mode_intervals() {
         assert_eq!(Mode::Major.intervals(), &[2, 2, 1, 2, 2, 2, 1]);
-        let custom_intervals: &'static [u8; 7] = &[1, 1, 2, 2, 1, 3, 2];
+        let custom_intervals = [1, 1, 2, 2, 1, 3, 2];
         assert_eq!(Mode::Custom(custom_intervals).intervals(), custom_intervals);
     }
 
+    #[test]
+    fn test_custom_mode_validation() {
+        assert_eq!(Mode::Locrian.validate(), Ok(()));
+        assert_eq!(Mode::Custom([1, 1, 2, 2, 1, 3, 2]).validate(), Ok(()));
+        assert_eq!(
+            Mode::Custom([1, 1, 2, 2, 1, 2, 2]).validate(),
+            Err(ModeError::Sum(11))
+        );
+        assert_eq!(
+            Mode::Custom([0, 2, 2, 2, 2, 2, 2]).validate(),
+            Err(ModeError::ZeroStep)
+        );
+
+        let broken = Scale {
+            root: PitchClass::C,
+            mode: Mode::Custom([2, 2, 2, 2, 2, 2, 2]),
+        };
+        assert_eq!(broken.try_notes(Octave::new(4)), Err(ModeError::Sum(14)));
+        let valid = Scale {
+            root: PitchClass::C,
+            mode: Mode::Custom([2, 2, 2, 2, 2, 1, 1]),
+        };
+        assert_eq!(valid.try_notes(Octave::new(4)).unwrap().len(), 7);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let chord = Chord {
+            root: Note {
+                pitch_cls: PitchClass::Fs,
+                octave: Octave::new(3),
+            },
+            kind: ChordKind::Custom(vec![0, 3, 7, 14]),
+        };
+        let json = serde_json::to_string(&chord).unwrap();
+        assert_eq!(serde_json::from_str::<Chord>(&json).unwrap(), chord);
+
+        let scale = Scale {
+            root: PitchClass::D,
+            mode: Mode::Dorian,
+        };
+        let json = serde_json::to_string(&scale).unwrap();
+        assert_eq!(json, r#"{"root":"D","mode":"Dorian"}"#);
+        assert_eq!(serde_json::from_str::<Scale>(&json).unwrap(), scale);
+
+        let tied = Duration::Tied(
+            Box::new(Duration::Half),
+            Box::new(Duration::Dotted(Box::new(Duration::Quarter))),
+        );
+        let json = serde_json::to_string(&tied).unwrap();
+        assert_eq!(serde_json::from_str::<Duration>(&json).unwrap(), tied);
+    }
+
+    #[test]
+    fn test_nearest_from_frequency() {
+        let a4 = Note {
+            pitch_cls: PitchClass::A,
+            octave: Octave::new(4),
+        };
+        let (note, cents) = Note::nearest_from_frequency(440.0);
+        assert_eq!(note, a4);
+        assert!(cents.abs() < 1e-3);
+
+        let (note, cents) = Note::nearest_from_frequency(445.0);
+        assert_eq!(note, a4);
+        assert!((cents - 19.56).abs() < 0.01);
+
+        // a quarter tone flat of A4 is still closer to A4 than to G#4
+        let (note, cents) = Note::nearest_from_frequency(428.0);
+        assert_eq!(note, a4);
+        assert!(cents < -40.0);
+
+        let (lowest, _) = Note::nearest_from_frequency(2.0);
+        assert_eq!(lowest.as_midi_number(), 0);
+        assert_eq!(Note::nearest_from_frequency(0.0).0.as_midi_number(), 0);
+        let (highest, cents) = Note::nearest_from_frequency(30_000.0);
+        assert_eq!(highest.as_midi_number(), 127);
+        assert!(cents > 100.0);
+    }
+
+    #[test]
+    fn test_note_display() {
+        let note = |pitch_cls, octave| Note {
+            pitch_cls,
+            octave: Octave::new(octave),
+        };
+        assert_eq!(note(PitchClass::A, 4).to_string(), "A4");
+        assert_eq!(note(PitchClass::Fs, 2).to_string(), "F#2");
+        assert_eq!(note(PitchClass::C, -1).to_string(), "C-1");
+    }
+
+    #[test]
+    fn test_note_from_str() {
+        let note = |pitch_cls, octave| Note {
+            pitch_cls,
+            octave: Octave::new(octave),
+        };
+        assert_eq!("C4".parse(), Ok(note(PitchClass::C, 4)));
+        assert_eq!(" F#2 ".parse(), Ok(note(PitchClass::Fs, 2)));
+        assert_eq!("Bb-1".parse(), Ok(note(PitchClass::As, -1)));
+        for note in ["A4", "C#-1", "G9"] {
+            assert_eq!(note.parse::<Note>().unwrap().to_string(), note);
+        }
+        assert!("C".parse::<Note>().is_err());
+        assert!("C10".parse::<Note>().is_err());
+        assert!("H4".parse::<Note>().is_err());
+        assert!("C4x".parse::<Note>().is_err());
+    }
+
+    #[test]
+    fn test_scale_from_str() {
+        let d_dorian = Scale {
+            root: PitchClass::D,
+            mode: Mode::Dorian,
+        };
+        assert_eq!("D dorian".parse(), Ok(d_dorian.clone()));
+        assert_eq!(
+            "F# natural minor".parse::<Scale>().unwrap().mode,
+            Mode::NaturalMinor
+        );
+        assert_eq!("Bb".parse::<Scale>().unwrap().root, PitchClass::As);
+        assert_eq!("D:dorian".parse(), Ok(d_dorian));
+        assert!("H major".parse::<Scale>().is_err());
+        assert!("C bogus".parse::<Scale>().is_err());
+    }
+
+    #[test]
+    fn test_roman_chords() {
+        let chord = |symbol: &str| symbol.parse::<Chord>().unwrap();
+        let c_major: Scale = "C:major".parse().unwrap();
+        assert_eq!(
+            c_major.progression("I,vi,IV,V7").unwrap(),
+            [chord("C"), chord("Am"), chord("F"), chord("G7")]
+        );
+        assert_eq!(c_major.roman_chord("vii°"), Ok(chord("Bdim")));
+        assert_eq!(c_major.roman_chord(" bVII "), Ok(chord("Bb")));
+        assert_eq!(c_major.roman_chord("IVmaj7"), Ok(chord("Fmaj7")));
+
+        // degrees follow the mode
+        let a_minor: Scale = "A minor".parse().unwrap();
+        assert_eq!(a_minor.roman_chord("III+"), Ok(chord("C+")));
+        assert_eq!(a_minor.roman_chord("iv"), Ok(chord("Dm")));
+
+        for bad in ["VIII", "Iv", "", "I9", "X"] {
+            assert!(c_major.roman_chord(bad).is_err(), "{bad}");
+        }
+        assert!(c_major.progression("I,,V").is_err());
+    }
+
+    #[test]
+    fn test_mode_from_str() {
+        assert_eq!("Dorian".parse(), Ok(Mode::Dorian));
+        assert_eq!("dorian".parse(), Ok(Mode::Dorian));
+        assert_eq!("DORIAN".parse(), Ok(Mode::Dorian));
+        assert_eq!("natural_minor".parse(), Ok(Mode::NaturalMinor));
+        assert_eq!(Mode::try_from("Aeolian"), Ok(Mode::Aeolian));
+
+        let err = "bogus".parse::<Mode>().unwrap_err();
+        assert_eq!(err.to_string(), "unknown mode 'bogus'");
+    }
+
     #[test]
     fn test_scale_generation() {
         // C Major scale in octave 4
@@ -614,6 +1760,217 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_scale_notes_in_range() {
+        let note = |pitch_cls, octave| Note {
+            pitch_cls,
+            octave: Octave::new(octave),
+        };
+        let c_major = Scale {
+            root: PitchClass::C,
+            mode: Mode::Ionian,
+        };
+        let notes = c_major.notes_in_range(note(PitchClass::C, 4), note(PitchClass::C, 6));
+        assert_eq!(notes.len(), 15);
+        assert_eq!(notes[0], note(PitchClass::C, 4));
+        assert_eq!(notes[7], note(PitchClass::C, 5));
+        assert_eq!(notes[14], note(PitchClass::C, 6));
+        assert!(notes
+            .windows(2)
+            .all(|pair| pair[0].as_midi_number() < pair[1].as_midi_number()));
+
+        // out-of-scale endpoints are dropped, and the octave carries at B
+        let a_minor = Scale {
+            root: PitchClass::A,
+            mode: Mode::Aeolian,
+        };
+        assert_eq!(
+            a_minor.notes_in_range(note(PitchClass::Gs, 3), note(PitchClass::Cs, 4)),
+            [
+                note(PitchClass::A, 3),
+                note(PitchClass::B, 3),
+                note(PitchClass::C, 4)
+            ]
+        );
+        assert!(c_major
+            .notes_in_range(note(PitchClass::D, 5), note(PitchClass::C, 5))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_weighted_random_note() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let d_dorian = Scale {
+            root: PitchClass::D,
+            mode: Mode::Dorian,
+        };
+        let mut rng = StdRng::seed_from_u64(7);
+        let tonic_only = [1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0];
+        for _ in 0..100 {
+            let note = d_dorian
+                .weighted_random_note(&tonic_only, &mut rng, Octave::new(3))
+                .unwrap();
+            assert_eq!(note.pitch_cls, PitchClass::D);
+            assert_eq!(note.octave, Octave::new(3));
+        }
+
+        assert_eq!(
+            d_dorian.weighted_random_note(&[1.0; 5], &mut rng, Octave::new(3)),
+            Err(WeightsError::Length {
+                expected: 7,
+                found: 5
+            })
+        );
+        assert_eq!(
+            d_dorian.weighted_random_note(&[0.0; 7], &mut rng, Octave::new(3)),
+            Err(WeightsError::Invalid)
+        );
+    }
+
+    #[test]
+    fn test_relative_keys() {
+        let c_major = Scale {
+            root: PitchClass::C,
+            mode: Mode::Major,
+        };
+        let a_minor = Scale {
+            root: PitchClass::A,
+            mode: Mode::Aeolian,
+        };
+        assert_eq!(c_major.relative_minor(), a_minor);
+        assert_eq!(a_minor.relative_major(), c_major);
+
+        // relatives share their notes
+        let mut major_notes = c_major.clone().notes(Octave::new(4));
+        let mut minor_notes = a_minor.notes(Octave::new(4));
+        major_notes.sort_by_key(|n| n.pitch_cls.midi_base());
+        minor_notes.sort_by_key(|n| n.pitch_cls.midi_base());
+        assert_eq!(major_notes, minor_notes);
+    }
+
+    #[test]
+    fn test_parallel_scale() {
+        let c_major = Scale {
+            root: PitchClass::C,
+            mode: Mode::Major,
+        };
+        let c_minor = c_major.parallel(Mode::Aeolian);
+        assert_eq!(c_minor.root, PitchClass::C);
+        assert_eq!(c_minor.mode, Mode::Aeolian);
+
+        // the 3rd, 6th and 7th drop a semitone
+        use PitchClass::*;
+        assert_eq!(
+            c_major.altered_degrees(&c_minor),
+            [(2, E, Ds), (5, A, Gs), (6, B, As)]
+        );
+        assert_eq!(
+            c_major.altered_degrees(&c_major.parallel(Mode::Lydian)),
+            [(3, F, Fs)]
+        );
+        assert!(c_major.altered_degrees(&c_major).is_empty());
+    }
+
+    #[test]
+    fn test_chord_kind_size() {
+        assert!(ChordKind::Major.is_triad());
+        assert!(!ChordKind::Major.is_seventh());
+        assert_eq!(ChordKind::Major.note_count(), 3);
+        assert!(ChordKind::Major7.is_seventh());
+        assert!(!ChordKind::Major7.is_triad());
+        assert_eq!(ChordKind::Major7.note_count(), 4);
+
+        let ninth = ChordKind::Custom(vec![0, 4, 7, 10, 14]);
+        assert!(!ninth.is_triad() && !ninth.is_seventh());
+        assert_eq!(ninth.note_count(), 5);
+    }
+
+    #[test]
+    fn test_chord_kind_from_str() {
+        assert_eq!("maj".parse(), Ok(ChordKind::Major));
+        assert_eq!("".parse(), Ok(ChordKind::Major));
+        assert_eq!("m".parse(), Ok(ChordKind::Minor));
+        assert_eq!("min".parse(), Ok(ChordKind::Minor));
+        assert_eq!("M7".parse(), Ok(ChordKind::Major7));
+        assert_eq!("maj7".parse(), Ok(ChordKind::Major7));
+        assert_eq!("m7".parse(), Ok(ChordKind::Minor7));
+        assert_eq!("MIN7".parse(), Ok(ChordKind::Minor7));
+        assert_eq!("7".parse(), Ok(ChordKind::Dominant7));
+        assert_eq!("dom7".parse(), Ok(ChordKind::Dominant7));
+        assert_eq!("dim".parse(), Ok(ChordKind::Diminished));
+        assert_eq!("+".parse(), Ok(ChordKind::Augmented));
+
+        let err = "sus4".parse::<ChordKind>().unwrap_err();
+        assert_eq!(err.to_string(), "unknown chord quality 'sus4'");
+    }
+
+    #[test]
+    fn test_runtime_custom_chord() {
+        // e.g. intervals read from a config file
+        let intervals: Vec<u8> = "0, 4, 7, 14"
+            .split(',')
+            .map(|step| step.trim().parse().unwrap())
+            .collect();
+        let add9 = Chord {
+            root: Note {
+                pitch_cls: PitchClass::C,
+                octave: Octave::new(4),
+            },
+            kind: ChordKind::Custom(intervals),
+        };
+        assert_eq!(add9.kind.intervals(), &[0, 4, 7, 14]);
+        let notes = add9.notes();
+        assert_eq!(notes.len(), 4);
+        assert_eq!(notes[3].pitch_cls, PitchClass::D);
+        assert_eq!(notes[3].octave, Octave::new(5));
+
+        let mode: Vec<u8> = vec![2, 2, 2, 1, 2, 1, 2];
+        let scale = Scale {
+            root: PitchClass::C,
+            mode: Mode::Custom(mode.try_into().unwrap()),
+        };
+        assert_eq!(
+            scale.try_notes(Octave::new(4)).unwrap()[3].pitch_cls,
+            PitchClass::Fs
+        );
+    }
+
+    #[test]
+    fn test_chord_from_str() {
+        let c_major: Chord = "C".parse().unwrap();
+        assert_eq!(
+            c_major,
+            Chord {
+                root: Note {
+                    pitch_cls: PitchClass::C,
+                    octave: Octave::new(4),
+                },
+                kind: ChordKind::Major,
+            }
+        );
+
+        let b_flat_minor7: Chord = "Bbm7".parse().unwrap();
+        assert_eq!(b_flat_minor7.root.pitch_cls, PitchClass::As);
+        assert_eq!(b_flat_minor7.kind, ChordKind::Minor7);
+
+        assert_eq!(
+            "F#m".parse::<Chord>().unwrap().root.pitch_cls,
+            PitchClass::Fs
+        );
+        assert_eq!(
+            "Bbdim".parse::<Chord>().unwrap().kind,
+            ChordKind::Diminished
+        );
+        assert_eq!("G7".parse::<Chord>().unwrap().kind, ChordKind::Dominant7);
+        assert_eq!("Eb".parse::<PitchClass>(), Ok(PitchClass::Ds));
+
+        let err = "H7".parse::<Chord>().unwrap_err();
+        assert_eq!(err.to_string(), "invalid chord root in 'H7'");
+        let err = "Csus9".parse::<Chord>().unwrap_err();
+        assert_eq!(err.to_string(), "unknown chord quality 'sus9' in 'Csus9'");
+    }
+
     #[test]
     fn test_chord_notes_major() {
         let c_major = Chord {
@@ -638,6 +1995,52 @@ mod tests {
         assert_eq!(notes[2].octave, Octave::new(4));
     }
 
+    #[test]
+    fn test_chord_frequencies() {
+        let c_major: Chord = "C".parse().unwrap();
+        let expected = [261.63, 329.63, 392.00];
+        let freqs = c_major.frequencies();
+        assert_eq!(freqs.len(), 3);
+        for (freq, expected) in freqs.iter().zip(expected) {
+            assert!((freq - expected).abs() < 0.01);
+        }
+
+        // baroque pitch moves every note down by the same ratio
+        let baroque = c_major.frequencies_with_tuning(415.0);
+        for (low, freq) in baroque.iter().zip(&freqs) {
+            assert!((low / freq - 415.0 / 440.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_chord_voicing_within() {
+        let note = |pitch_cls, octave| Note {
+            pitch_cls,
+            octave: Octave::new(octave),
+        };
+        let a_major: Chord = "A".parse().unwrap();
+        // plain notes() spills into octave 5
+        assert!(a_major.notes().iter().any(|n| n.octave == Octave::new(5)));
+
+        let voiced = a_major
+            .voicing_within(note(PitchClass::C, 4), note(PitchClass::C, 5))
+            .unwrap();
+        assert_eq!(
+            voiced,
+            vec![
+                note(PitchClass::Cs, 4),
+                note(PitchClass::E, 4),
+                note(PitchClass::A, 4)
+            ]
+        );
+
+        // a window narrower than the chord can't hold every tone
+        let err = a_major
+            .voicing_within(note(PitchClass::D, 4), note(PitchClass::G, 4))
+            .unwrap_err();
+        assert_eq!(err.pitch_cls, PitchClass::A);
+    }
+
     #[test]
     fn test_chord_notes_major7() {
         // C Major7 chord
@@ -708,6 +2111,96 @@ mod tests {
         assert_eq!(c_notes[2].octave, Octave::new(4)); // G4 = MIDI 67
     }
 
+    #[test]
+    fn test_duration_beats() {
+        assert_eq!(Duration::Whole.beats(), 4.0);
+        assert_eq!(Duration::Eighth.beats(), 0.5);
+        assert_eq!(Duration::Dotted(Box::new(Duration::Quarter)).beats(), 1.5);
+        assert!((Duration::Triplet(Box::new(Duration::Quarter)).beats() - 1.0 / 3.0).abs() < 1e-6);
+        assert_eq!(Duration::Custom(3.0).beats(), 3.0);
+        assert_eq!(
+            Duration::Tied(Box::new(Duration::Quarter), Box::new(Duration::Eighth)).beats(),
+            1.5
+        );
+        // either side of a tie can itself be dotted, a triplet or another tie
+        let tied_dotted = Duration::Tied(
+            Box::new(Duration::Half),
+            Box::new(Duration::Dotted(Box::new(Duration::Quarter))),
+        );
+        assert_eq!(tied_dotted.beats(), 3.5);
+    }
+
+    #[test]
+    fn test_runtime_built_durations() {
+        let beats: f32 = "0.75".parse().unwrap();
+        let dotted = Duration::Dotted(Box::new(Duration::Custom(beats)));
+        assert_eq!(dotted.beats(), 1.125);
+        let triplet = Duration::Triplet(Box::new(dotted.clone()));
+        assert!((triplet.beats() - 0.375).abs() < 1e-6);
+        assert_eq!(Tempo(60).frequency(&dotted), 1.0 / 1.125);
+    }
+
+    #[test]
+    fn test_tempo_frequency() {
+        assert_eq!(Tempo(120).frequency(&Duration::Quarter), 2.0);
+        assert_eq!(Tempo(120).frequency(&Duration::Whole), 0.5);
+        assert_eq!(Tempo(60).frequency(&Duration::Eighth), 2.0);
+    }
+
+    #[test]
+    fn test_time_signature() {
+        assert_eq!(TimeSignature::new(7, 8), Some(TimeSignature(7, 8)));
+        assert_eq!(TimeSignature::new(4, 3), None);
+        assert_eq!(TimeSignature::new(0, 0), None);
+        assert_eq!(TimeSignature::new(0, 4), None);
+        assert_eq!(TimeSignature(6, 8).beats_per_measure(), 3.0);
+        assert_eq!(TimeSignature(4, 4).beats_per_measure(), 4.0);
+        assert_eq!(TimeSignature(7, 8).beats_per_measure(), 3.5);
+        assert_eq!(TimeSignature(3, 2).beats_per_measure(), 6.0);
+    }
+
+    #[test]
+    fn test_tempo_bounds() {
+        assert_eq!(Tempo::new(0), None);
+        assert_eq!(Tempo::new(120), Some(Tempo(120)));
+        assert_eq!(Tempo::new(1000), None);
+        assert_eq!(Tempo::from(0), Tempo(Tempo::MIN));
+        assert_eq!(Tempo::from(1000), Tempo(Tempo::MAX));
+        assert_eq!(Tempo(0).seconds_per_beat(), 60.0);
+
+        use TempoMarkings::*;
+        for marking in [Largo, Adagio, Andante, Moderato, Allegro, Presto] {
+            let tempo = marking.to_tempo();
+            assert_eq!(Tempo::new(tempo.0), Some(tempo));
+        }
+    }
+
+    #[test]
+    fn test_tempo_map_seconds() {
+        let constant = TempoMap::new(Tempo(90));
+        assert_eq!(constant.seconds_at(0.0), 0.0);
+        assert!((constant.seconds_at(6.0) - 4.0).abs() < 1e-6);
+
+        // 4 beats at 120 BPM take 2 s, then 4 at 60 BPM another 4 s
+        let map = TempoMap::new(Tempo(120)).with_change(4.0, Tempo(60));
+        assert_eq!(map.seconds_at(4.0), 2.0);
+        assert_eq!(map.seconds_at(8.0), 6.0);
+        assert_eq!(map.seconds_at(2.0), 1.0);
+        assert_eq!(map.tempo_at(3.9), Tempo(120));
+        assert_eq!(map.tempo_at(4.0), Tempo(60));
+
+        // out-of-order changes are sorted and a repeated beat replaces
+        let map = TempoMap::new(Tempo(60))
+            .with_change(8.0, Tempo(240))
+            .with_change(4.0, Tempo(30))
+            .with_change(8.0, Tempo(120));
+        assert_eq!(
+            map.breakpoints(),
+            [(0.0, Tempo(60)), (4.0, Tempo(30)), (8.0, Tempo(120))]
+        );
+        assert_eq!(map.seconds_at(10.0), 4.0 + 8.0 + 1.0);
+    }
+
     #[test]
     fn test_tempo_marking_conversion() {
         let marking = TempoMarkings::Allegro;
@@ -1,7 +1,359 @@
 //! TODO: Implement [Melody], [Rhythm], and [Meter] structs.
 
+use crate::events::{Event, Generator};
+use crate::{Duration, Dynamic, Note, Octave, Scale};
+use rand::{distr::weighted::WeightedIndex, prelude::Distribution, rngs::StdRng, SeedableRng};
+use std::collections::HashMap;
+
 pub struct Melody {}
 
 pub struct Rhythm {}
 
 pub struct Meter {}
+
+/// Note `degree` steps up `scale` from its root in `octave`, carrying into
+/// the neighbouring octaves past either end
+fn scale_degree(scale: &Scale, octave: Octave, degree: i32) -> Note {
+    let len = scale.mode.intervals().len().max(1) as i32;
+    let shift = degree.div_euclid(len);
+    let octave = Octave::clamped((octave.num() as i32 + shift).clamp(-128, 127) as i8);
+    scale.clone().notes(octave)[degree.rem_euclid(len) as usize]
+}
+
+/// What an L-system symbol plays once the string is read as a melody
+#[derive(Debug, Clone, PartialEq)]
+pub enum Symbol {
+    /// A note on a scale degree counted from the root of the melody's octave.
+    /// Degrees past the end of the scale (or below 0) carry into the
+    /// neighbouring octaves.
+    Note { degree: i32, duration: Duration },
+    /// A rest
+    Rest(Duration),
+}
+
+/// Melody grown by Lindenmayer rewriting.
+///
+/// Starting from the axiom, every symbol with a rule is replaced by its
+/// rule's expansion, `iterations` times over. The final string is then read
+/// left to right, each symbol with a [`Symbol`] mapping becoming a note or a
+/// rest; the rest are structural and play nothing. Since every expansion
+/// repeats the rules' patterns at a larger scale, the result is
+/// self-similar: motifs recur, stretched and nested inside one another.
+///
+/// ```
+/// use procsynth_core::melody::{LSystemMelody, Symbol};
+/// use procsynth_core::{events::Generator, Duration, Octave, Scale};
+///
+/// let melody = LSystemMelody::new("C major".parse().unwrap(), Octave::new(4), "A", 3)
+///     .rule('A', "AB")
+///     .rule('B', "A")
+///     .symbol('A', Symbol::Note { degree: 0, duration: Duration::Quarter })
+///     .symbol('B', Symbol::Note { degree: 4, duration: Duration::Eighth });
+/// assert_eq!(melody.expand(), "ABAAB");
+/// assert_eq!(melody.generate().len(), 5);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct LSystemMelody {
+    /// Scale the degrees are taken from
+    pub scale: Scale,
+    /// Octave of degree 0
+    pub octave: Octave,
+    /// Starting string
+    pub axiom: String,
+    /// Rewrite rules; symbols without one are copied unchanged
+    pub rules: HashMap<char, String>,
+    /// How symbols are played; symbols without one are silent
+    pub symbols: HashMap<char, Symbol>,
+    /// Number of rewriting passes
+    pub iterations: usize,
+    /// Dynamic of every note
+    pub dynamic: Dynamic,
+}
+
+impl LSystemMelody {
+    /// Longest string an expansion grows to; rewriting stops there, since
+    /// most rule sets grow exponentially with the iteration count
+    pub const MAX_SYMBOLS: usize = 1 << 16;
+
+    /// An L-system with no rules or symbols yet, played mezzo-forte
+    pub fn new(scale: Scale, octave: Octave, axiom: &str, iterations: usize) -> Self {
+        LSystemMelody {
+            scale,
+            octave,
+            axiom: axiom.to_string(),
+            rules: HashMap::new(),
+            symbols: HashMap::new(),
+            iterations,
+            dynamic: Dynamic::MezzoForte,
+        }
+    }
+
+    /// Adds the rule rewriting `from` into `to`
+    pub fn rule(mut self, from: char, to: &str) -> Self {
+        self.rules.insert(from, to.to_string());
+        self
+    }
+
+    /// Sets what `symbol` plays
+    pub fn symbol(mut self, symbol: char, plays: Symbol) -> Self {
+        self.symbols.insert(symbol, plays);
+        self
+    }
+
+    /// The string after every rewriting pass, capped at [`Self::MAX_SYMBOLS`]
+    pub fn expand(&self) -> String {
+        let mut current: Vec<char> = self.axiom.chars().collect();
+        for _ in 0..self.iterations {
+            let mut next = Vec::with_capacity(current.len() * 2);
+            for c in &current {
+                match self.rules.get(c) {
+                    Some(to) => next.extend(to.chars()),
+                    None => next.push(*c),
+                }
+                if next.len() >= Self::MAX_SYMBOLS {
+                    break;
+                }
+            }
+            next.truncate(Self::MAX_SYMBOLS);
+            current = next;
+        }
+        current.into_iter().collect()
+    }
+}
+
+impl Generator for LSystemMelody {
+    fn generate(&self) -> Vec<Event> {
+        let mut notes: HashMap<i32, Note> = HashMap::new();
+        self.expand()
+            .chars()
+            .filter_map(|c| self.symbols.get(&c))
+            .map(|symbol| match symbol {
+                Symbol::Note { degree, duration } => {
+                    let note = *notes
+                        .entry(*degree)
+                        .or_insert_with(|| scale_degree(&self.scale, self.octave, *degree));
+                    Event::Note(note, duration.clone(), self.dynamic)
+                }
+                Symbol::Rest(duration) => Event::Rest(duration.clone()),
+            })
+            .collect()
+    }
+}
+
+/// Melody that wanders up and down a scale a few degrees at a time.
+///
+/// Each step moves between 1 and `max_leap` degrees either way, never
+/// further than `range` degrees from the root of `octave`, where the walk
+/// starts. Steps are weighted by `1 / (1 + pull * distance)`, with `distance`
+/// how many degrees from the start they land, so the line keeps drifting back
+/// toward its centre: 0.0 is a plain random walk, and larger values hold it
+/// closer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RandomWalkMelody {
+    pub scale: Scale,
+    /// Octave whose root is the centre of the walk
+    pub octave: Octave,
+    /// Number of notes
+    pub length: usize,
+    /// Largest step in scale degrees
+    pub max_leap: u32,
+    /// Furthest the walk strays from its centre, in scale degrees
+    pub range: u32,
+    /// Strength of the tendency to return to the centre
+    pub pull: f32,
+    pub duration: Duration,
+    pub dynamic: Dynamic,
+    /// Seed of the walk; the same seed walks the same line
+    pub seed: u64,
+}
+
+impl RandomWalkMelody {
+    /// A walk of `length` quarter notes in steps of up to 2 degrees, within
+    /// an octave of its centre either way, played mezzo-forte
+    pub fn new(scale: Scale, octave: Octave, length: usize, seed: u64) -> Self {
+        RandomWalkMelody {
+            scale,
+            octave,
+            length,
+            max_leap: 2,
+            range: 7,
+            pull: 0.5,
+            duration: Duration::Quarter,
+            dynamic: Dynamic::MezzoForte,
+            seed,
+        }
+    }
+
+    /// Scale degrees of each note, relative to the centre
+    pub fn degrees(&self) -> Vec<i32> {
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let (leap, range) = (self.max_leap as i32, self.range as i32);
+        let mut degree = 0;
+        let mut degrees = Vec::with_capacity(self.length);
+        for _ in 0..self.length {
+            degrees.push(degree);
+            let steps: Vec<i32> = (-leap..=leap)
+                .filter(|&step| step != 0 && (degree + step).abs() <= range)
+                .collect();
+            let weights = steps
+                .iter()
+                .map(|step| 1.0 / (1.0 + self.pull.max(0.0) * (degree + step).abs() as f32));
+            // no possible step (a zero leap or range) holds the note
+            if let Ok(dist) = WeightedIndex::new(weights) {
+                degree += steps[dist.sample(&mut rng)];
+            }
+        }
+        degrees
+    }
+}
+
+impl Generator for RandomWalkMelody {
+    fn generate(&self) -> Vec<Event> {
+        self.degrees()
+            .into_iter()
+            .map(|degree| {
+                let note = scale_degree(&self.scale, self.octave, degree);
+                Event::Note(note, self.duration.clone(), self.dynamic)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Mode, PitchClass};
+
+    fn fibonacci(iterations: usize) -> LSystemMelody {
+        let scale = Scale {
+            root: PitchClass::D,
+            mode: Mode::Dorian,
+        };
+        LSystemMelody::new(scale, Octave::new(3), "A", iterations)
+            .rule('A', "AB")
+            .rule('B', "A")
+            .symbol(
+                'A',
+                Symbol::Note {
+                    degree: 0,
+                    duration: Duration::Quarter,
+                },
+            )
+            .symbol(
+                'B',
+                Symbol::Note {
+                    degree: 11,
+                    duration: Duration::Eighth,
+                },
+            )
+    }
+
+    #[test]
+    fn test_expansion_length() {
+        // the Fibonacci L-system grows by the Fibonacci numbers
+        for (iterations, len) in [(0, 1), (1, 2), (2, 3), (5, 13), (10, 144)] {
+            let melody = fibonacci(iterations);
+            assert_eq!(melody.expand().len(), len);
+            assert_eq!(melody.generate().len(), len);
+            assert_eq!(melody.generate(), melody.generate());
+        }
+        assert_eq!(fibonacci(4).expand(), "ABAABABA");
+        assert_eq!(fibonacci(100).expand().len(), LSystemMelody::MAX_SYMBOLS);
+    }
+
+    #[test]
+    fn test_notes_are_in_scale() {
+        let melody = fibonacci(6)
+            .rule('B', "A-C")
+            .symbol('-', Symbol::Rest(Duration::Sixteenth))
+            .symbol(
+                'C',
+                Symbol::Note {
+                    degree: -3,
+                    duration: Duration::Half,
+                },
+            );
+        let in_scale: Vec<PitchClass> = melody
+            .scale
+            .clone()
+            .notes(Octave::new(4))
+            .iter()
+            .map(|n| n.pitch_cls)
+            .collect();
+        let events = melody.generate();
+        assert!(events.iter().any(|e| matches!(e, Event::Rest(_))));
+        for event in &events {
+            if let Event::Note(note, _, _) = event {
+                assert!(in_scale.contains(&note.pitch_cls), "{note}");
+            }
+        }
+
+        // degree 11 is the fifth degree (A) an octave up, -3 the fifth below
+        let a = |octave| crate::Note {
+            pitch_cls: PitchClass::A,
+            octave: Octave::new(octave),
+        };
+        assert!(events
+            .iter()
+            .any(|e| matches!(e, Event::Note(n, Duration::Half, _) if *n == a(2))));
+        assert_eq!(
+            fibonacci(1).generate()[1],
+            Event::Note(a(4), Duration::Eighth, Dynamic::MezzoForte)
+        );
+    }
+
+    #[test]
+    fn test_random_walk_steps() {
+        let scale = Scale {
+            root: PitchClass::E,
+            mode: Mode::Phrygian,
+        };
+        let walk = RandomWalkMelody {
+            max_leap: 3,
+            ..RandomWalkMelody::new(scale.clone(), Octave::new(4), 200, 9)
+        };
+        let events = walk.generate();
+        assert_eq!(events.len(), 200);
+        assert_eq!(events, walk.generate());
+
+        // recover each note's degree from its position among all scale notes
+        let ladder: Vec<u8> = (Octave::MIN..=Octave::MAX)
+            .flat_map(|octave| scale.clone().notes(Octave::new(octave)))
+            .map(Note::as_midi_number)
+            .collect();
+        let positions: Vec<usize> = events
+            .iter()
+            .map(|event| match event {
+                Event::Note(note, _, _) => ladder
+                    .iter()
+                    .position(|&midi| midi == note.as_midi_number())
+                    .unwrap(),
+                Event::Rest(_) => panic!("a walk never rests"),
+            })
+            .collect();
+        for pair in positions.windows(2) {
+            let leap = pair[0].abs_diff(pair[1]);
+            assert!((1..=3).contains(&leap), "{leap}");
+        }
+        let centre = positions[0];
+        assert!(positions.iter().all(|p| p.abs_diff(centre) <= 7));
+
+        // a stronger pull keeps the line closer to its centre
+        let spread = |pull| {
+            let walk = RandomWalkMelody {
+                pull,
+                ..RandomWalkMelody::new(scale.clone(), Octave::new(4), 2000, 1)
+            };
+            walk.degrees().iter().map(|d| d.abs()).sum::<i32>()
+        };
+        assert!(spread(4.0) < spread(0.0));
+        assert_eq!(
+            RandomWalkMelody {
+                max_leap: 0,
+                ..RandomWalkMelody::new(scale, Octave::new(4), 3, 0)
+            }
+            .degrees(),
+            [0, 0, 0]
+        );
+    }
+}
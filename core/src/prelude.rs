@@ -0,0 +1,25 @@
+//! Glob import for the commonly used types and traits.
+//!
+//! ```
+//! use procsynth_core::prelude::*;
+//!
+//! let note = Note {
+//!     pitch_cls: PitchClass::A,
+//!     octave: Octave::new(4),
+//! };
+//! let scale = Scale {
+//!     root: PitchClass::A,
+//!     mode: Mode::Aeolian,
+//! };
+//! assert!(scale.notes(Octave::new(4)).contains(&note));
+//!
+//! let event = Event::Note(note, Duration::Quarter, Dynamic::MezzoPiano);
+//! assert!(matches!(event, Event::Note(..)));
+//! ```
+
+pub use crate::events::{Event, Generator};
+pub use crate::track::Track;
+pub use crate::{
+    Chord, ChordKind, Duration, Dynamic, Interval, Key, Mode, ModeError, Note, Octave, ParseError,
+    PitchClass, Scale, Tempo, TempoMap, TempoMarkings, TimeSignature, VoicingError, WeightsError,
+};
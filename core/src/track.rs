@@ -0,0 +1,323 @@
+//! Polyphony: several monophonic [`Event`] lines played together, and their
+//! export as a Standard MIDI File.
+
+use crate::events::Event;
+use crate::TempoMap;
+use std::io::{self, Write};
+
+/// Several voices, each a monophonic sequence of events, all starting
+/// together.
+///
+/// Voices are aligned by [`Duration::beats`](crate::Duration::beats), so
+/// notes from different voices sound at once wherever their onsets line up.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Track {
+    pub voices: Vec<Vec<Event>>,
+}
+
+/// A note of a [`Track`] placed in time, in MIDI ticks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimedNote {
+    /// Index of the voice the note belongs to in [`Track::voices`]
+    pub voice: usize,
+    /// MIDI channel of that voice, shared by voices 15 apart
+    pub channel: u8,
+    /// MIDI note number
+    pub key: u8,
+    pub velocity: u8,
+    pub start: u32,
+    pub end: u32,
+}
+
+impl Track {
+    /// Resolution of exported MIDI, in ticks per quarter note
+    pub const TICKS_PER_QUARTER: u16 = 480;
+
+    pub fn new(voices: Vec<Vec<Event>>) -> Self {
+        Track { voices }
+    }
+
+    /// Adds a voice that starts with the others
+    pub fn push_voice(&mut self, events: Vec<Event>) {
+        self.voices.push(events);
+    }
+
+    /// Length of the longest voice in beats
+    pub fn beats(&self) -> f32 {
+        self.voices
+            .iter()
+            .map(|voice| voice.iter().map(|event| event_beats(event) as f32).sum())
+            .fold(0.0, f32::max)
+    }
+
+    /// MIDI channel of voice `index`: one per voice in order, skipping
+    /// channel 10 (9 counting from 0), which General MIDI keeps for drums,
+    /// and wrapping once all 15 others are taken
+    pub fn channel(index: usize) -> u8 {
+        let channel = (index % 15) as u8;
+        if channel >= 9 {
+            channel + 1
+        } else {
+            channel
+        }
+    }
+
+    /// Every note of every voice, sorted by start time then channel.
+    ///
+    /// Times are rounded from each onset's exact position in beats rather
+    /// than summed tick by tick, so voices never drift apart.
+    pub fn notes(&self) -> Vec<TimedNote> {
+        let mut notes = Vec::new();
+        for (index, voice) in self.voices.iter().enumerate() {
+            let mut time = 0.0;
+            for event in voice {
+                let length = event_beats(event);
+                if let Event::Note(note, _, dynamic) = event {
+                    let (start, end) = (ticks(time), ticks(time + length));
+                    if end > start {
+                        notes.push(TimedNote {
+                            voice: index,
+                            channel: Self::channel(index),
+                            key: note.as_midi_number().min(127),
+                            velocity: dynamic.as_midi_velocity().clamp(1, 127),
+                            start,
+                            end,
+                        });
+                    }
+                }
+                time += length;
+            }
+        }
+        notes.sort_by_key(|note| (note.start, note.channel));
+        notes
+    }
+
+    /// Writes the track as a format 1 Standard MIDI File at `tempo`, a
+    /// [`Tempo`](crate::Tempo) or a [`TempoMap`]: a tempo track followed by
+    /// one track per voice, each on its own channel
+    pub fn write_midi(&self, tempo: impl Into<TempoMap>, mut writer: impl Write) -> io::Result<()> {
+        let tracks = self.voices.len() as u16 + 1;
+        writer.write_all(b"MThd")?;
+        writer.write_all(&6u32.to_be_bytes())?;
+        writer.write_all(&1u16.to_be_bytes())?;
+        writer.write_all(&tracks.to_be_bytes())?;
+        writer.write_all(&Self::TICKS_PER_QUARTER.to_be_bytes())?;
+
+        let mut data = Vec::new();
+        let mut last = 0;
+        for &(beat, tempo) in tempo.into().breakpoints() {
+            let tick = ticks(beat as f64);
+            write_var_len(&mut data, tick - last);
+            let micros = (60_000_000 / tempo.0.max(1) as u32).to_be_bytes();
+            data.extend([0xFF, 0x51, 3, micros[1], micros[2], micros[3]]);
+            last = tick;
+        }
+        write_chunk(&mut writer, &data)?;
+
+        let notes = self.notes();
+        for index in 0..self.voices.len() {
+            let channel = Self::channel(index);
+            // (tick, is note on, key, velocity); offs sort before ons at the
+            // same tick so repeated notes retrigger cleanly. Voices past the
+            // fifteenth share a channel, so pick notes by voice.
+            let mut messages: Vec<(u32, bool, u8, u8)> = notes
+                .iter()
+                .filter(|note| note.voice == index)
+                .flat_map(|note| {
+                    [
+                        (note.start, true, note.key, note.velocity),
+                        (note.end, false, note.key, 0),
+                    ]
+                })
+                .collect();
+            messages.sort_by_key(|&(tick, on, _, _)| (tick, on));
+
+            let mut data = Vec::new();
+            let mut last = 0;
+            for (tick, on, key, velocity) in messages {
+                write_var_len(&mut data, tick - last);
+                let status = if on { 0x90 } else { 0x80 };
+                data.extend([status | channel, key, velocity]);
+                last = tick;
+            }
+            write_chunk(&mut writer, &data)?;
+        }
+        Ok(())
+    }
+
+    /// The track as the bytes of a Standard MIDI File, see [`Self::write_midi`]
+    pub fn to_midi(&self, tempo: impl Into<TempoMap>) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.write_midi(tempo, &mut bytes)
+            .expect("writing to a Vec can't fail");
+        bytes
+    }
+}
+
+impl From<Vec<Vec<Event>>> for Track {
+    fn from(voices: Vec<Vec<Event>>) -> Self {
+        Track::new(voices)
+    }
+}
+
+/// `beats` in MIDI ticks
+fn ticks(beats: f64) -> u32 {
+    (beats * Track::TICKS_PER_QUARTER as f64).round() as u32
+}
+
+fn event_beats(event: &Event) -> f64 {
+    match event {
+        Event::Note(_, duration, _) | Event::Rest(duration) => duration.beats() as f64,
+    }
+}
+
+/// Writes an `MTrk` chunk holding `events`, closed with an end of track event
+fn write_chunk(writer: &mut impl Write, events: &[u8]) -> io::Result<()> {
+    const END_OF_TRACK: [u8; 4] = [0, 0xFF, 0x2F, 0];
+    writer.write_all(b"MTrk")?;
+    writer.write_all(&((events.len() + END_OF_TRACK.len()) as u32).to_be_bytes())?;
+    writer.write_all(events)?;
+    writer.write_all(&END_OF_TRACK)
+}
+
+/// Appends `value` as a MIDI variable-length quantity: 7 bits per byte, most
+/// significant first, with the high bit set on all but the last
+fn write_var_len(bytes: &mut Vec<u8>, value: u32) {
+    let mut groups = vec![(value & 0x7F) as u8];
+    let mut rest = value >> 7;
+    while rest > 0 {
+        groups.push((rest & 0x7F) as u8 | 0x80);
+        rest >>= 7;
+    }
+    bytes.extend(groups.iter().rev());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Duration, Dynamic, Note, Octave, PitchClass, Tempo};
+
+    fn note(pitch_cls: PitchClass, octave: i8, duration: Duration) -> Event {
+        let note = Note {
+            pitch_cls,
+            octave: Octave::new(octave),
+        };
+        Event::Note(note, duration, Dynamic::MezzoForte)
+    }
+
+    /// (tick, channel, key) of every note on in a format 1 file
+    fn note_ons(bytes: &[u8]) -> Vec<(u32, u8, u8)> {
+        assert_eq!(&bytes[..4], b"MThd");
+        let mut ons = Vec::new();
+        let mut pos = 14;
+        while pos < bytes.len() {
+            assert_eq!(&bytes[pos..pos + 4], b"MTrk");
+            let len = u32::from_be_bytes(bytes[pos + 4..pos + 8].try_into().unwrap()) as usize;
+            let chunk = &bytes[pos + 8..pos + 8 + len];
+            let (mut i, mut tick) = (0, 0);
+            while i < chunk.len() {
+                let mut delta = 0;
+                loop {
+                    delta = (delta << 7) | (chunk[i] & 0x7F) as u32;
+                    i += 1;
+                    if chunk[i - 1] & 0x80 == 0 {
+                        break;
+                    }
+                }
+                tick += delta;
+                match chunk[i] {
+                    0xFF => i += 3 + chunk[i + 2] as usize,
+                    status => {
+                        if status & 0xF0 == 0x90 {
+                            ons.push((tick, status & 0x0F, chunk[i + 1]));
+                        }
+                        i += 3;
+                    }
+                }
+            }
+            pos += 8 + len;
+        }
+        ons.sort();
+        ons
+    }
+
+    #[test]
+    fn test_two_voices_export_together() {
+        use PitchClass::*;
+        let melody = vec![
+            note(E, 5, Duration::Quarter),
+            note(D, 5, Duration::Eighth),
+            note(C, 5, Duration::Eighth),
+            Event::Rest(Duration::Quarter),
+            note(G, 5, Duration::Quarter),
+        ];
+        let bass = vec![note(C, 3, Duration::Half), note(G, 2, Duration::Half)];
+        let track = Track::new(vec![melody, bass]);
+        assert_eq!(track.beats(), 4.0);
+
+        let bytes = track.to_midi(Tempo(120));
+        // format 1, a tempo track and one per voice, 480 ticks per quarter
+        assert_eq!(&bytes[8..14], &[0, 1, 0, 3, 0x01, 0xE0]);
+        // 500,000 microseconds per quarter note
+        assert_eq!(&bytes[22..29], &[0, 0xFF, 0x51, 3, 0x07, 0xA1, 0x20]);
+
+        assert_eq!(
+            note_ons(&bytes),
+            [
+                (0, 0, 76),
+                (0, 1, 48),
+                (480, 0, 74),
+                (720, 0, 72),
+                (960, 1, 43),
+                (1440, 0, 79),
+            ]
+        );
+        let notes = track.notes();
+        assert_eq!(notes.len(), 6);
+        assert!(notes.iter().all(|n| n.velocity == 64 && n.end > n.start));
+        assert_eq!((notes[1].start, notes[1].end), (0, 960));
+    }
+
+    #[test]
+    fn test_var_len_and_channels() {
+        for (value, bytes) in [
+            (0, vec![0x00]),
+            (0x7F, vec![0x7F]),
+            (0x80, vec![0x81, 0x00]),
+            (0x3FFF, vec![0xFF, 0x7F]),
+            (0x0FFF_FFFF, vec![0xFF, 0xFF, 0xFF, 0x7F]),
+        ] {
+            let mut out = Vec::new();
+            write_var_len(&mut out, value);
+            assert_eq!(out, bytes);
+        }
+        let channels: Vec<u8> = (0..17).map(Track::channel).collect();
+        assert!(!channels[..15].contains(&9));
+        assert_eq!(&channels[8..11], &[8, 10, 11]);
+        assert_eq!(&channels[15..], &[0, 1]);
+    }
+
+    #[test]
+    fn test_voices_sharing_a_channel_keep_their_own_tracks() {
+        // seventeen voices, so voices 15 and 16 wrap onto channels 0 and 1
+        let voices: Vec<Vec<Event>> = (0..17)
+            .map(|i| vec![note(PitchClass::from(i as u8), 4, Duration::Quarter)])
+            .collect();
+        let track = Track::new(voices);
+        assert_eq!(track.notes().len(), 17);
+        let bytes = track.to_midi(Tempo(120));
+        assert_eq!(note_ons(&bytes).len(), 17);
+
+        // after the header and tempo track, every voice's chunk holds one
+        // note on and one note off
+        let mut pos = 14 + 8 + u32::from_be_bytes(bytes[18..22].try_into().unwrap()) as usize;
+        for voice in 0..17 {
+            assert_eq!(&bytes[pos..pos + 4], b"MTrk");
+            let len = u32::from_be_bytes(bytes[pos + 4..pos + 8].try_into().unwrap()) as usize;
+            assert_eq!(len, 13, "voice {voice}");
+            pos += 8 + len;
+        }
+        assert_eq!(pos, bytes.len());
+    }
+}